@@ -1,6 +1,8 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use nsm::{from_mode, Nsm, NsmError, NsmMode};
+use std::sync::OnceLock;
 use system::{dmesg, SystemError};
 
 // Referenced from: https://git.distrust.co/public/enclaveos/src/branch/master/src/aws/src/lib.rs
@@ -25,33 +27,27 @@ fn nitro_heartbeat() {
     dmesg(format!("Sent NSM heartbeat"));
 }
 
+// NSM device, or the mock NSM with the `mock-nsm` feature, created on first use
+fn nsm() -> Result<&'static dyn Nsm, NsmError> {
+    static NSM: OnceLock<Box<dyn Nsm>> = OnceLock::new();
+    if let Some(nsm) = NSM.get() {
+        return Ok(nsm.as_ref());
+    }
+    #[cfg(feature = "mock-nsm")]
+    let mode = NsmMode::Mock;
+    #[cfg(not(feature = "mock-nsm"))]
+    let mode = NsmMode::Nitro;
+    let nsm = from_mode(mode, Default::default())?;
+    Ok(NSM.get_or_init(|| nsm).as_ref())
+}
+
 // Get entropy sample from Nitro device
 pub fn get_entropy(size: usize) -> Result<Vec<u8>, SystemError> {
-    use nsm_api::api::ErrorCode;
-    use nsm_lib::{nsm_get_random, nsm_lib_init};
-    let nsm_fd = nsm_lib_init();
-    if nsm_fd < 0 {
-        return Err(SystemError {
-            message: String::from("Failed to connect to NSM device"),
-        });
-    };
-    let mut dest = Vec::with_capacity(size);
-    while dest.len() < size {
-        let mut buf = [0u8; 256];
-        let mut buf_len = buf.len();
-        let status = unsafe { nsm_get_random(nsm_fd, buf.as_mut_ptr(), &mut buf_len) };
-        match status {
-            ErrorCode::Success => {
-                dest.extend_from_slice(&buf);
-            }
-            _ => {
-                return Err(SystemError {
-                    message: String::from("Failed to get entropy from NSM device"),
-                });
-            }
-        };
-    }
-    Ok(dest)
+    nsm()
+        .and_then(|nsm| nsm.random(size))
+        .map_err(|e| SystemError {
+            message: format!("Failed to get entropy from NSM device: {}", e),
+        })
 }
 
 // Initialize nitro device
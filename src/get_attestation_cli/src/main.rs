@@ -1,13 +1,32 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
-use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
-use nsm_api::driver;
-use serde_bytes::ByteBuf;
+use get_attestation_cli::verifier;
+use nsm::{MockNsm, NitroNsm, Nsm, NsmMode};
+use x509_cert::der::{pem::LineEnding, EncodePem};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
 #[command(name = "get-attestation")]
 #[command(about = "Lấy attestation trực tiếp từ NSM driver mà không cần endpoint")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    get: GetArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Kiểm tra một attestation document trước khi gọi `register_enclave`
+    Verify(VerifyArgs),
+}
+
+#[derive(Args)]
+struct GetArgs {
     /// Public key để include trong attestation (hex format)
     #[arg(long)]
     public_key: Option<String>,
@@ -23,63 +42,144 @@ struct Cli {
     /// Tạo keypair mới và sử dụng public key của nó
     #[arg(long)]
     generate_keypair: bool,
+
+    /// NSM được dùng: `nitro` trong enclave, `mock` để chạy thử trên máy thường
+    #[arg(long, default_value = "nitro")]
+    nsm: NsmMode,
+
+    /// PCR của mock NSM dạng <index>=<hex>, có thể lặp lại
+    #[arg(long = "pcr", value_parser = verifier::parse_pcr)]
+    pcrs: Vec<(usize, Vec<u8>)>,
+
+    /// Ghi root certificate của mock NSM (PEM) vào file này, dùng cho `verify --root-cert`
+    #[arg(long)]
+    mock_root_cert: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// Attestation document (hex format), như `attestation` của `/get_attestation`
+    #[arg(long, required_unless_present = "document_file")]
+    document: Option<String>,
+
+    /// File chứa attestation document (hex format)
+    #[arg(long, conflicts_with = "document")]
+    document_file: Option<PathBuf>,
+
+    /// Root certificate của AWS Nitro Enclaves (PEM), ví dụ `root.pem`
+    #[arg(long)]
+    root_cert: PathBuf,
+
+    /// PCR mong đợi dạng <index>=<hex>, ví dụ `--pcr 0=$PCR0`, có thể lặp lại
+    #[arg(long = "pcr", value_parser = verifier::parse_pcr)]
+    pcrs: Vec<(usize, Vec<u8>)>,
+
+    /// Chấp nhận attestation khi không truyền đủ `--pcr` cho PCR0, PCR1 và PCR2
+    #[arg(long)]
+    allow_any_pcrs: bool,
+
+    /// Nonce mong đợi (hex format)
+    #[arg(long)]
+    nonce: Option<String>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    
+    match cli.command {
+        Some(Command::Verify(args)) => verify(args),
+        None => get(cli.get),
+    }
+}
+
+fn verify(args: VerifyArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let document = match (args.document, args.document_file) {
+        (Some(document), _) => document,
+        (None, Some(path)) => std::fs::read_to_string(path)?,
+        (None, None) => unreachable!("clap requires one of them"),
+    };
+    let document = hex::decode(document.trim().trim_start_matches("0x"))?;
+    let root = verifier::root_from_pem(&std::fs::read(&args.root_cert)?)?;
+    let now_secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let pcrs = args.pcrs.into_iter().collect::<BTreeMap<_, _>>();
+    if !args.allow_any_pcrs {
+        verifier::require_image_pcrs(&pcrs)
+            .map_err(|e| format!("{}: pass --pcr for each, or --allow-any-pcrs to accept any image", e))?;
+    }
+
+    let attestation = verifier::verify(&document, &root, now_secs)?;
+    attestation.check_pcrs(&pcrs)?;
+    if let Some(nonce) = args.nonce {
+        attestation.check_nonce(&hex::decode(nonce.trim_start_matches("0x"))?)?;
+    }
+
+    println!("Attestation hợp lệ!");
+    println!("module_id: {}", attestation.module_id);
+    println!("timestamp: {}", attestation.timestamp);
+    for (index, pcr) in attestation.image_pcrs() {
+        println!("PCR{}: {}", index, hex::encode(pcr));
+    }
+    let print_hex = |name: &str, value: &Option<Vec<u8>>| {
+        if let Some(value) = value {
+            println!("{}: {}", name, hex::encode(value));
+        }
+    };
+    print_hex("public_key", &attestation.public_key);
+    print_hex("nonce", &attestation.nonce);
+    print_hex("user_data", &attestation.user_data);
+    Ok(())
+}
+
+fn get(cli: GetArgs) -> Result<(), Box<dyn std::error::Error>> {
     // Xử lý public key
     let public_key = if cli.generate_keypair {
         let kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
         println!("Generated keypair. Public key: {}", hex::encode(kp.public().as_bytes()));
-        Some(ByteBuf::from(kp.public().as_bytes().to_vec()))
+        Some(kp.public().as_bytes().to_vec())
     } else if let Some(pk_hex) = cli.public_key {
-        let pk_bytes = hex::decode(pk_hex)?;
-        Some(ByteBuf::from(pk_bytes))
+        Some(hex::decode(pk_hex)?)
     } else {
         None
     };
     
     // Xử lý nonce
     let nonce = if let Some(nonce_hex) = cli.nonce {
-        let nonce_bytes = hex::decode(nonce_hex)?;
-        Some(ByteBuf::from(nonce_bytes))
+        Some(hex::decode(nonce_hex)?)
     } else {
         None
     };
     
     // Xử lý user data
     let user_data = if let Some(data_hex) = cli.user_data {
-        let data_bytes = hex::decode(data_hex)?;
-        Some(ByteBuf::from(data_bytes))
+        Some(hex::decode(data_hex)?)
     } else {
         None
     };
     
-    println!("Khởi tạo NSM driver...");
-    let fd = driver::nsm_init();
-    
-    println!("Gửi attestation request...");
-    let request = NsmRequest::Attestation {
-        user_data,
-        nonce,
-        public_key,
+    println!("Khởi tạo NSM ({:?})...", cli.nsm);
+    let nsm: Box<dyn Nsm> = match cli.nsm {
+        NsmMode::Nitro => Box::new(NitroNsm),
+        NsmMode::Mock => {
+            let mock = MockNsm::new(cli.pcrs.into_iter().collect())?;
+            if let Some(path) = &cli.mock_root_cert {
+                std::fs::write(path, mock.root().to_pem(LineEnding::LF)?)?;
+                println!("Đã ghi root certificate của mock NSM vào {}", path.display());
+            }
+            Box::new(mock)
+        }
     };
-    
-    let response = driver::nsm_process_request(fd, request);
-    
-    match response {
-        NsmResponse::Attestation { document } => {
-            driver::nsm_exit(fd);
+
+    println!("Gửi attestation request...");
+    match nsm.attestation(public_key, user_data, nonce) {
+        Ok(document) => {
             println!("Thành công! Attestation document:");
             println!("{}", hex::encode(&document));
         }
-        _ => {
-            driver::nsm_exit(fd);
-            eprintln!("Lỗi: Phản hồi không mong đợi từ NSM");
+        Err(e) => {
+            eprintln!("Lỗi: {}", e);
             std::process::exit(1);
         }
     }
-    
+
     Ok(())
 }
@@ -0,0 +1,326 @@
+//! Verification of AWS Nitro attestation documents, as returned by `/get_attestation`.
+//!
+//! A document is a COSE_Sign1 structure whose CBOR payload carries the PCRs, the
+//! enclave public key, nonce and user data. The payload is signed with the leaf
+//! certificate of a chain that must end at the AWS Nitro root certificate.
+
+use nsm::document::{AttestationDoc, CoseError, CoseSign1};
+use p384::ecdsa::signature::Verifier;
+use p384::ecdsa::{Signature, VerifyingKey};
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use thiserror::Error;
+use x509_cert::der::{Decode, DecodePem, Encode};
+use x509_cert::Certificate;
+
+/// Indices of the PCRs measuring the enclave image, kernel and application.
+pub const IMAGE_PCRS: [usize; 3] = [0, 1, 2];
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error(transparent)]
+    Cose(#[from] CoseError),
+    #[error("Invalid attestation payload: {0}")]
+    Payload(String),
+    #[error("Invalid certificate: {0}")]
+    Certificate(String),
+    #[error("Certificate chain does not end at the root certificate")]
+    UntrustedRoot,
+    #[error("Certificate {subject} is not valid at {at_secs}")]
+    Expired { subject: String, at_secs: u64 },
+    #[error("Signature check failed: {0}")]
+    Signature(String),
+    #[error("PCR{index} is {actual}, expected {expected}")]
+    PcrMismatch {
+        index: usize,
+        expected: String,
+        actual: String,
+    },
+    #[error("No expected value for PCR{missing:?}")]
+    MissingPcrs { missing: Vec<usize> },
+    #[error("Nonce {actual:?} does not match the expected {expected}")]
+    NonceMismatch {
+        expected: String,
+        actual: Option<String>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, VerifyError>;
+
+/// What a verified document attests to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attestation {
+    pub module_id: String,
+    pub timestamp: u64,
+    pub pcrs: BTreeMap<usize, Vec<u8>>,
+    pub public_key: Option<Vec<u8>>,
+    pub user_data: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl Attestation {
+    /// PCR0, PCR1 and PCR2.
+    pub fn image_pcrs(&self) -> Vec<(usize, &[u8])> {
+        IMAGE_PCRS
+            .iter()
+            .filter_map(|index| Some((*index, self.pcrs.get(index)?.as_slice())))
+            .collect()
+    }
+
+    /// Fails on the first PCR differing from `expected`, a PCR missing from the
+    /// document counts as a mismatch.
+    pub fn check_pcrs(&self, expected: &BTreeMap<usize, Vec<u8>>) -> Result<()> {
+        for (index, expected) in expected {
+            let actual = self.pcrs.get(index);
+            if actual != Some(expected) {
+                return Err(VerifyError::PcrMismatch {
+                    index: *index,
+                    expected: hex::encode(expected),
+                    actual: actual.map(hex::encode).unwrap_or_default(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_nonce(&self, expected: &[u8]) -> Result<()> {
+        if self.nonce.as_deref() != Some(expected) {
+            return Err(VerifyError::NonceMismatch {
+                expected: hex::encode(expected),
+                actual: self.nonce.as_ref().map(hex::encode),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Root certificate from its PEM encoding, e.g. `root.pem` of the AWS Nitro Enclaves
+/// root bundle.
+/// Fail unless `expected` pins every PCR of `IMAGE_PCRS`.
+pub fn require_image_pcrs(expected: &BTreeMap<usize, Vec<u8>>) -> Result<()> {
+    let missing = IMAGE_PCRS
+        .into_iter()
+        .filter(|index| !expected.contains_key(index))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(VerifyError::MissingPcrs { missing });
+    }
+    Ok(())
+}
+
+pub fn root_from_pem(pem: &[u8]) -> Result<Certificate> {
+    Certificate::from_pem(pem).map_err(|e| VerifyError::Certificate(e.to_string()))
+}
+
+/// Check `document` was signed by an NSM whose certificate chain ends at `root` and
+/// whose certificates are all valid at `now_secs`, and return what it attests to.
+pub fn verify(document: &[u8], root: &Certificate, now_secs: u64) -> Result<Attestation> {
+    let sign1 = CoseSign1::decode(document)?;
+    let doc: AttestationDoc = ciborium::de::from_reader(sign1.payload.as_slice())
+        .map_err(|e| VerifyError::Payload(e.to_string()))?;
+    if doc.digest != "SHA384" {
+        return Err(VerifyError::Payload(format!(
+            "unsupported digest {}",
+            doc.digest
+        )));
+    }
+
+    let leaf = verify_chain(&doc, root, now_secs)?;
+    let key = verifying_key(&leaf)?;
+    let signature = Signature::from_slice(&sign1.signature)
+        .map_err(|e| VerifyError::Signature(e.to_string()))?;
+    key.verify(&sign1.sig_structure()?, &signature)
+        .map_err(|e| VerifyError::Signature(e.to_string()))?;
+
+    Ok(Attestation {
+        module_id: doc.module_id,
+        timestamp: doc.timestamp,
+        pcrs: doc
+            .pcrs
+            .into_iter()
+            .map(|(index, pcr)| (index, pcr.into_vec()))
+            .collect(),
+        public_key: doc.public_key.map(ByteBuf::into_vec),
+        user_data: doc.user_data.map(ByteBuf::into_vec),
+        nonce: doc.nonce.map(ByteBuf::into_vec),
+    })
+}
+
+/// Check `cabundle` starts at `root` and each certificate down to the leaf is signed
+/// by the one before it, returning the leaf.
+fn verify_chain(doc: &AttestationDoc, root: &Certificate, now_secs: u64) -> Result<Certificate> {
+    let (bundle_root, intermediates) = doc
+        .cabundle
+        .split_first()
+        .ok_or_else(|| VerifyError::Payload("empty cabundle".to_string()))?;
+    let root_der = root
+        .to_der()
+        .map_err(|e| VerifyError::Certificate(e.to_string()))?;
+    if bundle_root.as_slice() != root_der.as_slice() {
+        return Err(VerifyError::UntrustedRoot);
+    }
+    check_validity(root, now_secs)?;
+
+    let mut issuer = root.clone();
+    for der in intermediates
+        .iter()
+        .chain(std::iter::once(&doc.certificate))
+    {
+        let certificate = Certificate::from_der(der)
+            .map_err(|e| VerifyError::Certificate(e.to_string()))?;
+        check_signed_by(&certificate, &issuer)?;
+        check_validity(&certificate, now_secs)?;
+        issuer = certificate;
+    }
+    Ok(issuer)
+}
+
+fn check_signed_by(certificate: &Certificate, issuer: &Certificate) -> Result<()> {
+    let tbs = &certificate.tbs_certificate;
+    if tbs.issuer != issuer.tbs_certificate.subject {
+        return Err(VerifyError::Certificate(format!(
+            "{} is not issued by {}",
+            tbs.subject, issuer.tbs_certificate.subject
+        )));
+    }
+    let message = tbs
+        .to_der()
+        .map_err(|e| VerifyError::Certificate(e.to_string()))?;
+    let signature = certificate
+        .signature
+        .as_bytes()
+        .ok_or_else(|| VerifyError::Certificate("unaligned signature".to_string()))?;
+    let signature =
+        Signature::from_der(signature).map_err(|e| VerifyError::Signature(e.to_string()))?;
+    verifying_key(issuer)?
+        .verify(&message, &signature)
+        .map_err(|_| VerifyError::Signature(format!("certificate {}", tbs.subject)))
+}
+
+fn check_validity(certificate: &Certificate, now_secs: u64) -> Result<()> {
+    let validity = &certificate.tbs_certificate.validity;
+    let not_before = validity.not_before.to_unix_duration().as_secs();
+    let not_after = validity.not_after.to_unix_duration().as_secs();
+    if now_secs < not_before || now_secs > not_after {
+        return Err(VerifyError::Expired {
+            subject: certificate.tbs_certificate.subject.to_string(),
+            at_secs: now_secs,
+        });
+    }
+    Ok(())
+}
+
+fn verifying_key(certificate: &Certificate) -> Result<VerifyingKey> {
+    let key = &certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key;
+    let key = key
+        .as_bytes()
+        .ok_or_else(|| VerifyError::Certificate("unaligned public key".to_string()))?;
+    VerifyingKey::from_sec1_bytes(key).map_err(|e| VerifyError::Certificate(e.to_string()))
+}
+
+/// Parse `index=hex` PCR arguments, e.g. `0=3a92...`.
+pub fn parse_pcr(arg: &str) -> std::result::Result<(usize, Vec<u8>), String> {
+    let (index, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected <index>=<hex>, got {}", arg))?;
+    let index = index
+        .trim_start_matches("PCR")
+        .parse::<usize>()
+        .map_err(|e| format!("invalid PCR index {}: {}", index, e))?;
+    let value = hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| format!("invalid PCR{} value: {}", index, e))?;
+    Ok((index, value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nsm::{MockNsm, Nsm, PCR_COUNT, PCR_LEN};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn attestation() -> Attestation {
+        Attestation {
+            module_id: "i-0".to_string(),
+            timestamp: 0,
+            pcrs: BTreeMap::from([(0, vec![1; 48]), (1, vec![2; 48]), (2, vec![3; 48])]),
+            public_key: None,
+            user_data: None,
+            nonce: Some(vec![7, 7]),
+        }
+    }
+
+    #[test]
+    fn test_check_pcrs() {
+        let attestation = attestation();
+        let expected = BTreeMap::from([(0, vec![1; 48]), (2, vec![3; 48])]);
+        assert!(attestation.check_pcrs(&expected).is_ok());
+
+        let expected = BTreeMap::from([(1, vec![0; 48])]);
+        assert!(matches!(
+            attestation.check_pcrs(&expected),
+            Err(VerifyError::PcrMismatch { index: 1, .. })
+        ));
+        let expected = BTreeMap::from([(4, vec![0; 48])]);
+        assert!(attestation.check_pcrs(&expected).is_err());
+    }
+
+    #[test]
+    fn test_require_image_pcrs() {
+        let expected = BTreeMap::from([(0, vec![1; 48]), (1, vec![2; 48]), (2, vec![3; 48])]);
+        assert!(require_image_pcrs(&expected).is_ok());
+
+        let expected = BTreeMap::from([(0, vec![1; 48]), (3, vec![4; 48])]);
+        assert!(matches!(
+            require_image_pcrs(&expected),
+            Err(VerifyError::MissingPcrs { missing }) if missing == vec![1, 2]
+        ));
+    }
+
+    #[test]
+    fn test_check_nonce() {
+        assert!(attestation().check_nonce(&[7, 7]).is_ok());
+        assert!(attestation().check_nonce(&[7]).is_err());
+    }
+
+    #[test]
+    fn test_parse_pcr() {
+        assert_eq!(parse_pcr("0=0x0102").unwrap(), (0, vec![1, 2]));
+        assert_eq!(parse_pcr("PCR2=ff").unwrap(), (2, vec![255]));
+        assert!(parse_pcr("0102").is_err());
+    }
+
+    #[test]
+    fn test_verify_mock_attestation() {
+        let pcrs = BTreeMap::from([(0, vec![1; PCR_LEN]), (2, vec![2; PCR_LEN])]);
+        let nsm = MockNsm::new(pcrs.clone()).unwrap();
+        let document = nsm
+            .attestation(
+                Some(vec![9; 32]),
+                Some(b"config".to_vec()),
+                Some(vec![7, 7]),
+            )
+            .unwrap();
+
+        let now_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let attestation = verify(&document, nsm.root(), now_secs).unwrap();
+        attestation.check_pcrs(&pcrs).unwrap();
+        attestation.check_nonce(&[7, 7]).unwrap();
+        assert_eq!(attestation.pcrs.len(), PCR_COUNT);
+        assert_eq!(attestation.pcrs[&1], vec![0; PCR_LEN]);
+        assert_eq!(attestation.public_key, Some(vec![9; 32]));
+        assert_eq!(attestation.user_data, Some(b"config".to_vec()));
+
+        // the document does not chain up to another mock's root
+        let other = MockNsm::new(BTreeMap::new()).unwrap();
+        assert!(matches!(
+            verify(&document, other.root(), now_secs),
+            Err(VerifyError::UntrustedRoot)
+        ));
+    }
+}
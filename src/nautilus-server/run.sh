@@ -24,6 +24,7 @@ busybox ip link set dev lo up
 echo "127.0.0.1   localhost" > /etc/hosts
 echo "127.0.0.64   fullnode.mainnet.sui.io:443" >> /etc/hosts
 echo "127.0.0.65   sui-mainnet.mystenlabs.com/graphql" >> /etc/hosts
+echo "127.0.0.66   kms.us-east-1.amazonaws.com" >> /etc/hosts
 
 
 
@@ -48,6 +49,7 @@ echo "$JSON_RESPONSE" | jq -r 'to_entries[] | "\(.key)=\(.value)"' > /tmp/kvpair
 # Traffic-forwarder-block
 python3 /traffic_forwarder.py 127.0.0.64 443 3 8101 &
 python3 /traffic_forwarder.py 127.0.0.65 443 3 8102 &
+python3 /traffic_forwarder.py 127.0.0.66 443 3 8103 &
 
 
 
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::io::{Error, ErrorKind};
+use crate::EnclaveError;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Route {
@@ -41,37 +41,50 @@ pub struct SwapByAmountInRequest {
 
 pub struct CetusAggregator;
 
+async fn fetch(api_url: &str) -> Result<String, EnclaveError> {
+    reqwest::get(api_url)
+        .await
+        .map_err(|e| EnclaveError::AggregatorFailure(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| EnclaveError::AggregatorFailure(e.to_string()))
+}
+
 impl CetusAggregator {
     pub fn new() -> Self {
         Self {}
     }
 
-    pub async fn swap_by_amount_in(&self, swap_request: SwapByAmountInRequest) -> Result<Route, Error> {
-        let api_url = format!("https://api-sui.cetus.zone/router_v2/find_routes?from={}&target={}&amount={}&byAmountIn=true&depth=3&providers=CETUS&v={}", swap_request.from, swap_request.to, swap_request.amount_in, 1001600);
-        let response = reqwest::get(&api_url).await.unwrap();
-        let body = response.text().await.unwrap();
+    pub async fn swap_by_amount_in(&self, swap_request: SwapByAmountInRequest) -> Result<Route, EnclaveError> {
+        let api_url = format!("https://api-sui.cetus.zone/router_v2/find_routes?from={}&target={}&amount={}&byAmountIn=true&depth=3&providers=CETUS,FLOWXV3&v={}", swap_request.from, swap_request.to, swap_request.amount_in, 1001600);
+        let body = fetch(&api_url).await?;
 
         // Parse response trực tiếp thành Route struct (không phải Vec<Route>)
-        let route: Route = serde_json::from_str(&body).unwrap();
+        let route: Route = serde_json::from_str(&body)
+            .map_err(|e| EnclaveError::AggregatorFailure(format!("Invalid route response: {}", e)))?;
         if route.code != 200 {
-            return Err(Error::new(ErrorKind::Other, route.msg));
+            return Err(EnclaveError::AggregatorFailure(route.msg));
         }
 
         Ok(route)
     }
     
-    pub async fn get_mark_price(&self, coin_a_type: String, coin_b_type: String, amount: u64) -> Result<u128, Error> {
+    pub async fn get_mark_price(&self, coin_a_type: String, coin_b_type: String, amount: u64) -> Result<u128, EnclaveError> {
         let api_url = format!("https://api-sui.cetus.zone/router_v2/find_routes?from={}&target={}&amount={}&byAmountIn=true&depth=3&providers=CETUS&v=1001600", coin_a_type, coin_b_type, amount);
-        let response = reqwest::get(&api_url).await.unwrap();
-        let body = response.text().await.unwrap();
+        let body = fetch(&api_url).await?;
 
-        let route: Route = serde_json::from_str(&body).unwrap();
+        let route: Route = serde_json::from_str(&body)
+            .map_err(|e| EnclaveError::AggregatorFailure(format!("Invalid route response: {}", e)))?;
         if route.code != 200 {
-            return Err(Error::new(ErrorKind::Other, route.msg));
+            return Err(EnclaveError::AggregatorFailure(route.msg));
         }
 
-        let sum_amount_out = route.data.as_ref().unwrap().routes.iter().map(|route| route.amount_out).sum::<u64>();
-        let sum_amount_in = route.data.as_ref().unwrap().routes.iter().map(|route| route.amount_in).sum::<u64>();
+        let data = route.data.as_ref().ok_or_else(|| EnclaveError::AggregatorFailure("No route found".to_string()))?;
+        let sum_amount_out = data.routes.iter().map(|route| route.amount_out).sum::<u64>();
+        let sum_amount_in = data.routes.iter().map(|route| route.amount_in).sum::<u64>();
+        if sum_amount_in == 0 {
+            return Err(EnclaveError::AggregatorFailure("Route has no input amount".to_string()));
+        }
 
         Ok(sum_amount_out as u128 * 1000000000u128 / sum_amount_in as u128)
     }
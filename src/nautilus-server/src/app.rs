@@ -9,30 +9,39 @@ use crate::common::{
     construct_kp_from_bech32_string, to_signed_response, IntentScope, ProcessDataRequest,
     ProcessedDataResponse,
 };
+use crate::dex;
 use crate::parsers;
 use crate::transactions_builder::helper;
 use crate::transactions_builder::helper::new_with_gas;
+use crate::transactions_builder::preflight::{self, PositionMark, PreflightPolicy, Simulation};
+use crate::transactions_builder::retry::{RetryDecision, RetryPolicy};
 use crate::transactions_builder::DexTransactionBuilder;
+use crate::gas_pool::GasLease;
+use crate::jobs::{EffectsSummary, Job, JobState, SimulationSummary, TickRange};
+use crate::math::clmm_math::{self, CalculateAmountsByLiquidityRequest};
 use crate::AppState;
 use crate::EnclaveError;
 use anyhow::anyhow;
-use axum::extract::State;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
 use axum::Json;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use sui_crypto::ed25519::Ed25519PrivateKey;
 use sui_crypto::simple::SimpleKeypair;
 use sui_crypto::Signer;
 use sui_crypto::SuiSigner;
-use sui_graphql_client::Client as GraphQLClient;
 use sui_graphql_client::PaginationFilter;
 use sui_rpc::field::FieldMask;
 use sui_rpc::proto::sui::rpc::v2beta2::GetBalanceRequest;
 use sui_rpc::proto::sui::rpc::v2beta2::GetObjectRequest;
-use sui_rpc::Client;
+use sui_sdk_types::Address;
 use sui_sdk_types::Input;
 use sui_sdk_types::Object;
 use sui_sdk_types::TransactionEffects;
 use sui_sdk_types::{Ed25519PublicKey, MultisigMemberPublicKey};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
 /// ====
 /// Core Nautilus server logic, replace it with your own
@@ -51,48 +60,104 @@ pub struct TransactionResponse<T> {
     pub request: T,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProcessDataV2Response {
+    pub job_id: String,
+}
+
+/// Enqueue the rebalance or compound of the strategy position and return the job id,
+/// the outcome is reported by `GET /jobs/{id}`.
 pub async fn process_data_v2(
     State(state): State<Arc<AppState>>,
     Json(request): Json<ProcessDataRequest<TransactionRequest>>,
+) -> (StatusCode, Json<ProcessDataV2Response>) {
+    let (job_id, _) = spawn_job(state, request.payload);
+    (StatusCode::ACCEPTED, Json(ProcessDataV2Response { job_id }))
+}
+
+/// Create a job for `request` and run it in the background, the handle completes once
+/// the job is finalized or failed.
+pub fn spawn_job(state: Arc<AppState>, request: TransactionRequest) -> (String, JoinHandle<()>) {
+    let job_id = state
+        .jobs
+        .create(request.pool_id.clone(), request.strategy_id.clone());
+    let handle = tokio::spawn({
+        let job_id = job_id.clone();
+        async move {
+            if let Err(e) = run_job(&state, &job_id, &request).await {
+                warn!("job {} failed: {:?}", job_id, e);
+                state.jobs.fail(&job_id, &e);
+            }
+        }
+    });
+    (job_id, handle)
+}
+
+pub async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<Job>, EnclaveError> {
+    state
+        .jobs
+        .get(&job_id)
+        .map(Json)
+        .ok_or(EnclaveError::JobNotFound(job_id))
+}
+
+/// Fetch, build, dry run and execute, refetching everything when the retry policy
+/// deems a failed attempt worth repeating. Failed attempts are recorded on the job,
+/// the error of the last one is returned.
+async fn run_job(
+    state: &AppState,
+    job_id: &str,
+    request: &TransactionRequest,
 ) -> Result<(), EnclaveError> {
+    state.jobs.set_state(job_id, JobState::Building);
     let kp = construct_kp_from_bech32_string(&state.pk_string)
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to construct keypair: {}", e)))?;
-    let mut client = Client::new("https://fullnode.mainnet.sui.io:443")
-        .map_err(|e| EnclaveError::GenericError(format!("Failed to create client: {}", e)))?;
-    let mut ledger_client = client.ledger_client();
-    let mut graphql_client = GraphQLClient::new_mainnet();
-
-    for _ in 0..10 {
-        let pool_data = ledger_client
+        .map_err(|e| EnclaveError::SigningFailure(format!("Failed to construct keypair: {}", e)))?;
+    let mut client = state
+        .config
+        .rpc_client()
+        .map_err(|e| EnclaveError::UpstreamRpc(format!("Failed to create client: {}", e)))?;
+    let mut graphql_client = state
+        .config
+        .graphql_client()
+        .map_err(|e| EnclaveError::UpstreamRpc(format!("Failed to create client: {}", e)))?;
+
+    let policy = RetryPolicy::new(&state.config.retry);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        state.jobs.set_state(job_id, JobState::Building);
+        let pool_data = client
+            .ledger_client()
             .get_object(GetObjectRequest {
-                object_id: Some(request.payload.pool_id.clone()),
+                object_id: Some(request.pool_id.clone()),
                 version: None,
                 read_mask: Some(FieldMask {
                     paths: vec!["*".into()],
                 }),
             })
             .await
-            .map_err(|e| EnclaveError::GenericError(format!("Failed to get pool: {}", e)))?;
-        let pool_object = pool_data.into_inner().object.unwrap();
+            .map_err(|e| get_object_error("pool", &request.pool_id, e))?;
+        let pool_object = pool_data.into_inner().object.ok_or_else(|| {
+            EnclaveError::ObjectNotFound(format!("Pool {}", request.pool_id))
+        })?;
 
-        let strategy_data = ledger_client
+        let strategy_data = client
+            .ledger_client()
             .get_object(GetObjectRequest {
-                object_id: Some(request.payload.strategy_id.clone()),
+                object_id: Some(request.strategy_id.clone()),
                 version: None,
                 read_mask: Some(FieldMask {
                     paths: vec!["*".into()],
                 }),
             })
             .await
-            .map_err(|e| EnclaveError::GenericError(format!("Failed to get strategy: {}", e)))?;
-        let strategy_object = strategy_data.into_inner().object.unwrap();
-
-        let processed_pool_data =
-            parsers::into_processed_pool_data(&mut graphql_client, pool_object, strategy_object)
-                .await
-                .map_err(|e| {
-                    EnclaveError::GenericError(format!("Failed to handle object: {}", e))
-                })?;
+            .map_err(|e| get_object_error("strategy", &request.strategy_id, e))?;
+        let strategy_object = strategy_data.into_inner().object.ok_or_else(|| {
+            EnclaveError::ObjectNotFound(format!("Strategy {}", request.strategy_id))
+        })?;
 
         let current_timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -101,40 +166,85 @@ pub async fn process_data_v2(
             })?
             .as_millis() as u64;
 
+        let processed_pool_data =
+            parsers::into_processed_pool_data(
+                &mut graphql_client,
+                &state.config,
+                pool_object,
+                strategy_object,
+                current_timestamp,
+            )
+                .await
+                .map_err(|e| EnclaveError::from_anyhow(e, EnclaveError::ParseMismatch))?;
+
         let address = kp.public_key().derive_address();
 
-        let dex_tx_builder = DexTransactionBuilder::new(&graphql_client, address, 100000000).await;
+        // a coin of the pool is held until the attempt ends, so concurrent jobs never
+        // spend the same coin version
+        let mut gas_lease = None;
+        let dex_tx_builder = if let Some(sponsor) = &state.sponsor {
+            DexTransactionBuilder::sponsored(
+                &graphql_client,
+                &state.config,
+                address,
+                sponsor.address(),
+                state.config.gas_budget,
+            )
+            .await?
+        } else if state.config.gas_pool.size > 0 {
+            let lease = state
+                .gas_pool
+                .lease(
+                    state.config.gas_budget,
+                    std::time::Duration::from_secs(state.config.gas_pool.lease_timeout_secs),
+                )
+                .await?;
+            let gas_coin = lease.coin().object_ref.clone();
+            gas_lease = Some(lease);
+            DexTransactionBuilder::with_gas_coins(
+                &graphql_client,
+                &state.config,
+                address,
+                &[gas_coin],
+                state.config.gas_budget,
+            )
+            .await?
+        } else {
+            DexTransactionBuilder::new(
+                &graphql_client,
+                &state.config,
+                address,
+                state.config.gas_budget,
+            )
+            .await?
+        };
         let tx: sui_transaction_builder::TransactionBuilder = match &processed_pool_data.request {
             parsers::Request::Rebalance(rebalance_req) => {
                 let strategy = processed_pool_data
                     .auto_rebalance_strategy
                     .as_ref()
-                    .unwrap();
-                let signed_data = Json(to_signed_response(
-                    &state.eph_kp,
+                    .ok_or_else(|| {
+                        EnclaveError::ParseMismatch("Strategy is not an auto rebalance strategy".to_string())
+                    })?;
+                let signed_data = to_signed_response(
+                    &state.eph_kp(),
                     TransactionResponse {
                         request: rebalance_req.clone(),
                     },
                     current_timestamp,
                     IntentScope::Transaction,
-                ));
+                )?;
+                record_signed_intent(state, job_id, &signed_data);
                 let (tick_lower_index_i32, tick_upper_index_i32) =
-                    parsers::strategies::auto_rebalance::get_new_tick_range(
-                        rebalance_req.current_sqrt_price,
-                        rebalance_req.current_tick_u32,
-                        rebalance_req.tick_lower_index_u32 as i32,
-                        rebalance_req.tick_upper_index_u32 as i32,
-                        strategy.lower_sqrt_price_change_threshold_bps,
-                        strategy.upper_sqrt_price_change_threshold_bps,
-                        strategy.lower_sqrt_price_change_threshold_direction,
-                        strategy.upper_sqrt_price_change_threshold_direction,
-                        strategy.range_multiplier_lower,
-                        strategy.range_multiplier_upper,
-                        rebalance_req.tick_spacing,
-                    )
-                    .map_err(|e| {
-                        EnclaveError::GenericError(format!("Failed to get new tick range: {}", e))
+                    processed_pool_data.new_tick_range.ok_or_else(|| {
+                        EnclaveError::ParseMismatch("Rebalance request has no new tick range".to_string())
                     })?;
+                state.jobs.update(job_id, |job| {
+                    job.tick_range = Some(TickRange {
+                        tick_lower_index: tick_lower_index_i32,
+                        tick_upper_index: tick_upper_index_i32,
+                    })
+                });
                 // convert to u32, if negative, add 2^32
                 let tick_lower_index = if tick_lower_index_i32 < 0 {
                     (tick_lower_index_i32 + 2i32.pow(32)) as u32
@@ -150,15 +260,18 @@ pub async fn process_data_v2(
                 dex_tx_builder
                     .rebalance(
                         rebalance_req.clone(),
-                        request.payload.pool_id.clone(),
+                        request.pool_id.clone(),
                         processed_pool_data.coin_a_type.clone(),
                         processed_pool_data.coin_b_type.clone(),
                         tick_lower_index,
                         tick_upper_index,
                         processed_pool_data.position_liquidity,
+                        processed_pool_data.fee_rate,
+                        processed_pool_data.pool_liquidity,
+                        processed_pool_data.pool_ticks.clone(),
                         processed_pool_data.position_registry_id,
                         processed_pool_data.dex,
-                        request.payload.enclave_id.clone(),
+                        request.enclave_id.clone(),
                         signed_data.signature.clone(),
                         current_timestamp,
                         lp_slippage_tolerance_bps,
@@ -166,60 +279,245 @@ pub async fn process_data_v2(
                         processed_pool_data.rewarder_coin_types.clone(),
                     )
                     .await
+                    .map_err(|e| EnclaveError::from_anyhow(e, EnclaveError::GenericError))?
             }
             parsers::Request::Compound(compound_req) => {
                 let signed_data = to_signed_response(
-                    &state.eph_kp,
+                    &state.eph_kp(),
                     TransactionResponse {
                         request: compound_req.clone(),
                     },
                     current_timestamp,
                     IntentScope::Transaction,
-                );
-                println!("state.eph_kp: {:?}", state.eph_kp);
-
-                println!("signed_data: {:?}", signed_data.signature);
+                )?;
+                record_signed_intent(state, job_id, &signed_data);
+                let strategy = processed_pool_data
+                    .auto_rebalance_strategy
+                    .as_ref()
+                    .ok_or_else(|| {
+                        EnclaveError::ParseMismatch("Strategy is not an auto rebalance strategy".to_string())
+                    })?;
                 dex_tx_builder
                     .compound(
                         compound_req.clone(),
+                        request.pool_id.clone(),
+                        processed_pool_data.coin_a_type.clone(),
+                        processed_pool_data.coin_b_type.clone(),
+                        processed_pool_data.fee_rate,
+                        processed_pool_data.pool_liquidity,
+                        processed_pool_data.pool_ticks.clone(),
+                        processed_pool_data.position_registry_id,
                         processed_pool_data.dex,
+                        request.enclave_id.clone(),
                         signed_data.signature.clone(),
+                        current_timestamp,
+                        strategy.lp_slippage_tolerance_bps,
+                        processed_pool_data.rewarder_coin_types.clone(),
+                        processed_pool_data.pending_rewards.clone(),
                     )
                     .await
+                    .map_err(|e| EnclaveError::from_anyhow(e, EnclaveError::GenericError))?
             }
         };
 
-        match helper::execute_and_wait_for_effects(&graphql_client, tx, &kp, false, None).await {
+        let mark = position_mark(&processed_pool_data)?;
+        let result = execute(
+            state,
+            job_id,
+            &graphql_client,
+            &mut client,
+            tx,
+            &kp,
+            &processed_pool_data,
+            &request.pool_id,
+            &mark,
+            &mut gas_lease,
+        )
+        .await;
+        match result {
             Ok(effects) => {
-                let transaction_digest = match effects {
+                if let Some(lease) = gas_lease {
+                    lease.release(&effects);
+                }
+                let transaction_digest = match &effects {
                     TransactionEffects::V1(effects) => effects.transaction_digest,
                     TransactionEffects::V2(effects) => effects.transaction_digest,
                 };
-                println!("transaction_digest: {:?}", transaction_digest);
-                break;
+                info!("job {} finalized in transaction {}", job_id, transaction_digest);
+                state.jobs.update(job_id, |job| {
+                    job.digest = Some(transaction_digest.to_string());
+                    job.effects = Some(EffectsSummary::from(&effects));
+                    job.state = JobState::Finalized;
+                });
+                return Ok(());
             }
             Err(e) => {
-                println!("Error executing transaction: {:?}", e);
+                warn!("job {} failed to execute its transaction: {:?}", job_id, e);
+                match policy.decide(&e, attempt) {
+                    RetryDecision::GiveUp => return Err(e.into()),
+                    RetryDecision::Retry(backoff) => {
+                        info!(
+                            "job {} retrying a {:?} failure in {:?}",
+                            job_id,
+                            policy.classify(&e),
+                            backoff
+                        );
+                        state.jobs.record_error(job_id, &EnclaveError::from(e));
+                        tokio::time::sleep(backoff).await;
+                    }
+                }
             }
         }
     }
+}
+
+/// Simulate `tx` with the configured gas budget, lower the budget to the simulated cost
+/// plus the margin and check the simulation against the preflight policy before
+/// signing, then submit and wait for it, moving the job through the matching states.
+async fn execute(
+    state: &AppState,
+    job_id: &str,
+    client: &sui_graphql_client::Client,
+    rpc_client: &mut sui_rpc::Client,
+    mut tx: sui_transaction_builder::TransactionBuilder,
+    kp: &Ed25519PrivateKey,
+    data: &parsers::ProcessedPoolData,
+    pool_id: &str,
+    mark: &PositionMark,
+    gas_lease: &mut Option<GasLease>,
+) -> helper::Result<TransactionEffects> {
+    state.jobs.set_state(job_id, JobState::DryRun);
+    let simulation = preflight::simulate(rpc_client, &helper::finish(tx.clone())?).await?;
+    let gas_budget =
+        simulation.gas_budget(state.config.gas_budget_margin_bps, state.config.gas_budget);
+    tx.set_gas_budget(gas_budget);
+    let tx = helper::finish(tx)?;
+    let sender = kp.public_key().derive_address();
+    let gas_owner = state.sponsor.as_ref().map_or(sender, |sponsor| sponsor.address());
+    let position_value_after = position_value_after(state, data, pool_id, mark, &simulation)?;
+    let violations = PreflightPolicy::new(&state.config.preflight).check(
+        sender,
+        gas_owner,
+        mark,
+        position_value_after,
+        simulation.net_gas_used(),
+        &simulation.balance_changes,
+    );
+    let summary = SimulationSummary::new(&simulation, gas_budget, violations.clone());
+    state.jobs.update(job_id, |job| job.simulation = Some(summary));
+    if !violations.is_empty() {
+        return Err(helper::SuiUtilsError::PreflightRejected(violations.join("; ")));
+    }
+    let sig = helper::sign_transaction(&tx, kp)?;
+    let signatures = match &state.sponsor {
+        Some(sponsor) => {
+            let sponsor_sig = sponsor.sign(&tx, &sig).await?;
+            vec![sig, sponsor_sig]
+        }
+        None => vec![sig],
+    };
+    state.jobs.set_state(job_id, JobState::Submitted);
+    if let Some(lease) = gas_lease {
+        lease.mark_submitted();
+    }
+    let effects = helper::execute(client, &tx, signatures).await?;
+    helper::wait_for_finalization(client, &tx).await?;
+    Ok(effects)
+}
+
+/// Mark the strategy position at the price the request was built from.
+fn position_mark(data: &parsers::ProcessedPoolData) -> Result<PositionMark, EnclaveError> {
+    let (sqrt_price, tick_lower_index, tick_upper_index) = match &data.request {
+        parsers::Request::Rebalance(req) => (
+            req.current_sqrt_price,
+            req.tick_lower_index_u32,
+            req.tick_upper_index_u32,
+        ),
+        parsers::Request::Compound(req) => (
+            req.current_sqrt_price,
+            req.tick_lower_index_u32,
+            req.tick_upper_index_u32,
+        ),
+    };
+    let (amount_a, amount_b) =
+        clmm_math::calculate_amounts_by_liquidity(CalculateAmountsByLiquidityRequest::new(
+            tick_lower_index as i32,
+            tick_upper_index as i32,
+            sqrt_price,
+            data.position_liquidity,
+        ))
+        .map_err(|e| EnclaveError::ParseMismatch(format!("Failed to mark position: {}", e)))?;
+    Ok(PositionMark::new(
+        data.coin_a_type.clone(),
+        data.coin_b_type.clone(),
+        sqrt_price,
+        amount_a,
+        amount_b,
+    ))
+}
+
+/// Value of the strategy position the simulation leaves in the registry, priced like
+/// `mark`. Its amounts are taken at the simulated pool price, so a swap that pushes the
+/// price away shows up as a loss.
+fn position_value_after(
+    state: &AppState,
+    data: &parsers::ProcessedPoolData,
+    pool_id: &str,
+    mark: &PositionMark,
+    simulation: &Simulation,
+) -> helper::Result<u128> {
+    let invalid = |e: anyhow::Error| helper::SuiUtilsError::execution(format!("Simulated position: {}", e));
+    let adapter = dex::get_adapter(data.dex).map_err(invalid)?;
+    let pool_id = Address::from_hex(pool_id).map_err(|e| invalid(e.into()))?;
+    let registry_bag_id = Address::from_hex(&state.config.kurage.registry_bag_object_id)
+        .map_err(|e| invalid(e.into()))?;
+
+    let pool_json = simulation
+        .output_json(pool_id)
+        .ok_or_else(|| invalid(anyhow!("the transaction does not write pool {}", pool_id)))?;
+    let pool = parsers::pools::try_match(Box::new(pool_json.clone())).map_err(invalid)?;
+    let sqrt_price = adapter.pool_summary(&pool).map_err(invalid)?.current_sqrt_price;
 
-    Ok(())
+    let position_json = simulation
+        .output_dynamic_field(registry_bag_id, data.position_registry_id)
+        .ok_or_else(|| {
+            invalid(anyhow!("the transaction does not return position {}", data.position_registry_id))
+        })?;
+    let position = adapter.parse_position(&Box::new(position_json)).map_err(invalid)?;
+    let summary = adapter.position_summary(&position).map_err(invalid)?;
+    let (amount_a, amount_b) =
+        clmm_math::calculate_amounts_by_liquidity(CalculateAmountsByLiquidityRequest::new(
+            summary.tick_lower_index_u32 as i32,
+            summary.tick_upper_index_u32 as i32,
+            sqrt_price,
+            summary.liquidity,
+        ))
+        .map_err(|e| invalid(e.into()))?;
+    Ok(mark.value_of_amounts(amount_a, amount_b))
 }
 
+fn record_signed_intent<T: Serialize>(state: &AppState, job_id: &str, signed_data: &T) {
+    let signed_intent = serde_json::to_value(signed_data).ok();
+    state.jobs.update(job_id, |job| job.signed_intent = signed_intent);
+}
+
+/// Map a failed object read, a missing object is reported as such.
+pub(crate) fn get_object_error(kind: &str, object_id: &str, status: tonic::Status) -> EnclaveError {
+    if status.code() == tonic::Code::NotFound {
+        EnclaveError::ObjectNotFound(format!("{} {}", kind, object_id))
+    } else {
+        EnclaveError::UpstreamRpc(format!("Failed to get {}: {}", kind, status))
+    }
+}
+
+#[cfg(test)]
 mod test {
     use super::*;
-    use fastcrypto::ed25519::Ed25519KeyPair;
-    use fastcrypto::traits::KeyPair;
-    use rand::rngs::OsRng;
+    use nsm::NitroNsm;
 
     #[tokio::test]
     async fn test_process_data_v2() {
-        let eph_kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
-        let state = AppState {
-            eph_kp: eph_kp,
-            pk_string: "".to_string(),
-        };
+        let state = AppState::for_test(NitroNsm);
         let request = ProcessDataRequest::<TransactionRequest> {
             payload: TransactionRequest {
                 pool_id: "0x1".to_string(),
@@ -227,8 +525,34 @@ mod test {
                 enclave_id: "0x3".to_string(),
             },
         };
-        process_data_v2(State(Arc::new(state)), Json(request))
+        let state = Arc::new(state);
+        let (status, Json(response)) =
+            process_data_v2(State(state.clone()), Json(request)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        // the empty signing key fails the job before anything is fetched
+        let mut job = None;
+        for _ in 0..50 {
+            let Json(current) = get_job(State(state.clone()), Path(response.job_id.clone()))
+                .await
+                .unwrap();
+            if current.state.is_finished() {
+                job = Some(current);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let job = job.unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(job.attempts[0].code, "SIGNING_FAILURE");
+    }
+
+    #[tokio::test]
+    async fn test_get_unknown_job() {
+        let state = AppState::for_test(NitroNsm);
+        let err = get_job(State(Arc::new(state)), Path("missing".to_string()))
             .await
-            .unwrap();
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
     }
 }
@@ -3,15 +3,15 @@
 
 use crate::AppState;
 use crate::EnclaveError;
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
 use fastcrypto::traits::Signer;
 use fastcrypto::{encoding::Encoding, traits::ToFromBytes};
 use fastcrypto::{encoding::Hex, traits::KeyPair as FcKeyPair};
-use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
-use nsm_api::driver;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_bytes::ByteBuf;
 use serde_repr::Deserialize_repr;
 use serde_repr::Serialize_repr;
 use std::collections::HashMap;
@@ -40,6 +40,8 @@ pub struct IntentMessage<T: Serialize> {
 #[repr(u8)]
 pub enum IntentScope {
     Transaction = 0,
+    /// Handover from the old enclave key to a new one, see `rotation`.
+    KeyRotation = 1,
 }
 
 impl<T: Serialize + Debug> IntentMessage<T> {
@@ -71,10 +73,10 @@ pub fn to_signed_response<T: Serialize + Clone>(
     payload: T,
     timestamp_ms: u64,
     intent: IntentScope,
-) -> ProcessedDataResponse<IntentMessage<T>> {
+) -> Result<ProcessedDataResponse<IntentMessage<T>>, EnclaveError> {
     println!("intent: {:?}", &intent);
     println!("timestamp_ms: {:?}", timestamp_ms);
-    println!("payload: {}", serde_json::to_string(&payload).unwrap());
+    println!("payload: {}", serde_json::to_string(&payload).unwrap_or_default());
 
     let intent_msg = IntentMessage {
         intent,
@@ -82,17 +84,30 @@ pub fn to_signed_response<T: Serialize + Clone>(
         data: payload.clone(),
     };
 
-    let signing_payload = bcs::to_bytes(&intent_msg).expect("should not fail");
+    let signing_payload = bcs::to_bytes(&intent_msg).map_err(|e| {
+        EnclaveError::SigningFailure(format!("Failed to serialize intent message: {}", e))
+    })?;
     println!("signing_payload: {:?}", Hex::encode(signing_payload.as_slice()));
     let sig = kp.sign(&signing_payload);
-    ProcessedDataResponse {
+    Ok(ProcessedDataResponse {
         response: intent_msg,
         signature: sig.as_ref().to_vec(),
-    }
+    })
 }
 
 /// ==== HEALTHCHECK, GET ATTESTASTION ENDPOINT IMPL ====
 
+/// Largest nonce and user data the NSM accepts, in bytes.
+pub const MAX_ATTESTATION_DATA_LEN: usize = 512;
+
+/// Caller data bound into the attestation, both hex encoded. A verifier picks a fresh
+/// nonce to prove the document is not replayed, user data carries e.g. a config hash.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GetAttestationRequest {
+    pub nonce: Option<String>,
+    pub user_data: Option<String>,
+}
+
 /// Response for get attestation.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetAttestationResponse {
@@ -100,38 +115,82 @@ pub struct GetAttestationResponse {
     pub attestation: String,
 }
 
-/// Endpoint that returns an attestation committed
-/// to the enclave's public key.
+/// Endpoint that returns an attestation committed to the enclave's public key and
+/// to the `nonce` and `user_data` query parameters, if any.
 pub async fn get_attestation(
     State(state): State<Arc<AppState>>,
+    Query(request): Query<GetAttestationRequest>,
 ) -> Result<Json<GetAttestationResponse>, EnclaveError> {
     info!("get attestation called");
+    attest(&state, &request)
+}
 
-    let pk = state.eph_kp.public();
-    let fd = driver::nsm_init();
+/// Same as `get_attestation`, with the nonce and user data in the JSON body.
+pub async fn post_attestation(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<GetAttestationRequest>,
+) -> Result<Json<GetAttestationResponse>, EnclaveError> {
+    info!("post attestation called");
+    attest(&state, &request)
+}
 
-    // Send attestation request to NSM driver with public key set.
-    let request = NsmRequest::Attestation {
-        user_data: None,
-        nonce: None,
-        public_key: Some(ByteBuf::from(pk.as_bytes().to_vec())),
+/// Decode a hex encoded nonce or user data, with or without the `0x` prefix.
+fn decode_attestation_data(
+    name: &str,
+    value: Option<&str>,
+) -> Result<Option<Vec<u8>>, EnclaveError> {
+    let Some(value) = value else {
+        return Ok(None);
     };
-
-    let response = driver::nsm_process_request(fd, request);
-    match response {
-        NsmResponse::Attestation { document } => {
-            driver::nsm_exit(fd);
-            Ok(Json(GetAttestationResponse {
-                attestation: Hex::encode(document),
-            }))
-        }
-        _ => {
-            driver::nsm_exit(fd);
-            Err(EnclaveError::GenericError(
-                "unexpected response".to_string(),
-            ))
-        }
+    let bytes = hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid {} hex: {}", name, e)))?;
+    if bytes.len() > MAX_ATTESTATION_DATA_LEN {
+        return Err(EnclaveError::GenericError(format!(
+            "{} is {} bytes, at most {} are allowed",
+            name,
+            bytes.len(),
+            MAX_ATTESTATION_DATA_LEN
+        )));
     }
+    Ok(Some(bytes))
+}
+
+fn attest(
+    state: &AppState,
+    request: &GetAttestationRequest,
+) -> Result<Json<GetAttestationResponse>, EnclaveError> {
+    let nonce = decode_attestation_data("nonce", request.nonce.as_deref())?;
+    let user_data = decode_attestation_data("user_data", request.user_data.as_deref())?;
+    let eph_kp = state.eph_kp();
+    let pk = eph_kp.public();
+
+    // Send attestation request to the NSM with public key set.
+    let document = state
+        .nsm
+        .attestation(Some(pk.as_bytes().to_vec()), user_data, nonce)
+        .map_err(|e| EnclaveError::AttestationFailure(e.to_string()))?;
+    Ok(Json(GetAttestationResponse {
+        attestation: Hex::encode(document),
+    }))
+}
+
+/// Response for get sealed key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SealedKeyResponse {
+    /// Seed of the enclave key sealed with KMS, base64 encoded. Pass it back as
+    /// `EPH_SEALED_KEY` to keep the key across restarts.
+    pub sealed_key: String,
+}
+
+/// Endpoint that returns the sealed enclave key. Only an enclave satisfying the KMS
+/// key policy can unseal it, so it is safe to hand to the host.
+pub async fn get_sealed_key(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SealedKeyResponse>, EnclaveError> {
+    let sealed_key = state
+        .sealed_key()
+        .ok_or_else(|| EnclaveError::GenericError("The enclave key is not sealed".to_string()))?;
+    Ok(Json(SealedKeyResponse { sealed_key }))
 }
 
 /// Health check response.
@@ -148,7 +207,8 @@ pub struct HealthCheckResponse {
 pub async fn health_check(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<HealthCheckResponse>, EnclaveError> {
-    let pk = state.eph_kp.public();
+    let eph_kp = state.eph_kp();
+    let pk = eph_kp.public();
 
     // Create HTTP client with timeout
     let client = Client::builder()
@@ -271,4 +331,62 @@ pub fn parse_sui_privkey(bech: &str) -> Result<Ed25519PrivateKey> {
 pub fn construct_kp_from_bech32_string(bech: &str) -> Result<Ed25519PrivateKey> {
     let key = parse_sui_privkey(bech)?;
     Ok(key)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::StatusCode;
+    use nsm::{MockNsm, NitroNsm};
+    use get_attestation_cli::verifier;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_decode_attestation_data() {
+        assert_eq!(decode_attestation_data("nonce", None).unwrap(), None);
+        assert_eq!(
+            decode_attestation_data("nonce", Some("0x0aff")).unwrap(),
+            Some(vec![10, 255])
+        );
+        assert!(decode_attestation_data("nonce", Some("xyz")).is_err());
+        let too_long = "00".repeat(MAX_ATTESTATION_DATA_LEN + 1);
+        assert!(decode_attestation_data("user_data", Some(&too_long)).is_err());
+    }
+
+    #[test]
+    fn test_attest_with_mock_nsm() {
+        let nsm = MockNsm::new(BTreeMap::from([(0, vec![1; 48])])).unwrap();
+        let root = nsm.root().clone();
+        let state = AppState::for_test(nsm);
+        let request = GetAttestationRequest {
+            nonce: Some("0a0b".to_string()),
+            user_data: None,
+        };
+        let Json(response) = attest(&state, &request).unwrap();
+
+        let document = Hex::decode(&response.attestation).unwrap();
+        let now_secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let attestation = verifier::verify(&document, &root, now_secs).unwrap();
+        attestation.check_nonce(&[10, 11]).unwrap();
+        assert_eq!(attestation.pcrs[&0], vec![1; 48]);
+        assert_eq!(
+            attestation.public_key.unwrap(),
+            state.eph_kp().public().as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_attest_without_nsm() {
+        // no /dev/nsm outside an enclave, a server side failure rather than a bad request
+        let state = AppState::for_test(NitroNsm);
+        let request = GetAttestationRequest {
+            nonce: None,
+            user_data: None,
+        };
+        let err = attest(&state, &request).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.code(), "ATTESTATION_FAILURE");
+    }
+}
@@ -0,0 +1,771 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::transactions_builder::constant::{
+    BLUEFIN_GLOBAL_CONFIG_ID, BLUEFIN_PACKAGE_ID, BLUEFIN_POSITION_TYPE,
+    BLUEFIN_SWAP_EVENT_TYPE, CETUS_AGGREGATOR_V1_PACKAGE_ID, CETUS_INTEGRATE_PACKAGE_ID,
+    CETUS_PACKAGE_ID, CETUS_PARTNER_ID, CETUS_POSITION_TYPE, CETUS_SWAP_EVENT_TYPE,
+    FLOWX_PACKAGE_ID, FLOWX_POOL_REGISTRY_ID, FLOWX_POSITION_REGISTRY_ID, FLOWX_POSITION_TYPE,
+    FLOWX_SWAP_EVENT_TYPE, FLOWX_VERSIONED_ID, GLOBAL_CONFIG_ID,
+    GLOBAL_CONFIG_OBJECT_ID,
+    INTEGER_MATE_PACKAGE_ID, KURAGE_PACKAGE_ID, MMT_PACKAGE_ID, MMT_POSITION_TYPE,
+    MMT_SWAP_EVENT_TYPE, MMT_VERSION_ID, REGISTRY_BAG_OBJECT_ID, REGISTRY_OBJECT_ID,
+    REWARDERS_GLOBAL_VAULT_ID,
+};
+use anyhow::Context;
+use nsm::NsmMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use sui_graphql_client::Client as GraphQLClient;
+use tracing::info;
+
+/// Default location of the config file, relative to the working directory of the server.
+pub const DEFAULT_CONFIG_PATH: &str = "config.yaml";
+
+/// Sui network the server talks to. Selects the default RPC and GraphQL endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl Network {
+    pub fn default_rpc_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://fullnode.mainnet.sui.io:443",
+            Network::Testnet => "https://fullnode.testnet.sui.io:443",
+            Network::Devnet => "https://fullnode.devnet.sui.io:443",
+            Network::Localnet => "http://127.0.0.1:9000",
+        }
+    }
+
+    pub fn default_graphql_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://sui-mainnet.mystenlabs.com/graphql",
+            Network::Testnet => "https://sui-testnet.mystenlabs.com/graphql",
+            Network::Devnet => "https://sui-devnet.mystenlabs.com/graphql",
+            Network::Localnet => "http://127.0.0.1:9125/graphql",
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            "localnet" | "local" => Ok(Network::Localnet),
+            _ => Err(anyhow::anyhow!("Unknown network '{}'", s)),
+        }
+    }
+}
+
+/// Package and object IDs of the kurage (strategy/registry) contracts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KurageConfig {
+    pub package_id: String,
+    pub integer_mate_package_id: String,
+    pub registry_object_id: String,
+    pub registry_bag_object_id: String,
+    pub global_config_object_id: String,
+}
+
+impl Default for KurageConfig {
+    fn default() -> Self {
+        Self {
+            package_id: KURAGE_PACKAGE_ID.to_string(),
+            integer_mate_package_id: INTEGER_MATE_PACKAGE_ID.to_string(),
+            registry_object_id: REGISTRY_OBJECT_ID.to_string(),
+            registry_bag_object_id: REGISTRY_BAG_OBJECT_ID.to_string(),
+            global_config_object_id: GLOBAL_CONFIG_OBJECT_ID.to_string(),
+        }
+    }
+}
+
+/// Package and object IDs of the Cetus CLMM and its aggregator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CetusConfig {
+    pub package_id: String,
+    pub integrate_package_id: String,
+    pub global_config_id: String,
+    pub rewarders_global_vault_id: String,
+    pub partner_id: String,
+    pub aggregator_v1_package_id: String,
+    pub position_type: String,
+    pub swap_event_type: String,
+}
+
+impl Default for CetusConfig {
+    fn default() -> Self {
+        Self {
+            package_id: CETUS_PACKAGE_ID.to_string(),
+            integrate_package_id: CETUS_INTEGRATE_PACKAGE_ID.to_string(),
+            global_config_id: GLOBAL_CONFIG_ID.to_string(),
+            rewarders_global_vault_id: REWARDERS_GLOBAL_VAULT_ID.to_string(),
+            partner_id: CETUS_PARTNER_ID.to_string(),
+            aggregator_v1_package_id: CETUS_AGGREGATOR_V1_PACKAGE_ID.to_string(),
+            position_type: CETUS_POSITION_TYPE.to_string(),
+            swap_event_type: CETUS_SWAP_EVENT_TYPE.to_string(),
+        }
+    }
+}
+
+/// Package and object IDs of the Momentum (MMT) CLMM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MmtConfig {
+    pub package_id: String,
+    /// Shared `Version` object every MMT entry point checks against.
+    pub version_id: String,
+    pub position_type: String,
+    pub swap_event_type: String,
+}
+
+impl Default for MmtConfig {
+    fn default() -> Self {
+        Self {
+            package_id: MMT_PACKAGE_ID.to_string(),
+            version_id: MMT_VERSION_ID.to_string(),
+            position_type: MMT_POSITION_TYPE.to_string(),
+            swap_event_type: MMT_SWAP_EVENT_TYPE.to_string(),
+        }
+    }
+}
+
+/// Package and object IDs of the Bluefin spot CLMM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BluefinConfig {
+    pub package_id: String,
+    pub global_config_id: String,
+    pub position_type: String,
+    pub swap_event_type: String,
+}
+
+impl Default for BluefinConfig {
+    fn default() -> Self {
+        Self {
+            package_id: BLUEFIN_PACKAGE_ID.to_string(),
+            global_config_id: BLUEFIN_GLOBAL_CONFIG_ID.to_string(),
+            position_type: BLUEFIN_POSITION_TYPE.to_string(),
+            swap_event_type: BLUEFIN_SWAP_EVENT_TYPE.to_string(),
+        }
+    }
+}
+
+/// Package and object IDs of the FlowX v3 CLMM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlowXConfig {
+    pub package_id: String,
+    /// FlowX pools are stored under this registry rather than shared individually.
+    pub pool_registry_id: String,
+    pub position_registry_id: String,
+    pub versioned_id: String,
+    pub position_type: String,
+    pub swap_event_type: String,
+    /// Aggregator package exposing the `flowx_clmm` swap module used for FlowX hops.
+    pub aggregator_package_id: String,
+}
+
+impl Default for FlowXConfig {
+    fn default() -> Self {
+        Self {
+            package_id: FLOWX_PACKAGE_ID.to_string(),
+            pool_registry_id: FLOWX_POOL_REGISTRY_ID.to_string(),
+            position_registry_id: FLOWX_POSITION_REGISTRY_ID.to_string(),
+            versioned_id: FLOWX_VERSIONED_ID.to_string(),
+            position_type: FLOWX_POSITION_TYPE.to_string(),
+            swap_event_type: FLOWX_SWAP_EVENT_TYPE.to_string(),
+            aggregator_package_id: CETUS_AGGREGATOR_V1_PACKAGE_ID.to_string(),
+        }
+    }
+}
+
+/// Move aborts raised by `module`, limited to `codes` unless it is empty.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbortRule {
+    pub module: String,
+    #[serde(default)]
+    pub codes: Vec<u64>,
+}
+
+impl AbortRule {
+    pub fn new(module: &str) -> Self {
+        Self {
+            module: module.to_string(),
+            codes: vec![],
+        }
+    }
+
+    pub fn matches(&self, module: &str, code: u64) -> bool {
+        self.module == module && (self.codes.is_empty() || self.codes.contains(&code))
+    }
+}
+
+/// How a failed execution is retried, see `transactions_builder::retry`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    /// Backoff before the second attempt, doubled on every following one.
+    pub base_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Aborts that fail the same way however often they are retried, e.g. a
+    /// rebalance cooldown that has not elapsed or a paused strategy.
+    pub fatal_aborts: Vec<AbortRule>,
+    /// Aborts of a slippage check, retried at once with a fresh quote.
+    pub requote_aborts: Vec<AbortRule>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            base_backoff_ms: 500,
+            max_backoff_ms: 10000,
+            fatal_aborts: vec![AbortRule::new("auto_rebalance"), AbortRule::new("registry")],
+            requote_aborts: vec![AbortRule::new("utils")],
+        }
+    }
+}
+
+/// Limits the simulated transaction must respect before the server signs it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreflightConfig {
+    /// Largest net gas cost in MIST, computation and storage minus the rebate.
+    pub max_gas_mist: u64,
+    /// Largest value the transaction may pay out to addresses, in basis points of
+    /// the position's marked value.
+    pub max_value_loss_bps: u64,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            max_gas_mist: 50000000,
+            max_value_loss_bps: 100,
+        }
+    }
+}
+
+/// Gas coins the sender's SUI is kept split into, so concurrent jobs never spend
+/// the same coin. See `gas_pool::GasPool`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GasPoolConfig {
+    /// Number of gas coins, 0 disables the pool and each job picks a coin itself.
+    pub size: usize,
+    /// A coin below this balance in MIST makes the pool split the SUI again.
+    pub min_coin_balance: u64,
+    /// Gas budget of the transaction merging and splitting the coins.
+    pub rebalance_gas_budget: u64,
+    pub refresh_interval_secs: u64,
+    /// How long a job waits for an idle coin before the attempt fails.
+    pub lease_timeout_secs: u64,
+}
+
+impl Default for GasPoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 8,
+            min_coin_balance: 200000000,
+            rebalance_gas_budget: 50000000,
+            refresh_interval_secs: 60,
+            lease_timeout_secs: 30,
+        }
+    }
+}
+
+/// Who signs as gas owner of the transactions the keeper sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SponsorMode {
+    /// The keeper pays gas from its own coins.
+    Disabled,
+    /// A sponsor key held by the server, read from `SPONSOR_PK`.
+    Local,
+    /// A sponsor service outside the enclave, called at `SponsorConfig::url`.
+    Remote,
+}
+
+impl FromStr for SponsorMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "disabled" | "none" => Ok(SponsorMode::Disabled),
+            "local" => Ok(SponsorMode::Local),
+            "remote" => Ok(SponsorMode::Remote),
+            _ => Err(anyhow::anyhow!("Unknown sponsor mode '{}'", s)),
+        }
+    }
+}
+
+/// Gas sponsorship, see `sponsor::Sponsor`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SponsorConfig {
+    pub mode: SponsorMode,
+    /// Gas owner address, required in remote mode.
+    pub address: Option<String>,
+    /// Endpoint signing sponsored transactions, required in remote mode.
+    pub url: Option<String>,
+}
+
+impl Default for SponsorConfig {
+    fn default() -> Self {
+        Self {
+            mode: SponsorMode::Disabled,
+            address: None,
+            url: None,
+        }
+    }
+}
+
+/// What makes the keeper evaluate a strategy between two discoveries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeeperTrigger {
+    /// Read every pool each `poll_interval_secs`.
+    Poll,
+    /// Follow the checkpoint stream and evaluate the strategies whose acceptable sqrt
+    /// price band a swap left. Pools are only read in full once per discovery.
+    Checkpoints,
+}
+
+impl FromStr for KeeperTrigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "poll" => Ok(KeeperTrigger::Poll),
+            "checkpoints" => Ok(KeeperTrigger::Checkpoints),
+            _ => Err(anyhow::anyhow!("Unknown keeper trigger '{}'", s)),
+        }
+    }
+}
+
+/// Built-in scheduler that rebalances every strategy without `/process_data_v2`
+/// being called, see `keeper::Keeper`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeeperConfig {
+    pub enabled: bool,
+    /// Enclave object the rebalance transactions are attested with, required when enabled.
+    /// Replaced by the new enclave when the key is rotated.
+    pub enclave_id: Option<String>,
+    /// Move type of the strategy objects, defaults to the kurage `AutoRebalanceStrategy`.
+    pub strategy_type: Option<String>,
+    pub trigger: KeeperTrigger,
+    /// How often the pools are polled for price changes, and how long to wait before
+    /// subscribing again when the checkpoint stream fails.
+    pub poll_interval_secs: u64,
+    /// How often the registry and strategy objects are enumerated again.
+    pub discovery_interval_secs: u64,
+    /// Largest number of rebalance jobs the keeper runs at once.
+    pub max_concurrency: usize,
+}
+
+impl KeeperConfig {
+    pub fn strategy_type(&self, kurage: &KurageConfig) -> String {
+        self.strategy_type.clone().unwrap_or_else(|| {
+            format!("{}::auto_rebalance::AutoRebalanceStrategy", kurage.package_id)
+        })
+    }
+}
+
+impl Default for KeeperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            enclave_id: None,
+            strategy_type: None,
+            trigger: KeeperTrigger::Checkpoints,
+            poll_interval_secs: 15,
+            discovery_interval_secs: 300,
+            max_concurrency: 4,
+        }
+    }
+}
+
+/// Where the enclave signing key comes from, see `enclave_key::load`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnclaveKeyMode {
+    /// A new key from NSM entropy on every boot.
+    Ephemeral,
+    /// A key sealed with KMS, unsealed on boot from `EPH_SEALED_KEY`.
+    Sealed,
+}
+
+impl FromStr for EnclaveKeyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ephemeral" => Ok(EnclaveKeyMode::Ephemeral),
+            "sealed" => Ok(EnclaveKeyMode::Sealed),
+            _ => Err(anyhow::anyhow!("Unknown enclave key mode '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnclaveKeyConfig {
+    pub mode: EnclaveKeyMode,
+    /// KMS key the seed is sealed with, required in sealed mode.
+    pub kms_key_id: Option<String>,
+    /// Region of the KMS key, required in sealed mode.
+    pub kms_region: Option<String>,
+}
+
+impl Default for EnclaveKeyConfig {
+    fn default() -> Self {
+        Self {
+            mode: EnclaveKeyMode::Ephemeral,
+            kms_key_id: None,
+            kms_region: None,
+        }
+    }
+}
+
+/// On-chain `Enclave` registration `/rotate_key` submits for the new key, see
+/// `rotation`. The transaction is only built when all three are set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RotationConfig {
+    /// Package of the `enclave` module.
+    pub enclave_package_id: Option<String>,
+    /// Shared `EnclaveConfig<T>` holding the expected PCRs.
+    pub enclave_config_id: Option<String>,
+    /// Type argument `T` of the enclave config, e.g. `0x...::kurage::KURAGE`.
+    pub enclave_type: Option<String>,
+}
+
+/// Where attestations and entropy come from, see the `nsm` crate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NsmConfig {
+    pub mode: NsmMode,
+    /// PCRs reported by the mock, hex encoded by index. PCRs left out are zeros.
+    pub pcrs: BTreeMap<usize, String>,
+}
+
+impl Default for NsmConfig {
+    fn default() -> Self {
+        Self {
+            mode: NsmMode::Nitro,
+            pcrs: BTreeMap::new(),
+        }
+    }
+}
+
+impl NsmConfig {
+    pub fn pcrs(&self) -> Result<BTreeMap<usize, Vec<u8>>, anyhow::Error> {
+        self.pcrs
+            .iter()
+            .map(|(index, pcr)| {
+                let pcr = hex::decode(pcr.trim_start_matches("0x"))
+                    .with_context(|| format!("nsm.pcrs.{} is not hex: {}", index, pcr))?;
+                Ok((*index, pcr))
+            })
+            .collect()
+    }
+}
+
+/// Runtime configuration of the server, loaded once at boot and held in `AppState`.
+///
+/// Values are read from a YAML file (see `DEFAULT_CONFIG_PATH`, overridable with
+/// `NAUTILUS_CONFIG`) and then from environment variables, which take precedence.
+/// Missing fields fall back to the mainnet deployment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub network: Network,
+    /// gRPC fullnode URL, defaults to the network's public fullnode.
+    pub rpc_url: Option<String>,
+    /// GraphQL URL, defaults to the network's public GraphQL service.
+    pub graphql_url: Option<String>,
+    pub listen_addr: String,
+    /// Gas budget in MIST transactions are simulated with, and the cap of the budget
+    /// estimated from the simulation.
+    pub gas_budget: u64,
+    /// Margin added to the simulated computation and storage cost, in basis points.
+    pub gas_budget_margin_bps: u64,
+    /// Largest share of either coin a zap may leave unadded, scaled by 1,000,000,000.
+    pub zap_max_remain_rate: u64,
+    pub kurage: KurageConfig,
+    pub cetus: CetusConfig,
+    pub mmt: MmtConfig,
+    pub bluefin: BluefinConfig,
+    pub flowx: FlowXConfig,
+    pub retry: RetryConfig,
+    pub preflight: PreflightConfig,
+    pub gas_pool: GasPoolConfig,
+    pub sponsor: SponsorConfig,
+    pub keeper: KeeperConfig,
+    pub nsm: NsmConfig,
+    pub enclave_key: EnclaveKeyConfig,
+    pub rotation: RotationConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            network: Network::Mainnet,
+            rpc_url: None,
+            graphql_url: None,
+            listen_addr: "0.0.0.0:3000".to_string(),
+            gas_budget: 100000000,
+            gas_budget_margin_bps: 2000,
+            zap_max_remain_rate: 2000000,
+            kurage: KurageConfig::default(),
+            cetus: CetusConfig::default(),
+            mmt: MmtConfig::default(),
+            bluefin: BluefinConfig::default(),
+            flowx: FlowXConfig::default(),
+            retry: RetryConfig::default(),
+            preflight: PreflightConfig::default(),
+            gas_pool: GasPoolConfig::default(),
+            sponsor: SponsorConfig::default(),
+            keeper: KeeperConfig::default(),
+            nsm: NsmConfig::default(),
+            enclave_key: EnclaveKeyConfig::default(),
+            rotation: RotationConfig::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Load the config file (if present) and apply environment overrides.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path =
+            std::env::var("NAUTILUS_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(content) => Self::from_yaml(&content)
+                .with_context(|| format!("Failed to parse config file {}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("Config file {} not found, using defaults", path);
+                Self::default()
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path)),
+        };
+        config.apply_env_overrides(|key| std::env::var(key).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, anyhow::Error> {
+        Ok(serde_yaml::from_str(content)?)
+    }
+
+    /// Override fields from environment variables. `get_var` is injected so tests
+    /// don't need to mutate the process environment. `nsm` and `enclave_key` are
+    /// only read from the config file: it is measured into the PCRs, while the host
+    /// controls the environment and could otherwise mock the NSM or seal the key
+    /// with a KMS key of its own.
+    pub fn apply_env_overrides<F>(&mut self, get_var: F) -> Result<(), anyhow::Error>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(network) = get_var("SUI_NETWORK") {
+            self.network = network.parse()?;
+        }
+        if let Some(rpc_url) = get_var("SUI_RPC_URL") {
+            self.rpc_url = Some(rpc_url);
+        }
+        if let Some(graphql_url) = get_var("SUI_GRAPHQL_URL") {
+            self.graphql_url = Some(graphql_url);
+        }
+        if let Some(listen_addr) = get_var("LISTEN_ADDR") {
+            self.listen_addr = listen_addr;
+        }
+        if let Some(gas_budget) = get_var("GAS_BUDGET") {
+            self.gas_budget = gas_budget
+                .parse()
+                .with_context(|| format!("GAS_BUDGET is not a number: {}", gas_budget))?;
+        }
+        if let Some(gas_budget_margin_bps) = get_var("GAS_BUDGET_MARGIN_BPS") {
+            self.gas_budget_margin_bps = gas_budget_margin_bps.parse().with_context(|| {
+                format!("GAS_BUDGET_MARGIN_BPS is not a number: {}", gas_budget_margin_bps)
+            })?;
+        }
+        if let Some(zap_max_remain_rate) = get_var("ZAP_MAX_REMAIN_RATE") {
+            self.zap_max_remain_rate = zap_max_remain_rate.parse().with_context(|| {
+                format!("ZAP_MAX_REMAIN_RATE is not a number: {}", zap_max_remain_rate)
+            })?;
+        }
+        if let Some(size) = get_var("GAS_POOL_SIZE") {
+            self.gas_pool.size = size
+                .parse()
+                .with_context(|| format!("GAS_POOL_SIZE is not a number: {}", size))?;
+        }
+        if let Some(mode) = get_var("SPONSOR_MODE") {
+            self.sponsor.mode = mode.parse()?;
+        }
+        if let Some(address) = get_var("SPONSOR_ADDRESS") {
+            self.sponsor.address = Some(address);
+        }
+        if let Some(url) = get_var("SPONSOR_URL") {
+            self.sponsor.url = Some(url);
+        }
+        if let Some(enabled) = get_var("KEEPER_ENABLED") {
+            self.keeper.enabled = enabled
+                .parse()
+                .with_context(|| format!("KEEPER_ENABLED is not a bool: {}", enabled))?;
+        }
+        if let Some(trigger) = get_var("KEEPER_TRIGGER") {
+            self.keeper.trigger = trigger.parse()?;
+        }
+        if let Some(enclave_id) = get_var("KEEPER_ENCLAVE_ID") {
+            self.keeper.enclave_id = Some(enclave_id);
+        }
+        if let Some(max_concurrency) = get_var("KEEPER_MAX_CONCURRENCY") {
+            self.keeper.max_concurrency = max_concurrency.parse().with_context(|| {
+                format!("KEEPER_MAX_CONCURRENCY is not a number: {}", max_concurrency)
+            })?;
+        }
+        if let Some(enclave_package_id) = get_var("ENCLAVE_PACKAGE_ID") {
+            self.rotation.enclave_package_id = Some(enclave_package_id);
+        }
+        if let Some(enclave_config_id) = get_var("ENCLAVE_CONFIG_ID") {
+            self.rotation.enclave_config_id = Some(enclave_config_id);
+        }
+        if let Some(enclave_type) = get_var("ENCLAVE_TYPE") {
+            self.rotation.enclave_type = Some(enclave_type);
+        }
+        if let Some(max_attempts) = get_var("RETRY_MAX_ATTEMPTS") {
+            self.retry.max_attempts = max_attempts
+                .parse()
+                .with_context(|| format!("RETRY_MAX_ATTEMPTS is not a number: {}", max_attempts))?;
+        }
+        if let Some(package_id) = get_var("KURAGE_PACKAGE_ID") {
+            self.kurage.package_id = package_id;
+        }
+        if let Some(registry_object_id) = get_var("KURAGE_REGISTRY_OBJECT_ID") {
+            self.kurage.registry_object_id = registry_object_id;
+        }
+        if let Some(registry_bag_object_id) = get_var("KURAGE_REGISTRY_BAG_OBJECT_ID") {
+            self.kurage.registry_bag_object_id = registry_bag_object_id;
+        }
+        if let Some(global_config_object_id) = get_var("KURAGE_GLOBAL_CONFIG_OBJECT_ID") {
+            self.kurage.global_config_object_id = global_config_object_id;
+        }
+        Ok(())
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        self.rpc_url
+            .as_deref()
+            .unwrap_or_else(|| self.network.default_rpc_url())
+    }
+
+    pub fn graphql_url(&self) -> &str {
+        self.graphql_url
+            .as_deref()
+            .unwrap_or_else(|| self.network.default_graphql_url())
+    }
+
+    pub fn rpc_client(&self) -> Result<sui_rpc::Client, anyhow::Error> {
+        sui_rpc::Client::new(self.rpc_url())
+            .map_err(|e| anyhow::anyhow!("Failed to create rpc client: {}", e))
+    }
+
+    pub fn graphql_client(&self) -> Result<GraphQLClient, anyhow::Error> {
+        GraphQLClient::new(self.graphql_url())
+            .map_err(|e| anyhow::anyhow!("Failed to create graphql client: {}", e))
+    }
+}
+
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_network_defaults() {
+        let config = AppConfig::from_yaml("network: testnet\nlisten_addr: 127.0.0.1:4000\n").unwrap();
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.rpc_url(), "https://fullnode.testnet.sui.io:443");
+        assert_eq!(config.listen_addr, "127.0.0.1:4000");
+        assert_eq!(config.gas_budget, 100000000);
+        assert_eq!(config.gas_budget_margin_bps, 2000);
+        assert_eq!(config.zap_max_remain_rate, 2000000);
+        assert_eq!(config.kurage.package_id, KURAGE_PACKAGE_ID);
+        assert_eq!(config.retry.max_attempts, 10);
+        assert_eq!(config.preflight.max_value_loss_bps, 100);
+        assert_eq!(config.gas_pool.size, 8);
+        assert!(!config.keeper.enabled);
+        assert_eq!(config.keeper.trigger, KeeperTrigger::Checkpoints);
+        assert_eq!(
+            config.keeper.strategy_type(&config.kurage),
+            format!("{}::auto_rebalance::AutoRebalanceStrategy", KURAGE_PACKAGE_ID)
+        );
+        assert!(config.retry.requote_aborts[0].matches("utils", 0));
+    }
+
+    #[test]
+    fn test_retry_rules() {
+        let config = AppConfig::from_yaml(
+            "retry:\n  fatal_aborts:\n    - module: auto_rebalance\n      codes: [1, 2]\n",
+        )
+        .unwrap();
+        let rule = &config.retry.fatal_aborts[0];
+        assert!(rule.matches("auto_rebalance", 2));
+        assert!(!rule.matches("auto_rebalance", 3));
+        assert!(!rule.matches("registry", 1));
+        assert_eq!(config.retry.requote_aborts, vec![AbortRule::new("utils")]);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = AppConfig::from_yaml("cetus:\n  partner_id: \"0x1\"\n").unwrap();
+        config
+            .apply_env_overrides(|key| match key {
+                "SUI_NETWORK" => Some("localnet".to_string()),
+                "SUI_GRAPHQL_URL" => Some("http://localhost:8000/graphql".to_string()),
+                "GAS_BUDGET" => Some("5000000".to_string()),
+                "RETRY_MAX_ATTEMPTS" => Some("3".to_string()),
+                "GAS_POOL_SIZE" => Some("0".to_string()),
+                "SPONSOR_MODE" => Some("remote".to_string()),
+                "SPONSOR_URL" => Some("http://localhost:9100/sponsor".to_string()),
+                "KEEPER_ENABLED" => Some("true".to_string()),
+                "KEEPER_TRIGGER" => Some("poll".to_string()),
+                "KEEPER_MAX_CONCURRENCY" => Some("2".to_string()),
+                "NSM_MODE" => Some("mock".to_string()),
+                "ENCLAVE_KEY_MODE" => Some("sealed".to_string()),
+                "KMS_KEY_ID" => Some("alias/attacker".to_string()),
+                "ENCLAVE_CONFIG_ID" => Some("0x4".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.network, Network::Localnet);
+        assert_eq!(config.rpc_url(), "http://127.0.0.1:9000");
+        assert_eq!(config.graphql_url(), "http://localhost:8000/graphql");
+        assert_eq!(config.gas_budget, 5000000);
+        assert_eq!(config.retry.max_attempts, 3);
+        assert_eq!(config.gas_pool.size, 0);
+        assert_eq!(config.sponsor.mode, SponsorMode::Remote);
+        assert_eq!(config.sponsor.url.as_deref(), Some("http://localhost:9100/sponsor"));
+        assert!(config.keeper.enabled);
+        assert_eq!(config.keeper.max_concurrency, 2);
+        assert_eq!(config.keeper.trigger, KeeperTrigger::Poll);
+        // the measured config file alone decides the NSM and the enclave key
+        assert_eq!(config.nsm.mode, NsmMode::Nitro);
+        assert_eq!(config.enclave_key.mode, EnclaveKeyMode::Ephemeral);
+        assert_eq!(config.enclave_key.kms_key_id, None);
+        assert_eq!(config.rotation.enclave_config_id.as_deref(), Some("0x4"));
+        assert_eq!(config.rotation.enclave_package_id, None);
+        assert_eq!(config.cetus.partner_id, "0x1");
+        assert_eq!(config.cetus.package_id, CETUS_PACKAGE_ID);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::AppConfig;
+use crate::dex::{self, DexAdapter, DexContext};
+use crate::parsers::{self, pools, positions};
+use crate::parsers::{PendingRewards, Pool, PoolSummary, Position, PositionSummary, SupportedDex};
+use crate::transactions_builder::bluefin::BluefinTransactionBuilder;
+use crate::transactions_builder::types::*;
+use async_trait::async_trait;
+use std::str::FromStr;
+use sui_graphql_client::Client;
+use sui_sdk_types::{Argument, StructTag, TypeTag};
+
+pub struct BluefinAdapter;
+
+impl BluefinAdapter {
+    async fn builder<'b>(ctx: &'b mut DexContext<'_>) -> BluefinTransactionBuilder<'b> {
+        BluefinTransactionBuilder::new(ctx.client, ctx.tx, ctx.arg_cache, ctx.config).await
+    }
+}
+
+#[async_trait]
+impl DexAdapter for BluefinAdapter {
+    fn dex(&self) -> SupportedDex {
+        SupportedDex::Bluefin
+    }
+
+    fn position_type(&self, config: &AppConfig) -> String {
+        config.bluefin.position_type.clone()
+    }
+
+    fn swap_event_type(&self, config: &AppConfig) -> String {
+        config.bluefin.swap_event_type.clone()
+    }
+
+    fn parse_pool(&self, json: &Box<prost_types::Value>) -> Result<Pool, anyhow::Error> {
+        Ok(Pool::Bluefin(pools::bluefin::map_pool_data(json)?))
+    }
+
+    fn parse_position(&self, json: &Box<serde_json::Value>) -> Result<Position, anyhow::Error> {
+        Ok(Position::Bluefin(positions::bluefin::map_position_data(json)?))
+    }
+
+    fn pool_summary(&self, pool: &Pool) -> Result<PoolSummary, anyhow::Error> {
+        let Pool::Bluefin(bluefin_pool) = pool else {
+            return Err(anyhow::anyhow!("Expected a Bluefin pool, got {:?}", pool.dex()));
+        };
+        let rewarder_coin_types = bluefin_pool
+            .reward_infos
+            .iter()
+            .map(|reward_info| {
+                let formatted = parsers::format_coin_type(&reward_info.reward_coin_type);
+                Ok(TypeTag::Struct(Box::new(StructTag::from_str(&formatted)?)))
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        Ok(PoolSummary {
+            current_tick_u32: bluefin_pool.current_tick_index.bits.parse::<u32>()?,
+            current_sqrt_price: bluefin_pool.current_sqrt_price.parse::<u128>()?,
+            tick_spacing: bluefin_pool.tick_spacing as u32,
+            fee_rate: bluefin_pool.fee_rate.parse::<u64>()?,
+            liquidity: bluefin_pool.liquidity.parse::<u128>()?,
+            rewarder_coin_types,
+        })
+    }
+
+    fn position_summary(&self, position: &Position) -> Result<PositionSummary, anyhow::Error> {
+        let Position::Bluefin(bluefin_position) = position else {
+            return Err(anyhow::anyhow!("Expected a Bluefin position, got {:?}", position.dex()));
+        };
+        let formatted_coin_a = parsers::format_coin_type(&bluefin_position.coin_type_a);
+        let formatted_coin_b = parsers::format_coin_type(&bluefin_position.coin_type_b);
+        Ok(PositionSummary {
+            tick_lower_index_u32: bluefin_position.lower_tick.bits.parse::<u32>()?,
+            tick_upper_index_u32: bluefin_position.upper_tick.bits.parse::<u32>()?,
+            coin_a_type: TypeTag::Struct(Box::new(StructTag::from_str(&formatted_coin_a)?)),
+            coin_b_type: TypeTag::Struct(Box::new(StructTag::from_str(&formatted_coin_b)?)),
+            liquidity: bluefin_position.liquidity,
+        })
+    }
+
+    async fn pending_rewards(
+        &self,
+        _client: &Client,
+        pool: &Pool,
+        position: &Position,
+    ) -> Result<PendingRewards, anyhow::Error> {
+        let Position::Bluefin(bluefin_position) = position else {
+            return Err(anyhow::anyhow!("Expected a Bluefin position, got {:?}", position.dex()));
+        };
+        // settled at the position's last update only, see `DexAdapter::pending_rewards`
+        Ok(PendingRewards {
+            fee_a: bluefin_position.token_a_fee,
+            fee_b: bluefin_position.token_b_fee,
+            rewards: dex::pending_rewards_from_owed(
+                self.pool_summary(pool)?.rewarder_coin_types,
+                &bluefin_position.coins_owed_reward,
+            ),
+        })
+    }
+
+    async fn collect_fees_and_rewards(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: CollectFeesAndRewardsRequest,
+    ) -> Result<Vec<CollectFeesAndRewardsResult>, anyhow::Error> {
+        Self::builder(ctx).await.collect_fees_and_rewards(request).await
+    }
+
+    async fn zap_out(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: ZapOutRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        Self::builder(ctx).await.zap_out(request).await
+    }
+
+    async fn zap_in(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: ZapInRequest,
+    ) -> Result<(Argument, Argument, Argument), anyhow::Error> {
+        Self::builder(ctx).await.zap_in(request).await
+    }
+
+    async fn swap_by_amount_in(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: SwapByAmountInRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        Self::builder(ctx).await.swap_by_amount_in(request).await
+    }
+
+    async fn add_liquidity(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: AddLiquidityRequest,
+    ) -> Result<(Argument, Argument), anyhow::Error> {
+        Self::builder(ctx).await.add_liquidity(request).await
+    }
+}
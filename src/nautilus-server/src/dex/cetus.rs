@@ -0,0 +1,179 @@
+use crate::config::AppConfig;
+use crate::dex::{self, DexAdapter, DexContext};
+use crate::math::fee_math;
+use crate::math::swap_math::PoolTicks;
+use crate::parsers::{self, pools, positions};
+use crate::parsers::{PendingRewards, Pool, PoolSummary, Position, PositionSummary, SupportedDex};
+use crate::transactions_builder::cetus::CetusTransactionBuilder;
+use crate::transactions_builder::types::*;
+use async_trait::async_trait;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sui_graphql_client::Client;
+use sui_sdk_types::{Address, Argument, StructTag, TypeTag};
+
+pub struct CetusAdapter;
+
+impl CetusAdapter {
+    async fn builder<'b>(ctx: &'b mut DexContext<'_>) -> CetusTransactionBuilder<'b> {
+        CetusTransactionBuilder::new(ctx.client, ctx.tx, ctx.arg_cache, ctx.config).await
+    }
+}
+
+#[async_trait]
+impl DexAdapter for CetusAdapter {
+    fn dex(&self) -> SupportedDex {
+        SupportedDex::Cetus
+    }
+
+    fn position_type(&self, config: &AppConfig) -> String {
+        config.cetus.position_type.clone()
+    }
+
+    fn swap_event_type(&self, config: &AppConfig) -> String {
+        config.cetus.swap_event_type.clone()
+    }
+
+    fn parse_pool(&self, json: &Box<prost_types::Value>) -> Result<Pool, anyhow::Error> {
+        Ok(Pool::Cetus(pools::cetus::map_pool_data(json)?))
+    }
+
+    fn parse_position(&self, json: &Box<serde_json::Value>) -> Result<Position, anyhow::Error> {
+        Ok(Position::Cetus(positions::cetus::map_position_data(json)?))
+    }
+
+    fn pool_summary(&self, pool: &Pool) -> Result<PoolSummary, anyhow::Error> {
+        let Pool::Cetus(cetus_pool) = pool else {
+            return Err(anyhow::anyhow!("Expected a Cetus pool, got {:?}", pool.dex()));
+        };
+        let rewarder_coin_types = cetus_pool
+            .rewarder_manager
+            .rewarders
+            .iter()
+            .map(|rewarder| {
+                Ok(TypeTag::Struct(Box::new(StructTag::from_str(&format!(
+                    "0x{}",
+                    rewarder.reward_coin.name
+                ))?)))
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        Ok(PoolSummary {
+            current_tick_u32: cetus_pool.current_tick_index.bits.parse::<u32>()?,
+            current_sqrt_price: cetus_pool.current_sqrt_price.parse::<u128>()?,
+            tick_spacing: cetus_pool.tick_spacing as u32,
+            fee_rate: cetus_pool.fee_rate.parse::<u64>()?,
+            liquidity: cetus_pool.liquidity.parse::<u128>()?,
+            rewarder_coin_types,
+        })
+    }
+
+    fn position_summary(&self, position: &Position) -> Result<PositionSummary, anyhow::Error> {
+        let Position::Cetus(cetus_position) = position else {
+            return Err(anyhow::anyhow!("Expected a Cetus position, got {:?}", position.dex()));
+        };
+        println!("cetus_position: {:?}", cetus_position);
+        let formatted_coin_a = parsers::format_coin_type(&cetus_position.coin_type_a.name);
+        let formatted_coin_b = parsers::format_coin_type(&cetus_position.coin_type_b.name);
+        Ok(PositionSummary {
+            tick_lower_index_u32: cetus_position.tick_lower_index.bits.parse::<u32>()?,
+            tick_upper_index_u32: cetus_position.tick_upper_index.bits.parse::<u32>()?,
+            coin_a_type: TypeTag::Struct(Box::new(StructTag::from_str(&formatted_coin_a)?)),
+            coin_b_type: TypeTag::Struct(Box::new(StructTag::from_str(&formatted_coin_b)?)),
+            liquidity: cetus_position.liquidity,
+        })
+    }
+
+    // The owed amounts in the position manager are only settled when the position is
+    // touched, so accrue the growth since then from the pool and boundary ticks.
+    async fn pending_rewards(
+        &self,
+        client: &Client,
+        pool: &Pool,
+        position: &Position,
+    ) -> Result<PendingRewards, anyhow::Error> {
+        let (Pool::Cetus(cetus_pool), Position::Cetus(cetus_position)) = (pool, position) else {
+            return Err(anyhow::anyhow!("Expected a Cetus pool and position, got {:?}", pool.dex()));
+        };
+        let position_info_field = client
+            .dynamic_field(
+                Address::from_hex(&cetus_pool.position_manager.positions.id)?,
+                TypeTag::from_str("0x2::object::ID")?,
+                Address::from_hex(&cetus_position.id)?,
+            )
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Position info not found for {}", cetus_position.id))?
+            .value_as_json
+            .ok_or_else(|| anyhow::anyhow!("Position info of {} has no json", cetus_position.id))?;
+        let position_info_json = serde_json::to_value(&position_info_field)?;
+        let position_info = positions::cetus::map_position_info_data(&Box::new(position_info_json))?;
+        let tick_lower_index = cetus_position.tick_lower_index.bits.parse::<u32>()? as i32;
+        let tick_upper_index = cetus_position.tick_upper_index.bits.parse::<u32>()? as i32;
+        let tick_lower = pools::cetus::fetch_tick(client, cetus_pool, tick_lower_index).await?;
+        let tick_upper = pools::cetus::fetch_tick(client, cetus_pool, tick_upper_index).await?;
+        let current_time_secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let pending = fee_math::get_position_fees_and_rewards(
+            cetus_pool,
+            tick_lower.as_ref(),
+            tick_upper.as_ref(),
+            cetus_position,
+            &position_info,
+            current_time_secs,
+        )?;
+        Ok(PendingRewards {
+            fee_a: pending.fee_a,
+            fee_b: pending.fee_b,
+            rewards: dex::pending_rewards_from_owed(
+                self.pool_summary(pool)?.rewarder_coin_types,
+                &pending.rewards,
+            ),
+        })
+    }
+
+    async fn pool_ticks(&self, client: &Client, pool: &Pool) -> Result<PoolTicks, anyhow::Error> {
+        let Pool::Cetus(cetus_pool) = pool else {
+            return Err(anyhow::anyhow!("Expected a Cetus pool, got {:?}", pool.dex()));
+        };
+        let ticks = pools::cetus::fetch_ticks(client, cetus_pool).await?;
+        PoolTicks::from_cetus(cetus_pool.current_tick_index.bits.parse::<u32>()? as i32, &ticks)
+    }
+
+    async fn collect_fees_and_rewards(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: CollectFeesAndRewardsRequest,
+    ) -> Result<Vec<CollectFeesAndRewardsResult>, anyhow::Error> {
+        Self::builder(ctx).await.collect_fees_and_rewards(request).await
+    }
+
+    async fn zap_out(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: ZapOutRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        Self::builder(ctx).await.zap_out(request).await
+    }
+
+    async fn zap_in(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: ZapInRequest,
+    ) -> Result<(Argument, Argument, Argument), anyhow::Error> {
+        Self::builder(ctx).await.zap_in(request).await
+    }
+
+    async fn swap_by_amount_in(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: SwapByAmountInRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        Self::builder(ctx).await.swap_by_amount_in(request).await
+    }
+
+    async fn add_liquidity(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: AddLiquidityRequest,
+    ) -> Result<(Argument, Argument), anyhow::Error> {
+        Self::builder(ctx).await.add_liquidity(request).await
+    }
+}
@@ -0,0 +1,365 @@
+use crate::config::AppConfig;
+use crate::math::swap_math::PoolTicks;
+use crate::parsers::{PendingReward, PendingRewards, Pool, PoolSummary, Position, PositionSummary, SupportedDex};
+use crate::transactions_builder::argument::{self, ArgCache};
+use crate::transactions_builder::types::*;
+use async_trait::async_trait;
+use std::str::FromStr;
+use sui_graphql_client::Client;
+use sui_sdk_types::{Address, Argument, Identifier, StructTag, TypeTag};
+use sui_transaction_builder::{Function, TransactionBuilder};
+
+pub mod bluefin;
+pub mod cetus;
+pub mod flowx;
+pub mod mmt;
+
+/// Borrowed state shared by every adapter while it appends commands to a PTB.
+pub struct DexContext<'a> {
+    pub client: &'a Client,
+    pub tx: &'a mut TransactionBuilder,
+    pub arg_cache: &'a mut ArgCache,
+    pub config: &'a AppConfig,
+}
+
+/// A CLMM integration. Parsing of on-chain pool/position objects and construction of the
+/// DEX specific part of the rebalance PTB live behind this trait, so supporting a new DEX
+/// means implementing it and registering the adapter in `adapters`.
+#[async_trait]
+pub trait DexAdapter: Send + Sync {
+    fn dex(&self) -> SupportedDex;
+
+    /// Fully qualified Move type of the DEX position object held by the registry.
+    fn position_type(&self, config: &AppConfig) -> String;
+
+    /// Fully qualified Move type of the event the DEX emits on every swap.
+    fn swap_event_type(&self, config: &AppConfig) -> String;
+
+    fn parse_pool(&self, json: &Box<prost_types::Value>) -> Result<Pool, anyhow::Error>;
+
+    fn parse_position(&self, json: &Box<serde_json::Value>) -> Result<Position, anyhow::Error>;
+
+    fn pool_summary(&self, pool: &Pool) -> Result<PoolSummary, anyhow::Error>;
+
+    fn position_summary(&self, position: &Position) -> Result<PositionSummary, anyhow::Error>;
+
+    /// Fees and rewards currently owed to `position`. Cetus derives them from the pool
+    /// and tick growth data. The other adapters return what the DEX settled into the
+    /// position at its last update (liquidity change or collect), which leaves out what
+    /// accrued since: the compound threshold is checked against a lower bound there.
+    async fn pending_rewards(
+        &self,
+        client: &Client,
+        pool: &Pool,
+        position: &Position,
+    ) -> Result<PendingRewards, anyhow::Error>;
+
+    /// Initialized ticks a zap swap may cross. Adapters that cannot list their ticks keep
+    /// this default, which prices the swap on the active liquidity alone.
+    async fn pool_ticks(&self, _client: &Client, pool: &Pool) -> Result<PoolTicks, anyhow::Error> {
+        Ok(PoolTicks {
+            current_tick_index: self.pool_summary(pool)?.current_tick_u32 as i32,
+            ticks: vec![],
+        })
+    }
+
+    /// PTB argument handed to the DEX calls as the pool. Pools are shared objects
+    /// on most DEXes, adapters whose pools live elsewhere override this.
+    async fn pool_arg(
+        &self,
+        ctx: &mut DexContext<'_>,
+        pool_id: &str,
+    ) -> Result<Argument, anyhow::Error> {
+        argument::shared_mut_cached(
+            ctx.client,
+            ctx.tx,
+            ctx.arg_cache,
+            Address::from_hex(pool_id)?,
+        )
+        .await
+    }
+
+    async fn collect_fees_and_rewards(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: CollectFeesAndRewardsRequest,
+    ) -> Result<Vec<CollectFeesAndRewardsResult>, anyhow::Error>;
+
+    /// Remove all liquidity, close the position and swap everything into coin A.
+    async fn zap_out(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: ZapOutRequest,
+    ) -> Result<(Argument, u64), anyhow::Error>;
+
+    /// Open a position in the new range funded only by coin A.
+    /// Returns the position and the leftover coin A and coin B.
+    async fn zap_in(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: ZapInRequest,
+    ) -> Result<(Argument, Argument, Argument), anyhow::Error>;
+
+    /// Add liquidity funded only by coin A to an existing position.
+    /// Returns the leftover coin A and coin B.
+    async fn add_liquidity(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: AddLiquidityRequest,
+    ) -> Result<(Argument, Argument), anyhow::Error>;
+
+    async fn swap_by_amount_in(
+        &self,
+        ctx: &mut DexContext<'_>,
+        request: SwapByAmountInRequest,
+    ) -> Result<(Argument, u64), anyhow::Error>;
+
+    /// Collect fees and rewards, swap everything into coin A and add it back as liquidity
+    /// to the same position. Leftovers go to the registry receipt.
+    async fn compound(&self, ctx: &mut DexContext<'_>, compound_data: CompoundData) -> Result<(), anyhow::Error> {
+        let position_type = self.position_type(ctx.config);
+        let registry_arg = argument::shared_mut_cached(
+            ctx.client,
+            ctx.tx,
+            ctx.arg_cache,
+            Address::from_hex(&ctx.config.kurage.registry_object_id)?,
+        )
+        .await?;
+        let collect_fees_and_rewards_request = CollectFeesAndRewardsRequest::new(
+            compound_data.position,
+            compound_data.pool_arg,
+            compound_data.coin_a_type.clone(),
+            compound_data.coin_b_type.clone(),
+            compound_data.rewarder_coin_types.clone(),
+            true,
+            true,
+        );
+        let results = self
+            .collect_fees_and_rewards(ctx, collect_fees_and_rewards_request)
+            .await?;
+
+        let coin_a = argument::zero_coin(ctx.tx, compound_data.coin_a_type.clone())?;
+        let coin_b = argument::zero_coin(ctx.tx, compound_data.coin_b_type.clone())?;
+        let mut swaps = vec![];
+        for result in results {
+            if result.coin_type == compound_data.coin_a_type {
+                ctx.tx.merge_coins(coin_a, vec![result.coin]);
+            } else if result.coin_type == compound_data.coin_b_type {
+                ctx.tx.merge_coins(coin_b, vec![result.coin]);
+            } else {
+                let amount_in = compound_data.pending_rewards.reward_amount(&result.coin_type);
+                swaps.push((result.coin_type, result.coin, amount_in));
+            }
+        }
+        let coin_b_amount = compound_data.pending_rewards.fee_b
+            + compound_data
+                .pending_rewards
+                .reward_amount(&compound_data.coin_b_type);
+        swaps.push((compound_data.coin_b_type.clone(), coin_b, coin_b_amount));
+
+        let mut coin_a_amount = compound_data.pending_rewards.fee_a
+            + compound_data
+                .pending_rewards
+                .reward_amount(&compound_data.coin_a_type);
+        for (coin_type, coin, amount_in) in swaps {
+            // nothing to route, park the coin in the registry instead
+            if amount_in == 0 {
+                add_coin_to_receipt(
+                    ctx,
+                    &position_type,
+                    registry_arg,
+                    compound_data.coin_receipt,
+                    coin_type,
+                    coin,
+                )?;
+                continue;
+            }
+            let (coin_out, amount_out) = self
+                .swap_by_amount_in(
+                    ctx,
+                    SwapByAmountInRequest::new(
+                        coin_type,
+                        compound_data.coin_a_type.clone(),
+                        amount_in,
+                        coin,
+                        None,
+                        compound_data.lp_slippage_tolerance_bps,
+                    ),
+                )
+                .await?;
+            ctx.tx.merge_coins(coin_a, vec![coin_out]);
+            coin_a_amount += amount_out;
+        }
+
+        let add_liquidity_request = AddLiquidityRequest::new(
+            compound_data.position,
+            coin_a,
+            compound_data.pool_arg,
+            compound_data.coin_a_type.clone(),
+            compound_data.coin_b_type.clone(),
+            compound_data.tick_lower_index,
+            compound_data.tick_upper_index,
+            compound_data.current_sqrt_price,
+            compound_data.fee_rate,
+            compound_data.pool_liquidity,
+            compound_data.pool_ticks.clone(),
+            coin_a_amount,
+            compound_data.lp_slippage_tolerance_bps,
+        );
+        let (coin_a, coin_b) = self.add_liquidity(ctx, add_liquidity_request).await?;
+        add_coin_to_receipt(
+            ctx,
+            &position_type,
+            registry_arg,
+            compound_data.coin_receipt,
+            compound_data.coin_a_type.clone(),
+            coin_a,
+        )?;
+        add_coin_to_receipt(
+            ctx,
+            &position_type,
+            registry_arg,
+            compound_data.coin_receipt,
+            compound_data.coin_b_type.clone(),
+            coin_b,
+        )
+    }
+
+    /// Collect fees and rewards into the registry receipt, then move the liquidity
+    /// to the new tick range. Returns the new position.
+    async fn rebalance(
+        &self,
+        ctx: &mut DexContext<'_>,
+        rebalance_data: RebalanceData,
+    ) -> Result<Argument, anyhow::Error> {
+        let position_type = self.position_type(ctx.config);
+        let registry_arg = argument::shared_mut_cached(
+            ctx.client,
+            ctx.tx,
+            ctx.arg_cache,
+            Address::from_hex(&ctx.config.kurage.registry_object_id)?,
+        )
+        .await?;
+        let collect_fees_and_rewards_request = CollectFeesAndRewardsRequest::new(
+            rebalance_data.position,
+            rebalance_data.pool_arg,
+            rebalance_data.coin_a_type.clone(),
+            rebalance_data.coin_b_type.clone(),
+            rebalance_data.rewarder_coin_types.clone(),
+            true,
+            true,
+        );
+        let results = self
+            .collect_fees_and_rewards(ctx, collect_fees_and_rewards_request)
+            .await?;
+        for result in results {
+            add_coin_to_receipt(
+                ctx,
+                &position_type,
+                registry_arg,
+                rebalance_data.coin_receipt,
+                result.coin_type,
+                result.coin,
+            )?;
+        }
+
+        let zap_out_request = ZapOutRequest::new(
+            rebalance_data.position,
+            rebalance_data.pool_arg,
+            rebalance_data.coin_a_type.clone(),
+            rebalance_data.coin_b_type.clone(),
+            rebalance_data.current_sqrt_price,
+            rebalance_data.current_position_liquidity,
+            rebalance_data.current_tick_lower_index,
+            rebalance_data.current_tick_upper_index,
+            rebalance_data.lp_slippage_tolerance_bps,
+        );
+        let (coin_a, amount_coin_a) = self.zap_out(ctx, zap_out_request).await?;
+
+        let zap_in_request = ZapInRequest::new(
+            coin_a,
+            rebalance_data.pool_arg,
+            rebalance_data.coin_a_type.clone(),
+            rebalance_data.coin_b_type.clone(),
+            rebalance_data.new_tick_lower_index,
+            rebalance_data.new_tick_upper_index,
+            rebalance_data.current_sqrt_price,
+            amount_coin_a,
+            rebalance_data.fee_rate,
+            rebalance_data.pool_liquidity,
+            rebalance_data.pool_ticks.clone(),
+            rebalance_data.lp_slippage_tolerance_bps,
+        );
+        let (position, coin_a, coin_b) = self.zap_in(ctx, zap_in_request).await?;
+        add_coin_to_receipt(
+            ctx,
+            &position_type,
+            registry_arg,
+            rebalance_data.coin_receipt,
+            rebalance_data.coin_a_type.clone(),
+            coin_a,
+        )?;
+        add_coin_to_receipt(
+            ctx,
+            &position_type,
+            registry_arg,
+            rebalance_data.coin_receipt,
+            rebalance_data.coin_b_type.clone(),
+            coin_b,
+        )?;
+        Ok(position)
+    }
+}
+
+/// Deposit `coin` into the registry balance bag tracked by `coin_receipt`.
+pub fn add_coin_to_receipt(
+    ctx: &mut DexContext<'_>,
+    position_type: &str,
+    registry_arg: Argument,
+    coin_receipt: Argument,
+    coin_type: TypeTag,
+    coin: Argument,
+) -> Result<(), anyhow::Error> {
+    ctx.tx.move_call(
+        Function::new(
+            Address::from_hex(&ctx.config.kurage.package_id)?,
+            Identifier::new("registry")?,
+            Identifier::new("add_coin")?,
+            vec![
+                TypeTag::Struct(Box::new(StructTag::from_str(position_type)?)),
+                coin_type,
+            ],
+        ),
+        vec![registry_arg, coin_receipt, coin],
+    );
+    Ok(())
+}
+
+/// Pair each rewarder coin type with the amount owed for it, both in pool rewarder order.
+pub fn pending_rewards_from_owed(rewarder_coin_types: Vec<TypeTag>, owed: &[u64]) -> Vec<PendingReward> {
+    rewarder_coin_types
+        .into_iter()
+        .zip(owed.iter())
+        .map(|(coin_type, amount)| PendingReward {
+            coin_type,
+            amount: *amount,
+        })
+        .collect()
+}
+
+static CETUS: cetus::CetusAdapter = cetus::CetusAdapter;
+static MMT: mmt::MmtAdapter = mmt::MmtAdapter;
+static BLUEFIN: bluefin::BluefinAdapter = bluefin::BluefinAdapter;
+static FLOWX: flowx::FlowXAdapter = flowx::FlowXAdapter;
+
+/// All registered adapters, in the order pools are matched against them.
+pub fn adapters() -> Vec<&'static dyn DexAdapter> {
+    vec![&CETUS, &MMT, &BLUEFIN, &FLOWX]
+}
+
+pub fn get_adapter(dex: SupportedDex) -> Result<&'static dyn DexAdapter, anyhow::Error> {
+    adapters()
+        .into_iter()
+        .find(|adapter| adapter.dex() == dex)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported dex: {:?}", dex))
+}
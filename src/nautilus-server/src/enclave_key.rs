@@ -0,0 +1,611 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The key the enclave signs its responses with.
+//!
+//! In ephemeral mode a new key is drawn from NSM entropy on every boot, so only the
+//! attested enclave ever holds it. In sealed mode the key survives restarts: its seed
+//! is a KMS data key, generated by KMS and only returned encrypted to an RSA key drawn
+//! inside the enclave and bound to its attestation. The sealed seed is handed back to
+//! the enclave on the next boot through `EPH_SEALED_KEY` and decrypted the same way,
+//! so the host never sees it in the clear. The key policy must require the enclave
+//! PCRs for both calls and deny `kms:Encrypt` and `kms:ReEncrypt*`, or the host could
+//! seal a seed of its own choosing.
+
+use crate::config::{EnclaveKeyConfig, EnclaveKeyMode};
+use aes::Aes256;
+use anyhow::{anyhow, Context, Result};
+use aws_sigv4::http_request::{sign, SignableBody, SignableRequest, SigningSettings};
+use aws_sigv4::sign::v4;
+use base64ct::{Base64, Encoding};
+use bcder::decode::{Constructed, DecodeError, Source};
+use bcder::{ConstOid, Mode, OctetString, Oid, Tag};
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use fastcrypto::ed25519::Ed25519KeyPair;
+use fastcrypto::traits::{KeyPair, ToFromBytes};
+use nsm::Nsm;
+use rsa::pkcs8::EncodePublicKey;
+use rsa::{Oaep, RsaPrivateKey};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::SystemTime;
+
+/// Environment variable the host passes the sealed seed in, base64 encoded.
+pub const SEALED_KEY_VAR: &str = "EPH_SEALED_KEY";
+/// Size of the ed25519 seed, and of the KMS data key it is in sealed mode.
+const SEED_LEN: usize = 32;
+/// Size of the RSA key KMS encrypts the unsealed seed to.
+const RECIPIENT_KEY_BITS: usize = 2048;
+/// `id-envelopedData`, the content type of `CiphertextForRecipient`.
+const ENVELOPED_DATA: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 7, 3]);
+/// `id-RSAES-OAEP`, the key encryption KMS uses for `RSAES_OAEP_SHA_256`.
+const RSAES_OAEP: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 7]);
+/// `id-mgf1`, the mask generation function of RSAES-OAEP.
+const MGF1: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 8]);
+/// `id-sha256`.
+const SHA256: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 2, 1]);
+/// `id-aes256-CBC`, the content encryption KMS uses for `CiphertextForRecipient`.
+const AES_256_CBC: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 42]);
+
+/// Boot key of `config.mode` and, in sealed mode, its seed sealed with KMS, base64
+/// encoded. `get_var` reads `EPH_SEALED_KEY` and the AWS credentials. A key unsealed
+/// from `EPH_SEALED_KEY` is refused unless its public key is `registered_pk`, when
+/// given.
+pub async fn load<F>(
+    config: &EnclaveKeyConfig,
+    nsm: &dyn Nsm,
+    registered_pk: Option<&[u8]>,
+    get_var: F,
+) -> Result<(Ed25519KeyPair, Option<String>)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let sealed = match config.mode {
+        EnclaveKeyMode::Sealed => get_var(SEALED_KEY_VAR),
+        EnclaveKeyMode::Ephemeral => None,
+    };
+    let Some(sealed) = sealed else {
+        return create(config, nsm, get_var).await;
+    };
+    let (kms, key_id) = kms(config, &get_var)?;
+    let ciphertext = Base64::decode_vec(sealed.trim())
+        .map_err(|e| anyhow!("{} is not base64: {}", SEALED_KEY_VAR, e))?;
+    let seed = unseal(&kms, key_id, &ciphertext, nsm).await?;
+    let kp = keypair(&seed)?;
+    if let Some(registered_pk) = registered_pk {
+        if kp.public().as_bytes() != registered_pk {
+            return Err(anyhow!(
+                "{} unseals to {}, not the registered enclave key {}",
+                SEALED_KEY_VAR,
+                hex::encode(kp.public().as_bytes()),
+                hex::encode(registered_pk)
+            ));
+        }
+    }
+    Ok((kp, Some(sealed)))
+}
+
+/// New key of `config.mode` and, in sealed mode, its seed sealed with KMS. The seed
+/// comes from NSM entropy, or in sealed mode from a KMS data key generated for the
+/// attested enclave. Used on first boot and when the key is rotated.
+pub async fn create<F>(
+    config: &EnclaveKeyConfig,
+    nsm: &dyn Nsm,
+    get_var: F,
+) -> Result<(Ed25519KeyPair, Option<String>)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match config.mode {
+        EnclaveKeyMode::Ephemeral => Ok((generate(nsm)?, None)),
+        EnclaveKeyMode::Sealed => {
+            let (kms, key_id) = kms(config, &get_var)?;
+            let (recipient_key, attestation) = recipient(nsm)?;
+            let (sealed, envelope) = kms
+                .generate_data_key_for_recipient(key_id, SEED_LEN, &attestation)
+                .await?;
+            let seed = open_envelope(&envelope, &recipient_key)?;
+            Ok((keypair(&seed)?, Some(Base64::encode_string(&sealed))))
+        }
+    }
+}
+
+/// KMS client and key id of sealed mode.
+fn kms<'a, F>(config: &'a EnclaveKeyConfig, get_var: &F) -> Result<(Kms, &'a str)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let key_id = config
+        .kms_key_id
+        .as_deref()
+        .ok_or_else(|| anyhow!("enclave_key.kms_key_id is required in sealed mode"))?;
+    let region = config
+        .kms_region
+        .as_deref()
+        .ok_or_else(|| anyhow!("enclave_key.kms_region is required in sealed mode"))?;
+    Ok((Kms::new(region, Credentials::from_vars(get_var)?), key_id))
+}
+
+/// Fresh key seeded from NSM entropy.
+pub fn generate(nsm: &dyn Nsm) -> Result<Ed25519KeyPair> {
+    keypair(&nsm.random(SEED_LEN)?)
+}
+
+fn keypair(seed: &[u8]) -> Result<Ed25519KeyPair> {
+    Ed25519KeyPair::from_bytes(seed).map_err(|e| anyhow!("Invalid ed25519 seed: {}", e))
+}
+
+/// Decrypt a sealed seed. KMS only answers to an attestation whose PCRs satisfy the
+/// key policy, and encrypts the seed to the RSA key the attestation carries.
+async fn unseal(kms: &Kms, key_id: &str, ciphertext: &[u8], nsm: &dyn Nsm) -> Result<Vec<u8>> {
+    let (recipient_key, attestation) = recipient(nsm)?;
+    let envelope = kms
+        .decrypt_for_recipient(key_id, ciphertext, &attestation)
+        .await?;
+    open_envelope(&envelope, &recipient_key)
+}
+
+/// RSA key for KMS to encrypt a plaintext to, and the attestation carrying it.
+fn recipient(nsm: &dyn Nsm) -> Result<(RsaPrivateKey, Vec<u8>)> {
+    let recipient_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, RECIPIENT_KEY_BITS)?;
+    let public_key = recipient_key
+        .to_public_key()
+        .to_public_key_der()
+        .map_err(|e| anyhow!("Failed to encode the recipient key: {}", e))?;
+    let attestation = nsm.attestation(Some(public_key.into_vec()), None, None)?;
+    Ok((recipient_key, attestation))
+}
+
+/// Credentials of the role allowed to use the KMS key, passed in by the host.
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    pub fn from_vars<F>(get_var: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let required =
+            |key: &str| get_var(key).ok_or_else(|| anyhow!("{} is required in sealed mode", key));
+        Ok(Self {
+            access_key_id: required("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: required("AWS_SECRET_ACCESS_KEY")?,
+            session_token: get_var("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+/// The two KMS calls sealed mode needs, over the JSON API with SigV4 signing. Both
+/// carry a recipient attestation, so the plaintext only ever leaves KMS encrypted to
+/// the enclave.
+pub struct Kms {
+    region: String,
+    credentials: Credentials,
+    client: reqwest::Client,
+}
+
+impl Kms {
+    pub fn new(region: &str, credentials: Credentials) -> Self {
+        Self {
+            region: region.to_string(),
+            credentials,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// `CiphertextBlob` of a new `length` byte data key under `key_id`, and its
+    /// `CiphertextForRecipient`, a CMS envelope encrypted to the public key of
+    /// `attestation`. KMS leaves the plaintext out of the response.
+    pub async fn generate_data_key_for_recipient(
+        &self,
+        key_id: &str,
+        length: usize,
+        attestation: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let response = self
+            .call(
+                "GenerateDataKey",
+                &json!({
+                    "KeyId": key_id,
+                    "NumberOfBytes": length,
+                    "Recipient": recipient_json(attestation),
+                }),
+            )
+            .await?;
+        Ok((
+            decode_field(&response, "CiphertextBlob")?,
+            decode_field(&response, "CiphertextForRecipient")?,
+        ))
+    }
+
+    /// `CiphertextForRecipient` of `ciphertext`, a CMS envelope encrypted to the
+    /// public key of `attestation`.
+    pub async fn decrypt_for_recipient(
+        &self,
+        key_id: &str,
+        ciphertext: &[u8],
+        attestation: &[u8],
+    ) -> Result<Vec<u8>> {
+        let response = self
+            .call(
+                "Decrypt",
+                &json!({
+                    "KeyId": key_id,
+                    "CiphertextBlob": Base64::encode_string(ciphertext),
+                    "Recipient": recipient_json(attestation),
+                }),
+            )
+            .await?;
+        decode_field(&response, "CiphertextForRecipient")
+    }
+
+    async fn call(&self, action: &str, body: &Value) -> Result<Value> {
+        let url = format!("https://kms.{}.amazonaws.com/", self.region);
+        let payload = serde_json::to_vec(body)?;
+        let target = format!("TrentService.{}", action);
+        let headers = [
+            ("content-type", "application/x-amz-json-1.1"),
+            ("x-amz-target", target.as_str()),
+        ];
+
+        let mut request = self.client.post(&url).body(payload.clone());
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        for (name, value) in self.signature_headers(&url, &headers, &payload, SystemTime::now())? {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("KMS {} request failed", action))?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!("KMS {} failed with {}: {}", action, status, body));
+        }
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Headers AWS Signature Version 4 adds to a POST of `payload` to `url`, among
+    /// them `authorization`, `x-amz-date` and `x-amz-security-token`.
+    fn signature_headers(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+        time: SystemTime,
+    ) -> Result<Vec<(String, String)>> {
+        let identity = aws_credential_types::Credentials::new(
+            &self.credentials.access_key_id,
+            &self.credentials.secret_access_key,
+            self.credentials.session_token.clone(),
+            None,
+            "enclave",
+        )
+        .into();
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region)
+            .name("kms")
+            .time(time)
+            .settings(SigningSettings::default())
+            .build()?
+            .into();
+        let request = SignableRequest::new(
+            "POST",
+            url,
+            headers.iter().copied(),
+            SignableBody::Bytes(payload),
+        )?;
+        let (instructions, _signature) = sign(request, &params)?.into_parts();
+        Ok(instructions
+            .headers()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect())
+    }
+}
+
+fn recipient_json(attestation: &[u8]) -> Value {
+    json!({
+        "KeyEncryptionAlgorithm": "RSAES_OAEP_SHA_256",
+        "AttestationDocument": Base64::encode_string(attestation),
+    })
+}
+
+fn decode_field(response: &Value, field: &str) -> Result<Vec<u8>> {
+    let value = response[field]
+        .as_str()
+        .ok_or_else(|| anyhow!("KMS response has no {}", field))?;
+    Base64::decode_vec(value).map_err(|e| anyhow!("KMS {} is not base64: {}", field, e))
+}
+
+/// Plaintext of a CMS `EnvelopedData` with a single key transport recipient, as KMS
+/// returns in `CiphertextForRecipient`: the content key is RSA-OAEP-SHA256 encrypted
+/// to `key` and the content AES-256-CBC encrypted. KMS uses BER indefinite lengths,
+/// so the envelope is decoded as BER rather than DER.
+fn open_envelope(envelope: &[u8], key: &RsaPrivateKey) -> Result<Vec<u8>> {
+    let envelope = Mode::Ber
+        .decode(envelope, Envelope::take_from)
+        .map_err(|e| anyhow!("Invalid KMS envelope: {}", e))?;
+    let content_key = key
+        .decrypt(Oaep::new::<Sha256>(), &envelope.encrypted_key)
+        .map_err(|e| anyhow!("Failed to decrypt the content key: {}", e))?;
+    cbc::Decryptor::<Aes256>::new_from_slices(&content_key, &envelope.iv)
+        .map_err(|e| anyhow!("Invalid content key: {}", e))?
+        .decrypt_padded_vec_mut::<Pkcs7>(&envelope.ciphertext)
+        .map_err(|e| anyhow!("Failed to decrypt the KMS envelope: {}", e))
+}
+
+/// The parts of a KMS `EnvelopedData` needed to open it, see RFC 5652 section 6.
+struct Envelope {
+    encrypted_key: Vec<u8>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    /// `ContentInfo` wrapping the `EnvelopedData`.
+    fn take_from<S: Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            ENVELOPED_DATA.skip_if(cons)?;
+            cons.take_constructed_if(Tag::CTX_0, |cons| {
+                cons.take_sequence(Self::take_enveloped_data)
+            })
+        })
+    }
+
+    fn take_enveloped_data<S: Source>(
+        cons: &mut Constructed<S>,
+    ) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_u8()?; // version
+        cons.take_opt_constructed_if(Tag::CTX_0, |cons| cons.skip_all())?; // originatorInfo
+        let encrypted_key = cons.take_set(|cons| cons.take_sequence(take_key_trans_recipient))?;
+        let (iv, ciphertext) = cons.take_sequence(|cons| {
+            Oid::skip_in(cons)?; // contentType
+            let iv = cons.take_sequence(|cons| {
+                if Oid::take_from(cons)? != AES_256_CBC {
+                    return Err(cons.content_err("content encryption is not AES-256-CBC"));
+                }
+                OctetString::take_from(cons)
+            })?;
+            let ciphertext = cons.take_value_if(Tag::CTX_0, OctetString::from_content)?;
+            Ok((iv, ciphertext))
+        })?;
+        cons.take_opt_constructed_if(Tag::CTX_1, |cons| cons.skip_all())?; // unprotectedAttrs
+        Ok(Self {
+            encrypted_key: encrypted_key.to_bytes().to_vec(),
+            iv: iv.to_bytes().to_vec(),
+            ciphertext: ciphertext.to_bytes().to_vec(),
+        })
+    }
+}
+
+/// `encryptedKey` of a `KeyTransRecipientInfo` whose key is RSAES-OAEP encrypted with
+/// SHA-256 as both the hash and the MGF1 hash, what `unseal` asks KMS for.
+fn take_key_trans_recipient<S: Source>(
+    cons: &mut Constructed<S>,
+) -> Result<OctetString, DecodeError<S::Error>> {
+    cons.take_u8()?; // version
+    cons.skip_one()?; // rid
+    cons.take_sequence(|cons| {
+        if Oid::take_from(cons)? != RSAES_OAEP {
+            return Err(cons.content_err("key encryption is not RSAES-OAEP"));
+        }
+        // RSAES-OAEP-params, whose hash and MGF1 hash default to SHA-1
+        let (hash, mgf_hash) = cons.take_sequence(|cons| {
+            let hash = cons.take_opt_constructed_if(Tag::CTX_0, take_digest_algorithm)?;
+            let mgf_hash = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+                cons.take_sequence(|cons| {
+                    MGF1.skip_if(cons)?;
+                    take_digest_algorithm(cons)
+                })
+            })?;
+            cons.take_opt_constructed_if(Tag::CTX_2, |cons| cons.skip_all())?; // pSourceFunc
+            Ok((hash, mgf_hash))
+        })?;
+        let is_sha256 = |oid: &Option<Oid>| matches!(oid, Some(oid) if *oid == SHA256);
+        if !is_sha256(&hash) || !is_sha256(&mgf_hash) {
+            return Err(cons.content_err("RSAES-OAEP does not use SHA-256"));
+        }
+        Ok(())
+    })?;
+    OctetString::take_from(cons)
+}
+
+/// Algorithm of a digest `AlgorithmIdentifier`, whose parameters are absent or NULL.
+fn take_digest_algorithm<S: Source>(
+    cons: &mut Constructed<S>,
+) -> Result<Oid, DecodeError<S::Error>> {
+    cons.take_sequence(|cons| {
+        let algorithm = Oid::take_from(cons)?;
+        cons.take_opt_null()?;
+        Ok(algorithm)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cbc::cipher::BlockEncryptMut;
+    use rand::rngs::OsRng;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const INTEGER: u8 = 0x02;
+    const OCTET_STRING: u8 = 0x04;
+    const NULL: u8 = 0x05;
+    const OID: u8 = 0x06;
+    const SEQUENCE: u8 = 0x30;
+    const SET: u8 = 0x31;
+    /// `id-data`
+    const DATA: &[u8] = &[42, 134, 72, 134, 247, 13, 1, 7, 1];
+    /// `rsaEncryption`, RSAES-PKCS1-v1_5
+    const RSA_ENCRYPTION: &[u8] = &[42, 134, 72, 134, 247, 13, 1, 1, 1];
+
+    #[test]
+    fn test_signature_headers() {
+        let kms = Kms::new(
+            "us-east-1",
+            Credentials {
+                access_key_id: "AKIDEXAMPLE".to_string(),
+                secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+                session_token: Some("session".to_string()),
+            },
+        );
+        let headers = kms
+            .signature_headers(
+                "https://kms.us-east-1.amazonaws.com/",
+                &[
+                    ("content-type", "application/x-amz-json-1.1"),
+                    ("x-amz-target", "TrentService.Encrypt"),
+                ],
+                b"{}",
+                UNIX_EPOCH + Duration::from_secs(1440938160),
+            )
+            .unwrap();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("x-amz-date"), Some("20150830T123600Z"));
+        assert_eq!(header("x-amz-security-token"), Some("session"));
+        assert_eq!(
+            header("authorization"),
+            Some(
+                "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/kms/aws4_request, \
+                 SignedHeaders=content-type;host;x-amz-date;x-amz-security-token;x-amz-target, \
+                 Signature=a376f2ab74ee166f4bd394893a541ac4a84406e2eecff0ee4dfbde014de714bb"
+            )
+        );
+    }
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut element = vec![tag];
+        match content.len() {
+            length @ 0..=0x7f => element.push(length as u8),
+            length @ 0x80..=0xff => element.extend([0x81, length as u8]),
+            length => element.extend([0x82, (length >> 8) as u8, length as u8]),
+        }
+        element.extend(content);
+        element
+    }
+
+    fn ber(tag: u8, content: &[u8]) -> Vec<u8> {
+        [&[tag, 0x80], content, &[0, 0]].concat()
+    }
+
+    /// RSAES-OAEP `AlgorithmIdentifier` with SHA-256 as the hash and the MGF1 hash.
+    fn oaep_sha256() -> Vec<u8> {
+        let sha256 = der(SEQUENCE, &[der(OID, SHA256.0), der(NULL, &[])].concat());
+        let mgf1_sha256 = der(SEQUENCE, &[der(OID, MGF1.0), sha256.clone()].concat());
+        let params = der(
+            SEQUENCE,
+            &[der(0xa0, &sha256), der(0xa1, &mgf1_sha256)].concat(),
+        );
+        der(SEQUENCE, &[der(OID, RSAES_OAEP.0), params].concat())
+    }
+
+    /// `EnvelopedData` of `plaintext` to `key`, laid out like KMS does when
+    /// `indefinite`.
+    fn envelope(
+        plaintext: &[u8],
+        key: &RsaPrivateKey,
+        key_encryption_algorithm: &[u8],
+        indefinite: bool,
+    ) -> Vec<u8> {
+        let constructed = if indefinite { ber } else { der };
+        let content_key = [7u8; 32];
+        let iv = [9u8; 16];
+        let ciphertext = cbc::Encryptor::<Aes256>::new_from_slices(&content_key, &iv)
+            .unwrap()
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+        let encrypted_key = key
+            .to_public_key()
+            .encrypt(&mut OsRng, Oaep::new::<Sha256>(), &content_key)
+            .unwrap();
+
+        let recipient = constructed(
+            SEQUENCE,
+            &[
+                der(INTEGER, &[2]),
+                der(0x80, &[1; 20]),
+                key_encryption_algorithm.to_vec(),
+                der(OCTET_STRING, &encrypted_key),
+            ]
+            .concat(),
+        );
+        let encrypted_content = if indefinite {
+            let chunks = ciphertext
+                .chunks(16)
+                .map(|chunk| der(OCTET_STRING, chunk))
+                .collect::<Vec<_>>()
+                .concat();
+            ber(0xa0, &chunks)
+        } else {
+            der(0x80, &ciphertext)
+        };
+        let content = constructed(
+            SEQUENCE,
+            &[
+                der(OID, DATA),
+                der(
+                    SEQUENCE,
+                    &[der(OID, AES_256_CBC.0), der(OCTET_STRING, &iv)].concat(),
+                ),
+                encrypted_content,
+            ]
+            .concat(),
+        );
+        let enveloped_data = constructed(
+            SEQUENCE,
+            &[der(INTEGER, &[2]), constructed(SET, &recipient), content].concat(),
+        );
+        constructed(
+            SEQUENCE,
+            &[
+                der(OID, ENVELOPED_DATA.0),
+                constructed(0xa0, &enveloped_data),
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn test_open_envelope() {
+        let key = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let seed = [42u8; 32];
+        for indefinite in [false, true] {
+            let envelope = envelope(&seed, &key, &oaep_sha256(), indefinite);
+            assert_eq!(open_envelope(&envelope, &key).unwrap(), seed);
+        }
+
+        let other = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let envelope = envelope(&seed, &key, &oaep_sha256(), true);
+        assert!(open_envelope(&envelope, &other).is_err());
+        assert!(open_envelope(&envelope[..40], &key).is_err());
+    }
+
+    #[test]
+    fn test_open_envelope_checks_key_encryption() {
+        let key = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let seed = [42u8; 32];
+        // PKCS#1 v1.5 instead of OAEP
+        let pkcs1 = der(
+            SEQUENCE,
+            &[der(OID, RSA_ENCRYPTION), der(NULL, &[])].concat(),
+        );
+        // OAEP with the default SHA-1 hashes
+        let oaep_sha1 = der(
+            SEQUENCE,
+            &[der(OID, RSAES_OAEP.0), der(SEQUENCE, &[])].concat(),
+        );
+        for algorithm in [pkcs1, oaep_sha1] {
+            assert!(open_envelope(&envelope(&seed, &key, &algorithm, true), &key).is_err());
+        }
+    }
+}
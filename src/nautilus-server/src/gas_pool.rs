@@ -0,0 +1,453 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::construct_kp_from_bech32_string;
+use crate::config::GasPoolConfig;
+use crate::transactions_builder::helper::SuiUtilsError;
+use crate::transactions_builder::{argument, helper};
+use crate::AppState;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sui_crypto::ed25519::Ed25519PrivateKey;
+use sui_graphql_client::Client;
+use sui_sdk_types::framework::Coin;
+use sui_sdk_types::{Address, Argument, ObjectOut, ObjectReference, TransactionEffects};
+use sui_transaction_builder::TransactionBuilder;
+use tokio::sync::Notify;
+use tracing::{error, warn};
+
+const SUI_COIN_TYPE: &str = "0x2::coin::Coin<0x2::sui::SUI>";
+
+/// Gas coin of the pool with the object ref the next transaction spends.
+#[derive(Debug, Clone)]
+pub struct GasCoin {
+    pub object_ref: ObjectReference,
+    pub balance: u64,
+}
+
+impl GasCoin {
+    pub fn id(&self) -> Address {
+        *self.object_ref.object_id()
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: HashMap<Address, GasCoin>,
+    leased: HashSet<Address>,
+    /// Coins spent by a transaction whose effects are unknown, refetched before reuse.
+    stale: HashSet<Address>,
+    /// Set while `rebalance` spends every coin, no lease starts meanwhile.
+    rebalancing: bool,
+}
+
+/// Gas coins of the sender key, leased to one in-flight transaction at a time so
+/// concurrent jobs never spend the same coin version.
+#[derive(Clone, Default)]
+pub struct GasPool {
+    state: Arc<Mutex<PoolState>>,
+    /// Woken when a coin goes back to the idle set.
+    released: Arc<Notify>,
+    /// Woken when a coin went stale, so the maintenance task refreshes early.
+    refresh_requested: Arc<Notify>,
+}
+
+/// A leased coin, returned to the pool when the lease is dropped.
+pub struct GasLease {
+    pool: GasPool,
+    coin: GasCoin,
+    submitted: bool,
+}
+
+impl GasLease {
+    pub fn coin(&self) -> &GasCoin {
+        &self.coin
+    }
+
+    /// The transaction paying with the coin is being submitted, from now on only its
+    /// effects tell the new coin version.
+    pub fn mark_submitted(&mut self) {
+        self.submitted = true;
+    }
+
+    /// Return the coin with the ref and balance the executed transaction left it with.
+    pub fn release(mut self, effects: &TransactionEffects) {
+        if let Some(coin) = coin_after(&self.coin, effects) {
+            self.coin = coin;
+            self.submitted = false;
+        }
+    }
+}
+
+impl Drop for GasLease {
+    fn drop(&mut self) {
+        if self.submitted {
+            self.pool.return_stale(self.coin.id());
+        } else {
+            self.pool.return_idle(self.coin.clone());
+        }
+    }
+}
+
+/// Ref and balance of `coin` after it paid for the transaction with `effects`.
+fn coin_after(coin: &GasCoin, effects: &TransactionEffects) -> Option<GasCoin> {
+    // V1 effects are no longer produced, their coin is refetched instead
+    let TransactionEffects::V2(effects) = effects else {
+        return None;
+    };
+    let changed = effects
+        .changed_objects
+        .get(effects.gas_object_index? as usize)?;
+    if changed.object_id != coin.id() {
+        return None;
+    }
+    let ObjectOut::ObjectWrite { digest, .. } = &changed.output_state else {
+        return None;
+    };
+    let gas_used = &effects.gas_used;
+    let charged =
+        (gas_used.computation_cost + gas_used.storage_cost).saturating_sub(gas_used.storage_rebate);
+    Some(GasCoin {
+        object_ref: ObjectReference::new(changed.object_id, effects.lamport_version, *digest),
+        balance: coin.balance.saturating_sub(charged),
+    })
+}
+
+impl GasPool {
+    /// Lease the smallest idle coin holding at least `min_balance`, waiting up to
+    /// `timeout` for one to be released.
+    pub async fn lease(&self, min_balance: u64, timeout: Duration) -> helper::Result<GasLease> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            if let Some(coin) = self.try_lease(min_balance) {
+                return Ok(GasLease {
+                    pool: self.clone(),
+                    coin,
+                    submitted: false,
+                });
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return Err(SuiUtilsError::GasCoinNotFound);
+            }
+        }
+    }
+
+    fn try_lease(&self, min_balance: u64) -> Option<GasCoin> {
+        let mut state = self.lock();
+        if state.rebalancing {
+            return None;
+        }
+        let id = state
+            .idle
+            .values()
+            .filter(|coin| coin.balance >= min_balance)
+            .min_by_key(|coin| coin.balance)?
+            .id();
+        let coin = state.idle.remove(&id)?;
+        state.leased.insert(id);
+        Some(coin)
+    }
+
+    fn return_idle(&self, coin: GasCoin) {
+        {
+            let mut state = self.lock();
+            state.leased.remove(&coin.id());
+            state.idle.insert(coin.id(), coin);
+        }
+        self.released.notify_waiters();
+    }
+
+    fn return_stale(&self, id: Address) {
+        {
+            let mut state = self.lock();
+            state.leased.remove(&id);
+            state.stale.insert(id);
+        }
+        self.refresh_requested.notify_one();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Replace the idle and stale coins with the SUI coins `owner` holds on chain.
+    /// Leased coins are left alone, their lease returns them.
+    pub async fn refresh(&self, client: &Client, owner: Address) -> helper::Result<()> {
+        let stale_before = self.lock().stale.clone();
+        let coins = fetch_coins(client, owner).await?;
+        self.merge(coins, &stale_before);
+        self.released.notify_waiters();
+        Ok(())
+    }
+
+    /// Take `coins`, fetched while `stale_before` were the stale coins, as the idle set.
+    /// Leases may have returned coins while the fetch ran: an idle coin keeps its ref
+    /// when it is newer than the fetched one, and a coin that went stale meanwhile
+    /// stays stale since its fetched ref may predate the transaction spending it.
+    fn merge(&self, coins: Vec<GasCoin>, stale_before: &HashSet<Address>) {
+        let mut state = self.lock();
+        state.stale.retain(|id| !stale_before.contains(id));
+        let idle = coins
+            .into_iter()
+            .filter(|coin| {
+                !state.leased.contains(&coin.id()) && !state.stale.contains(&coin.id())
+            })
+            .map(|coin| match state.idle.get(&coin.id()) {
+                Some(current) if current.object_ref.version() > coin.object_ref.version() => {
+                    current.clone()
+                }
+                _ => coin,
+            })
+            .map(|coin| (coin.id(), coin))
+            .collect();
+        state.idle = idle;
+    }
+
+    /// Merge every coin and split the SUI evenly into `config.size` coins when there
+    /// are too few or too small ones. Runs only while no coin is leased or stale,
+    /// returns whether a rebalance transaction was executed.
+    pub async fn rebalance(
+        &self,
+        client: &Client,
+        pk: &Ed25519PrivateKey,
+        config: &GasPoolConfig,
+    ) -> helper::Result<bool> {
+        let coins = {
+            let mut state = self.lock();
+            if !state.leased.is_empty() || !state.stale.is_empty() {
+                return Ok(false);
+            }
+            let balanced = state.idle.len() == config.size
+                && state
+                    .idle
+                    .values()
+                    .all(|coin| coin.balance >= config.min_coin_balance);
+            let total = state.idle.values().map(|coin| coin.balance).sum::<u64>();
+            let needed = config.min_coin_balance * config.size as u64 + config.rebalance_gas_budget;
+            if balanced || total < needed {
+                if total < needed {
+                    warn!(
+                        "gas pool holds {} MIST, {} needed to rebalance",
+                        total, needed
+                    );
+                }
+                return Ok(false);
+            }
+            state.rebalancing = true;
+            state.idle.values().cloned().collect::<Vec<_>>()
+        };
+        let result = self.split_evenly(client, pk, config, &coins).await;
+        self.lock().rebalancing = false;
+        let owner = pk.public_key().derive_address();
+        self.refresh(client, owner).await?;
+        result.map(|_| true)
+    }
+
+    async fn split_evenly(
+        &self,
+        client: &Client,
+        pk: &Ed25519PrivateKey,
+        config: &GasPoolConfig,
+        coins: &[GasCoin],
+    ) -> helper::Result<()> {
+        let owner = pk.public_key().derive_address();
+        let total = coins.iter().map(|coin| coin.balance).sum::<u64>();
+        let amount = (total - config.rebalance_gas_budget) / config.size as u64;
+        let gas_coins = coins
+            .iter()
+            .map(|coin| coin.object_ref.clone())
+            .collect::<Vec<_>>();
+        let mut tx =
+            helper::new_with_gas_coins(client, owner, &gas_coins, config.rebalance_gas_budget)
+                .await?;
+        // the merged gas coin stays in the pool as one of the coins
+        if config.size > 1 {
+            self.split_gas(&mut tx, owner, amount, config.size - 1)?;
+        }
+        helper::execute_and_wait_for_effects(client, tx, pk, true, None).await?;
+        Ok(())
+    }
+
+    /// Split `count` coins of `amount` off the gas coin and send them to `owner`.
+    fn split_gas(
+        &self,
+        tx: &mut TransactionBuilder,
+        owner: Address,
+        amount: u64,
+        count: usize,
+    ) -> helper::Result<()> {
+        let build_error = |e: anyhow::Error| SuiUtilsError::TransactionBuildingError(e.to_string());
+        let amounts = (0..count)
+            .map(|_| argument::pure(tx, amount))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(build_error)?;
+        let split = tx.split_coins(Argument::Gas, amounts);
+        let new_coins = (0..count)
+            .map(|i| argument::nested(split, i as u16))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(build_error)?;
+        let owner_arg = argument::pure(tx, owner).map_err(build_error)?;
+        tx.transfer_objects(new_coins, owner_arg);
+        Ok(())
+    }
+}
+
+async fn fetch_coins(client: &Client, owner: Address) -> helper::Result<Vec<GasCoin>> {
+    let ids = helper::get_owned_coins(client, owner, Some(SUI_COIN_TYPE))
+        .await?
+        .iter()
+        .map(|coin| coin.id().to_owned().into())
+        .collect::<Vec<Address>>();
+    let objects = helper::get_multi(client, ids).await?;
+    Ok(objects
+        .iter()
+        .filter_map(|object| {
+            let coin = Coin::try_from_object(object)?;
+            Some(GasCoin {
+                object_ref: ObjectReference::new(
+                    object.object_id(),
+                    object.version(),
+                    object.digest(),
+                ),
+                balance: coin.balance(),
+            })
+        })
+        .collect())
+}
+
+/// Keep the pool of `state` in sync with the chain: refresh on every interval or
+/// when a lease went stale, then rebalance if needed.
+pub async fn maintain(state: Arc<AppState>) {
+    let config = &state.config.gas_pool;
+    if config.size == 0 || state.sponsor.is_some() {
+        return;
+    }
+    let kp = match construct_kp_from_bech32_string(&state.pk_string) {
+        Ok(kp) => kp,
+        Err(e) => {
+            warn!("gas pool disabled, failed to construct keypair: {}", e);
+            return;
+        }
+    };
+    let owner = kp.public_key().derive_address();
+    let client = match state.config.graphql_client() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("gas pool disabled: {}", e);
+            return;
+        }
+    };
+    let pool = &state.gas_pool;
+    loop {
+        if let Err(e) = pool.refresh(&client, owner).await {
+            error!("gas pool refresh failed: {}", e);
+        } else if let Err(e) = pool.rebalance(&client, &kp, config).await {
+            error!("gas pool rebalance failed: {}", e);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(config.refresh_interval_secs)) => {}
+            _ = pool.refresh_requested.notified() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use sui_sdk_types::Digest;
+
+    fn coin(id: &str, balance: u64) -> GasCoin {
+        GasCoin {
+            object_ref: ObjectReference::new(Address::from_str(id).unwrap(), 1, Digest::ZERO),
+            balance,
+        }
+    }
+
+    fn pool(coins: Vec<GasCoin>) -> GasPool {
+        let pool = GasPool::default();
+        pool.lock().idle = coins.into_iter().map(|coin| (coin.id(), coin)).collect();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_lease_smallest_sufficient_coin() {
+        let pool = pool(vec![coin("0x1", 50), coin("0x2", 200), coin("0x3", 120)]);
+        let lease = pool.lease(100, Duration::ZERO).await.unwrap();
+        assert_eq!(lease.coin().balance, 120);
+        assert!(pool.lease(300, Duration::ZERO).await.is_err());
+
+        drop(lease);
+        assert_eq!(pool.lock().idle.len(), 3);
+        assert!(pool.lock().leased.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_submitted_lease_goes_stale() {
+        let pool = pool(vec![coin("0x1", 200)]);
+        let mut lease = pool.lease(100, Duration::ZERO).await.unwrap();
+        lease.mark_submitted();
+        drop(lease);
+        assert!(pool.lock().idle.is_empty());
+        assert!(pool
+            .lock()
+            .stale
+            .contains(&Address::from_str("0x1").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_coins_returned_during_fetch() {
+        let pool = pool(vec![coin("0x1", 150), coin("0x2", 200)]);
+        pool.lock().stale.insert(Address::from_str("0x3").unwrap());
+        let stale_before = pool.lock().stale.clone();
+
+        // while the fetch runs, 0x1 pays for a transaction and comes back at version 2,
+        // and 0x2 is submitted with unknown effects
+        let mut lease = pool.lease(100, Duration::ZERO).await.unwrap();
+        lease.coin = GasCoin {
+            object_ref: ObjectReference::new(lease.coin().id(), 2, Digest::ZERO),
+            balance: 120,
+        };
+        drop(lease);
+        let mut lease = pool.lease(130, Duration::ZERO).await.unwrap();
+        lease.mark_submitted();
+        drop(lease);
+
+        pool.merge(
+            vec![coin("0x1", 150), coin("0x2", 200), coin("0x3", 80)],
+            &stale_before,
+        );
+        let state = pool.lock();
+        let first = &state.idle[&Address::from_str("0x1").unwrap()];
+        assert_eq!((first.object_ref.version(), first.balance), (2, 120));
+        assert!(!state.idle.contains_key(&Address::from_str("0x2").unwrap()));
+        assert!(state.stale.contains(&Address::from_str("0x2").unwrap()));
+        // stale before the fetch, its fetched ref is taken
+        assert_eq!(state.idle[&Address::from_str("0x3").unwrap()].balance, 80);
+        assert_eq!(state.stale.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lease_waits_for_release() {
+        let pool = pool(vec![coin("0x1", 200)]);
+        let lease = pool.lease(100, Duration::ZERO).await.unwrap();
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.lease(100, Duration::from_secs(5))
+                    .await
+                    .map(|lease| lease.coin().id())
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(lease);
+        assert_eq!(
+            waiter.await.unwrap().unwrap(),
+            Address::from_str("0x1").unwrap()
+        );
+    }
+}
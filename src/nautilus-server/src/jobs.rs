@@ -0,0 +1,277 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::transactions_builder::preflight::Simulation;
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use sui_sdk_types::{ExecutionStatus, TransactionEffects};
+
+/// Finished jobs kept around for `GET /jobs/{id}`, the oldest are dropped first.
+const MAX_FINISHED_JOBS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Building,
+    DryRun,
+    Submitted,
+    Finalized,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Finalized | JobState::Failed)
+    }
+}
+
+/// Error of one attempt, in the same shape as the HTTP error body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobError {
+    pub attempt: u32,
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abort_code: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TickRange {
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectsSummary {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abort_code: Option<u64>,
+    pub epoch: u64,
+    pub computation_cost: u64,
+    pub storage_cost: u64,
+    pub storage_rebate: u64,
+    pub non_refundable_storage_fee: u64,
+}
+
+impl From<&TransactionEffects> for EffectsSummary {
+    fn from(effects: &TransactionEffects) -> Self {
+        let (status, epoch, gas_used) = match effects {
+            TransactionEffects::V1(effects) => (&effects.status, effects.epoch, &effects.gas_used),
+            TransactionEffects::V2(effects) => (&effects.status, effects.epoch, &effects.gas_used),
+        };
+        Self {
+            status: match status {
+                ExecutionStatus::Success => "success".to_string(),
+                failure => format!("{:?}", failure),
+            },
+            abort_code: crate::transactions_builder::helper::abort_code(status),
+            epoch,
+            computation_cost: gas_used.computation_cost,
+            storage_cost: gas_used.storage_cost,
+            storage_rebate: gas_used.storage_rebate,
+            non_refundable_storage_fee: gas_used.non_refundable_storage_fee,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceChangeSummary {
+    pub address: String,
+    pub coin_type: String,
+    /// Signed amount in the smallest unit, as a string since it may not fit in a JSON number.
+    pub amount: String,
+}
+
+/// Preflight simulation of the last built transaction and the limits it broke.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationSummary {
+    pub effects: EffectsSummary,
+    pub net_gas_used: u64,
+    /// Budget the transaction is signed with, estimated from the simulation.
+    pub gas_budget: u64,
+    pub balance_changes: Vec<BalanceChangeSummary>,
+    pub violations: Vec<String>,
+}
+
+impl SimulationSummary {
+    pub fn new(simulation: &Simulation, gas_budget: u64, violations: Vec<String>) -> Self {
+        Self {
+            effects: EffectsSummary::from(&simulation.effects),
+            net_gas_used: simulation.net_gas_used(),
+            gas_budget,
+            balance_changes: simulation
+                .balance_changes
+                .iter()
+                .map(|delta| BalanceChangeSummary {
+                    address: delta.address.to_string(),
+                    coin_type: delta.coin_type.to_string(),
+                    amount: delta.amount.to_string(),
+                })
+                .collect(),
+            violations,
+        }
+    }
+}
+
+/// A `process_data_v2` request running in the background.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub state: JobState,
+    pub pool_id: String,
+    pub strategy_id: String,
+    /// Errors of the attempts that failed, oldest first.
+    pub attempts: Vec<JobError>,
+    /// New range of a rebalance.
+    pub tick_range: Option<TickRange>,
+    /// Intent message and enclave signature handed to the strategy.
+    pub signed_intent: Option<serde_json::Value>,
+    pub simulation: Option<SimulationSummary>,
+    pub digest: Option<String>,
+    pub effects: Option<EffectsSummary>,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn new_job_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+/// In-memory job registry shared by the handlers and the job tasks.
+#[derive(Clone, Default)]
+pub struct JobStore {
+    jobs: Arc<RwLock<HashMap<String, Job>>>,
+}
+
+impl JobStore {
+    /// Register a queued job and return its id.
+    pub fn create(&self, pool_id: String, strategy_id: String) -> String {
+        let id = new_job_id();
+        let now = now_ms();
+        let job = Job {
+            id: id.clone(),
+            state: JobState::Queued,
+            pool_id,
+            strategy_id,
+            attempts: vec![],
+            tick_range: None,
+            signed_intent: None,
+            simulation: None,
+            digest: None,
+            effects: None,
+            created_at_ms: now,
+            updated_at_ms: now,
+        };
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+        prune(&mut jobs);
+        jobs.insert(id.clone(), job);
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+
+    /// Apply `f` to the job, a no-op if it was pruned meanwhile.
+    pub fn update(&self, id: &str, f: impl FnOnce(&mut Job)) {
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(job) = jobs.get_mut(id) {
+            f(job);
+            job.updated_at_ms = now_ms();
+        }
+    }
+
+    pub fn set_state(&self, id: &str, state: JobState) {
+        self.update(id, |job| job.state = state);
+    }
+
+    pub fn record_error(&self, id: &str, error: &EnclaveError) {
+        self.update(id, |job| {
+            let attempt = job.attempts.len() as u32 + 1;
+            job.attempts.push(JobError {
+                attempt,
+                code: error.code().to_string(),
+                message: error.to_string(),
+                abort_code: error.abort_code(),
+            });
+        });
+    }
+
+    pub fn fail(&self, id: &str, error: &EnclaveError) {
+        self.record_error(id, error);
+        self.set_state(id, JobState::Failed);
+    }
+}
+
+fn prune(jobs: &mut HashMap<String, Job>) {
+    let mut finished = jobs
+        .values()
+        .filter(|job| job.state.is_finished())
+        .map(|job| (job.updated_at_ms, job.id.clone()))
+        .collect::<Vec<_>>();
+    if finished.len() < MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_job_lifecycle() {
+        let store = JobStore::default();
+        let id = store.create("0x1".to_string(), "0x2".to_string());
+        assert_eq!(store.get(&id).unwrap().state, JobState::Queued);
+
+        store.set_state(&id, JobState::Building);
+        store.record_error(&id, &EnclaveError::UpstreamRpc("timeout".to_string()));
+        store.fail(
+            &id,
+            &EnclaveError::ExecutionAborted {
+                abort_code: Some(3),
+                message: "cooldown".to_string(),
+            },
+        );
+
+        let job = store.get(&id).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(job.attempts.len(), 2);
+        assert_eq!(job.attempts[0].code, "UPSTREAM_RPC_FAILURE");
+        assert_eq!(job.attempts[1].attempt, 2);
+        assert_eq!(job.attempts[1].abort_code, Some(3));
+        assert!(store.get("unknown").is_none());
+    }
+
+    #[test]
+    fn test_prune_keeps_running_jobs() {
+        let store = JobStore::default();
+        let running = store.create("0x1".to_string(), "0x2".to_string());
+        for _ in 0..MAX_FINISHED_JOBS + 5 {
+            let id = store.create("0x1".to_string(), "0x2".to_string());
+            store.set_state(&id, JobState::Finalized);
+        }
+        let jobs = store.jobs.read().unwrap();
+        assert!(jobs.len() <= MAX_FINISHED_JOBS + 1);
+        assert!(jobs.contains_key(&running));
+    }
+}
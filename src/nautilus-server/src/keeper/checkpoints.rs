@@ -0,0 +1,167 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::AppConfig;
+use crate::dex;
+use crate::parsers::common::{extract_string_from_fields, extract_struct_fields};
+use prost_types::Value;
+use std::collections::HashSet;
+use sui_rpc::field::FieldMask;
+use sui_rpc::proto::sui::rpc::v2beta2::{
+    Event, SubscribeCheckpointsRequest, SubscribeCheckpointsResponse,
+};
+use tonic::Streaming;
+
+/// Fields naming the pool in the swap events of the supported DEXes.
+const POOL_ID_FIELDS: [&str; 2] = ["pool", "pool_id"];
+
+/// Fields holding the pool sqrt price after the swap, Cetus and Bluefin use the first,
+/// MMT and FlowX the second.
+const SQRT_PRICE_AFTER_FIELDS: [&str; 2] = ["after_sqrt_price", "sqrt_price_after"];
+
+/// A swap moved the price of `pool_id` to `sqrt_price`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swap {
+    pub pool_id: String,
+    pub sqrt_price: u128,
+}
+
+/// Stream of every checkpoint from the latest one on, with the events of its
+/// transactions.
+pub async fn subscribe(
+    client: &mut sui_rpc::Client,
+) -> Result<Streaming<SubscribeCheckpointsResponse>, tonic::Status> {
+    let response = client
+        .subscription_client()
+        .subscribe_checkpoints(SubscribeCheckpointsRequest {
+            read_mask: Some(FieldMask {
+                paths: vec![
+                    "sequence_number".into(),
+                    "transactions.events.events.event_type".into(),
+                    "transactions.events.events.json".into(),
+                ],
+            }),
+        })
+        .await?;
+    Ok(response.into_inner())
+}
+
+/// Move types of the swap events of every supported DEX.
+pub fn swap_event_types(config: &AppConfig) -> HashSet<String> {
+    dex::adapters()
+        .into_iter()
+        .map(|adapter| adapter.swap_event_type(config))
+        .collect()
+}
+
+/// Swaps of the checkpoint, in execution order.
+pub fn swaps(
+    response: &SubscribeCheckpointsResponse,
+    swap_event_types: &HashSet<String>,
+) -> Vec<Swap> {
+    response
+        .checkpoint
+        .iter()
+        .flat_map(|checkpoint| &checkpoint.transactions)
+        .filter_map(|transaction| transaction.events.as_ref())
+        .flat_map(|events| &events.events)
+        .filter_map(|event| parse_swap(event, swap_event_types))
+        .collect()
+}
+
+/// The swap an event reports, `None` unless its type is one of `swap_event_types`.
+/// Other events may carry a pool and a sqrt price too, e.g. of a DEX that is not
+/// supported.
+pub fn parse_swap(event: &Event, swap_event_types: &HashSet<String>) -> Option<Swap> {
+    if !swap_event_types.contains(event.event_type.as_deref()?) {
+        return None;
+    }
+    parse_swap_json(event.json.as_deref()?)
+}
+
+fn parse_swap_json(json: &Value) -> Option<Swap> {
+    let fields = extract_struct_fields(json).ok()?;
+    let pool_id = POOL_ID_FIELDS
+        .iter()
+        .find_map(|key| extract_string_from_fields(fields, key).ok())?;
+    let sqrt_price = SQRT_PRICE_AFTER_FIELDS
+        .iter()
+        .find_map(|key| extract_string_from_fields(fields, key).ok())?
+        .parse::<u128>()
+        .ok()?;
+    Some(Swap {
+        pool_id,
+        sqrt_price,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost_types::value::Kind;
+    use prost_types::Struct;
+
+    fn event_json(fields: &[(&str, &str)]) -> Value {
+        Value {
+            kind: Some(Kind::StructValue(Struct {
+                fields: fields
+                    .iter()
+                    .map(|(key, value)| {
+                        (
+                            key.to_string(),
+                            Value {
+                                kind: Some(Kind::StringValue(value.to_string())),
+                            },
+                        )
+                    })
+                    .collect(),
+            })),
+        }
+    }
+
+    #[test]
+    fn test_parse_swap() {
+        let cetus = event_json(&[
+            ("pool", "0x5"),
+            ("after_sqrt_price", "18446744073709551616"),
+        ]);
+        assert_eq!(
+            parse_swap_json(&cetus),
+            Some(Swap {
+                pool_id: "0x5".to_string(),
+                sqrt_price: 1 << 64,
+            })
+        );
+        let mmt = event_json(&[("pool_id", "0x6"), ("sqrt_price_after", "42")]);
+        assert_eq!(parse_swap_json(&mmt).unwrap().sqrt_price, 42);
+
+        let liquidity_added = event_json(&[("pool", "0x5"), ("amount_a", "10")]);
+        assert_eq!(parse_swap_json(&liquidity_added), None);
+    }
+
+    #[test]
+    fn test_parse_swap_filters_event_type() {
+        let config = AppConfig::default();
+        let swap_event_types = swap_event_types(&config);
+        assert_eq!(swap_event_types.len(), 4);
+        let event = |event_type: &str| Event {
+            event_type: Some(event_type.to_string()),
+            json: Some(Box::new(event_json(&[
+                ("pool", "0x5"),
+                ("after_sqrt_price", "42"),
+            ]))),
+            ..Default::default()
+        };
+
+        let swap = event(&config.cetus.swap_event_type);
+        assert_eq!(parse_swap(&swap, &swap_event_types).unwrap().sqrt_price, 42);
+        // same fields, emitted by another package
+        let other = event("0x7::pool::SwapEvent");
+        assert_eq!(parse_swap(&other, &swap_event_types), None);
+        let untyped = Event {
+            event_type: None,
+            ..swap
+        };
+        assert_eq!(parse_swap(&untyped, &swap_event_types), None);
+    }
+}
@@ -0,0 +1,629 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::app::{self, TransactionRequest};
+use crate::config::KeeperTrigger;
+use crate::dex;
+use crate::parsers::strategies::{self, auto_rebalance};
+use crate::parsers::{
+    self, AutoRebalanceStrategy, Pool, PoolSummary, Position, PositionSummary, Strategy,
+};
+use crate::transactions_builder::helper;
+use crate::AppState;
+use crate::EnclaveError;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sui_graphql_client::Client as GraphQLClient;
+use sui_rpc::field::FieldMask;
+use sui_rpc::proto::sui::rpc::v2beta2::GetObjectRequest;
+use sui_sdk_types::Address;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+
+pub mod checkpoints;
+
+/// A strategy and the pool its registry position provides liquidity to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub strategy_id: String,
+    pub pool_id: String,
+    pub position_registry_id: u64,
+}
+
+/// Outcome of evaluating a strategy against the current pool price.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Out of range and allowed to rebalance, a job should be started.
+    Eligible,
+    /// Within the acceptable sqrt price band, nothing to do until the price moves.
+    InRange,
+    /// Out of range but rebalancing now would abort on chain.
+    Blocked(String),
+}
+
+/// Whether `strategy` may be rebalanced at `now_ms`: the price must have left the
+/// acceptable band and the guardrails of `auto_rebalance::validate_rebalance` must
+/// hold, with some of the new range left within the strategy tick bounds.
+pub fn check_rebalance(
+    strategy: &AutoRebalanceStrategy,
+    pool: &PoolSummary,
+    position: &PositionSummary,
+    now_ms: u64,
+) -> Verdict {
+    if !auto_rebalance::is_out_of_range(pool.current_sqrt_price, band(strategy, position)) {
+        return Verdict::InRange;
+    }
+    let checked = auto_rebalance::validate_rebalance(strategy, now_ms).and_then(|_| {
+        auto_rebalance::rebalance_tick_range(
+            strategy,
+            pool.current_sqrt_price,
+            pool.current_tick_u32,
+            position.tick_lower_index_u32 as i32,
+            position.tick_upper_index_u32 as i32,
+            pool.tick_spacing,
+        )
+    });
+    match checked {
+        Ok(_) => Verdict::Eligible,
+        Err(e) => Verdict::Blocked(e.to_string()),
+    }
+}
+
+/// `auto_rebalance::acceptable_sqrt_price_band` of `position` under `strategy`.
+fn band(strategy: &AutoRebalanceStrategy, position: &PositionSummary) -> (u128, u128) {
+    auto_rebalance::acceptable_sqrt_price_band(
+        position.tick_lower_index_u32 as i32,
+        position.tick_upper_index_u32 as i32,
+        strategy.lower_sqrt_price_change_threshold_bps,
+        strategy.upper_sqrt_price_change_threshold_bps,
+        strategy.lower_sqrt_price_change_threshold_direction,
+        strategy.upper_sqrt_price_change_threshold_direction,
+    )
+}
+
+/// Built-in scheduler starting the rebalance jobs `/process_data_v2` would otherwise
+/// have to be called for. See `run`.
+pub struct Keeper {
+    state: Arc<AppState>,
+    jobs: Arc<Semaphore>,
+    /// Strategies with a running job, they are not evaluated until it ends.
+    running: Arc<Mutex<HashSet<String>>>,
+    targets: Vec<Target>,
+    /// Pool sqrt prices seen by the last poll.
+    sqrt_prices: HashMap<String, u128>,
+    /// Strategies found in range by the last poll, skipped while their pool price holds.
+    in_range: HashSet<String>,
+    /// Acceptable sqrt price band of each evaluated strategy.
+    bands: HashMap<String, (u128, u128)>,
+    /// Strategies a swap moved out of their band, evaluated by `poll_queued`.
+    queued: HashSet<String>,
+}
+
+impl Keeper {
+    pub fn new(state: Arc<AppState>) -> Self {
+        let max_concurrency = state.config.keeper.max_concurrency.max(1);
+        Self {
+            state,
+            jobs: Arc::new(Semaphore::new(max_concurrency)),
+            running: Default::default(),
+            targets: vec![],
+            sqrt_prices: HashMap::new(),
+            in_range: HashSet::new(),
+            bands: HashMap::new(),
+            queued: HashSet::new(),
+        }
+    }
+
+    /// Pair every strategy object with the pool of its position in the registry bag.
+    pub async fn discover(
+        &mut self,
+        graphql_client: &GraphQLClient,
+        client: &mut sui_rpc::Client,
+    ) -> Result<(), anyhow::Error> {
+        let config = &self.state.config;
+        let bag = Address::from_hex(&config.kurage.registry_bag_object_id)?;
+        let mut pool_ids = HashMap::new();
+        for field in helper::get_dynamic_fields(graphql_client, bag).await? {
+            let Ok(position_registry_id) = bcs::from_bytes::<u64>(&field.name.bcs) else {
+                continue;
+            };
+            let Some(json) = field.value_as_json else {
+                continue;
+            };
+            match parse_position(json) {
+                Ok(position) => {
+                    pool_ids.insert(position_registry_id, position.pool_id().to_string());
+                }
+                Err(e) => warn!(position_registry_id, error = %e, "keeper skips position"),
+            }
+        }
+
+        let strategy_type = config.keeper.strategy_type(&config.kurage);
+        let mut targets = vec![];
+        for object in helper::get_by_type(graphql_client, &strategy_type).await? {
+            let strategy_id = object.object_id().to_string();
+            let strategy = match get_strategy(client, &strategy_id).await {
+                Ok(strategy) => strategy,
+                Err(e) => {
+                    warn!(strategy_id = %strategy_id, error = %e, "keeper skips strategy");
+                    continue;
+                }
+            };
+            match pool_ids.get(&strategy.position_registry_id) {
+                Some(pool_id) => targets.push(Target {
+                    strategy_id,
+                    pool_id: pool_id.clone(),
+                    position_registry_id: strategy.position_registry_id,
+                }),
+                None => warn!(
+                    strategy_id = %strategy_id,
+                    position_registry_id = strategy.position_registry_id,
+                    "keeper skips strategy, its position is not in the registry"
+                ),
+            }
+        }
+        info!(
+            strategies = targets.len(),
+            positions = pool_ids.len(),
+            "keeper discovered strategies"
+        );
+        self.targets = targets;
+        Ok(())
+    }
+
+    /// Read every pool once and evaluate the strategies whose pool price moved, or
+    /// that were not in range at the last poll. Eligible strategies get a job as
+    /// long as fewer than `max_concurrency` are running.
+    pub async fn poll(&mut self, graphql_client: &GraphQLClient, client: &mut sui_rpc::Client) {
+        let targets = self.targets.clone();
+        self.poll_targets(graphql_client, client, targets, false)
+            .await;
+    }
+
+    /// Queue the strategies on the pool of `swap` whose band the new price left, or
+    /// that were not evaluated yet.
+    pub fn on_swap(&mut self, swap: &checkpoints::Swap) {
+        for target in &self.targets {
+            if target.pool_id != swap.pool_id {
+                continue;
+            }
+            let left_band = self
+                .bands
+                .get(&target.strategy_id)
+                .map_or(true, |(min, max)| {
+                    swap.sqrt_price < *min || swap.sqrt_price > *max
+                });
+            if left_band {
+                self.queued.insert(target.strategy_id.clone());
+            }
+        }
+    }
+
+    /// Evaluate the strategies queued by `on_swap`, reading only their pools.
+    pub async fn poll_queued(
+        &mut self,
+        graphql_client: &GraphQLClient,
+        client: &mut sui_rpc::Client,
+    ) {
+        if self.queued.is_empty() {
+            return;
+        }
+        let targets = self
+            .targets
+            .iter()
+            .filter(|target| self.queued.contains(&target.strategy_id))
+            .cloned()
+            .collect();
+        self.queued.clear();
+        self.poll_targets(graphql_client, client, targets, true)
+            .await;
+    }
+
+    /// Evaluate `targets`, all of them when `force` and otherwise only those whose pool
+    /// price moved or that were not in range. Targets left once `max_concurrency` jobs
+    /// run are queued.
+    async fn poll_targets(
+        &mut self,
+        graphql_client: &GraphQLClient,
+        client: &mut sui_rpc::Client,
+        targets: Vec<Target>,
+        force: bool,
+    ) {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_millis() as u64)
+            .unwrap_or_default();
+
+        let mut pools = HashMap::new();
+        for target in &targets {
+            if pools.contains_key(&target.pool_id) {
+                continue;
+            }
+            match get_pool(client, &target.pool_id).await {
+                Ok(pool) => {
+                    pools.insert(target.pool_id.clone(), pool);
+                }
+                Err(e) => warn!(pool_id = %target.pool_id, error = %e, "keeper failed to read pool"),
+            }
+        }
+
+        for (i, target) in targets.iter().enumerate() {
+            let Some((pool, pool_summary)) = pools.get(&target.pool_id) else {
+                continue;
+            };
+            if self.running.lock().unwrap().contains(&target.strategy_id) {
+                continue;
+            }
+            let price_moved =
+                self.sqrt_prices.get(&target.pool_id) != Some(&pool_summary.current_sqrt_price);
+            if !force && !price_moved && self.in_range.contains(&target.strategy_id) {
+                continue;
+            }
+            let verdict = match self
+                .evaluate(graphql_client, client, target, pool, pool_summary, now_ms)
+                .await
+            {
+                Ok((verdict, band)) => {
+                    self.bands.insert(target.strategy_id.clone(), band);
+                    Ok(verdict)
+                }
+                Err(e) => Err(e),
+            };
+            match verdict {
+                Ok(Verdict::Eligible) => {
+                    self.in_range.remove(&target.strategy_id);
+                    if !self.start(target) {
+                        self.queued
+                            .extend(targets[i..].iter().map(|target| target.strategy_id.clone()));
+                        break;
+                    }
+                }
+                Ok(Verdict::InRange) => {
+                    self.in_range.insert(target.strategy_id.clone());
+                }
+                Ok(Verdict::Blocked(reason)) => {
+                    self.in_range.remove(&target.strategy_id);
+                    info!(
+                        strategy_id = %target.strategy_id,
+                        pool_id = %target.pool_id,
+                        reason = %reason,
+                        "keeper holds strategy"
+                    );
+                }
+                Err(e) => error!(
+                    strategy_id = %target.strategy_id,
+                    pool_id = %target.pool_id,
+                    error = %e,
+                    "keeper failed to evaluate strategy"
+                ),
+            }
+        }
+
+        self.sqrt_prices.extend(
+            pools
+                .into_iter()
+                .map(|(pool_id, (_, summary))| (pool_id, summary.current_sqrt_price)),
+        );
+    }
+
+    async fn evaluate(
+        &self,
+        graphql_client: &GraphQLClient,
+        client: &mut sui_rpc::Client,
+        target: &Target,
+        pool: &Pool,
+        pool_summary: &PoolSummary,
+        now_ms: u64,
+    ) -> Result<(Verdict, (u128, u128)), anyhow::Error> {
+        let strategy = get_strategy(client, &target.strategy_id).await?;
+        let position_json = parsers::fetch_registry_position(
+            graphql_client,
+            &self.state.config,
+            strategy.position_registry_id,
+        )
+        .await?;
+        let adapter = dex::get_adapter(pool.dex())?;
+        let position = adapter.parse_position(&Box::new(position_json))?;
+        let position_summary = adapter.position_summary(&position)?;
+        let verdict = check_rebalance(&strategy, pool_summary, &position_summary, now_ms);
+        Ok((verdict, band(&strategy, &position_summary)))
+    }
+
+    /// Start the job of `target` unless `max_concurrency` jobs are running. The job is
+    /// attested with the enclave the current key is registered for.
+    fn start(&self, target: &Target) -> bool {
+        let Some(enclave_id) = self.state.enclave_id() else {
+            return false;
+        };
+        let Ok(permit) = self.jobs.clone().try_acquire_owned() else {
+            return false;
+        };
+        self.running
+            .lock()
+            .unwrap()
+            .insert(target.strategy_id.clone());
+        let request = TransactionRequest {
+            pool_id: target.pool_id.clone(),
+            strategy_id: target.strategy_id.clone(),
+            enclave_id,
+        };
+        let (job_id, handle) = app::spawn_job(self.state.clone(), request);
+        info!(
+            job_id = %job_id,
+            strategy_id = %target.strategy_id,
+            pool_id = %target.pool_id,
+            "keeper started job"
+        );
+        let running = self.running.clone();
+        let strategy_id = target.strategy_id.clone();
+        tokio::spawn(async move {
+            let _ = handle.await;
+            running.lock().unwrap().remove(&strategy_id);
+            drop(permit);
+        });
+        true
+    }
+}
+
+/// Discover the strategies every `discovery_interval_secs` and evaluate them until the
+/// server stops, see `KeeperTrigger`. Returns at once unless enabled.
+pub async fn run(state: Arc<AppState>) {
+    let config = state.config.keeper.clone();
+    if !config.enabled {
+        return;
+    }
+    if state.enclave_id().is_none() {
+        warn!("keeper disabled, keeper.enclave_id is not set");
+        return;
+    }
+    let (graphql_client, mut client) =
+        match (state.config.graphql_client(), state.config.rpc_client()) {
+            (Ok(graphql_client), Ok(client)) => (graphql_client, client),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!(error = %e, "keeper disabled");
+                return;
+            }
+        };
+    let mut keeper = Keeper::new(state);
+    let discovery_interval = Duration::from_secs(config.discovery_interval_secs);
+    let poll_interval = Duration::from_secs(config.poll_interval_secs);
+    let mut discovered_at: Option<Instant> = None;
+    loop {
+        if discovered_at.map_or(true, |at| at.elapsed() >= discovery_interval) {
+            match keeper.discover(&graphql_client, &mut client).await {
+                Ok(()) => discovered_at = Some(Instant::now()),
+                Err(e) => error!(error = %e, "keeper discovery failed"),
+            }
+        }
+        keeper.poll(&graphql_client, &mut client).await;
+        if config.trigger == KeeperTrigger::Checkpoints {
+            let next_discovery = discovered_at.unwrap_or_else(Instant::now) + discovery_interval;
+            follow_checkpoints(&mut keeper, &graphql_client, &mut client, next_discovery).await;
+        } else {
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+}
+
+/// Queue an evaluation whenever a swap moves a pool price out of a strategy band,
+/// until `deadline` or until the stream fails. A failed subscription falls back to
+/// one poll interval of sleep.
+async fn follow_checkpoints(
+    keeper: &mut Keeper,
+    graphql_client: &GraphQLClient,
+    client: &mut sui_rpc::Client,
+    deadline: Instant,
+) {
+    let mut stream = match checkpoints::subscribe(client).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!(error = %e, "keeper failed to subscribe to checkpoints");
+            let poll_interval = keeper.state.config.keeper.poll_interval_secs;
+            tokio::time::sleep(Duration::from_secs(poll_interval)).await;
+            return;
+        }
+    };
+    let swap_event_types = checkpoints::swap_event_types(&keeper.state.config);
+    let deadline = tokio::time::sleep_until(deadline.into());
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            message = stream.message() => match message {
+                Ok(Some(response)) => {
+                    for swap in checkpoints::swaps(&response, &swap_event_types) {
+                        keeper.on_swap(&swap);
+                    }
+                    keeper.poll_queued(graphql_client, client).await;
+                }
+                Ok(None) => {
+                    warn!("keeper checkpoint stream ended");
+                    return;
+                }
+                Err(e) => {
+                    warn!(error = %e, "keeper checkpoint stream failed");
+                    return;
+                }
+            },
+            _ = &mut deadline => return,
+        }
+    }
+}
+
+fn parse_position(json: serde_json::Value) -> Result<Position, anyhow::Error> {
+    let json = Box::new(json);
+    dex::adapters()
+        .into_iter()
+        .find_map(|adapter| adapter.parse_position(&json).ok())
+        .ok_or_else(|| anyhow::anyhow!("no adapter parses the position"))
+}
+
+async fn get_object_json(
+    client: &mut sui_rpc::Client,
+    kind: &str,
+    object_id: &str,
+) -> Result<Box<prost_types::Value>, EnclaveError> {
+    client
+        .ledger_client()
+        .get_object(GetObjectRequest {
+            object_id: Some(object_id.to_string()),
+            version: None,
+            read_mask: Some(FieldMask {
+                paths: vec!["object_id".into(), "json".into()],
+            }),
+        })
+        .await
+        .map_err(|e| app::get_object_error(kind, object_id, e))?
+        .into_inner()
+        .object
+        .and_then(|object| object.json)
+        .ok_or_else(|| EnclaveError::ParseMismatch(format!("{} {} has no json", kind, object_id)))
+}
+
+async fn get_strategy(
+    client: &mut sui_rpc::Client,
+    strategy_id: &str,
+) -> Result<AutoRebalanceStrategy, anyhow::Error> {
+    let json = get_object_json(client, "strategy", strategy_id).await?;
+    let Strategy::AutoRebalance(strategy) = strategies::try_match(json)?;
+    Ok(strategy)
+}
+
+async fn get_pool(
+    client: &mut sui_rpc::Client,
+    pool_id: &str,
+) -> Result<(Pool, PoolSummary), anyhow::Error> {
+    let json = get_object_json(client, "pool", pool_id).await?;
+    let pool = parsers::pools::try_match(json)?;
+    let summary = dex::get_adapter(pool.dex())?.pool_summary(&pool)?;
+    Ok((pool, summary))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::tick_math;
+    use crate::parsers::I32Wrapper;
+    use nsm::NitroNsm;
+    use std::sync::RwLock;
+    use sui_sdk_types::TypeTag;
+
+    fn strategy() -> AutoRebalanceStrategy {
+        AutoRebalanceStrategy {
+            id: "0x1".to_string(),
+            owner: "0x2".to_string(),
+            position_registry_id: 7,
+            description: String::new(),
+            lower_sqrt_price_change_threshold_bps: 0,
+            upper_sqrt_price_change_threshold_bps: 0,
+            lower_sqrt_price_change_threshold_direction: true,
+            upper_sqrt_price_change_threshold_direction: true,
+            rebalance_cooldown_secs: 3600,
+            range_multiplier_lower: 1000,
+            range_multiplier_upper: 1000,
+            rebalance_max_tick: I32Wrapper {
+                bits: 100000u32.to_string(),
+            },
+            rebalance_min_tick: I32Wrapper {
+                bits: (-100000i32 as u32).to_string(),
+            },
+            rebalance_paused: false,
+            lp_slippage_tolerance_bps: 50,
+            compound_min_value: None,
+            last_rebalance_timestamp: 1000000,
+        }
+    }
+
+    fn pool(tick: i32) -> PoolSummary {
+        PoolSummary {
+            current_tick_u32: tick as u32,
+            current_sqrt_price: tick_math::get_sqrt_price_at_tick(tick),
+            tick_spacing: 60,
+            fee_rate: 2500,
+            liquidity: 0,
+            rewarder_coin_types: vec![],
+        }
+    }
+
+    fn position() -> PositionSummary {
+        PositionSummary {
+            tick_lower_index_u32: -600i32 as u32,
+            tick_upper_index_u32: 600,
+            coin_a_type: TypeTag::U64,
+            coin_b_type: TypeTag::U64,
+            liquidity: 1000,
+        }
+    }
+
+    #[test]
+    fn test_check_rebalance() {
+        let now_ms = 1000000 + 3600 * 1000;
+        assert_eq!(
+            check_rebalance(&strategy(), &pool(0), &position(), now_ms),
+            Verdict::InRange
+        );
+        assert_eq!(
+            check_rebalance(&strategy(), &pool(5000), &position(), now_ms),
+            Verdict::Eligible
+        );
+
+        let paused = AutoRebalanceStrategy {
+            rebalance_paused: true,
+            ..strategy()
+        };
+        assert!(matches!(
+            check_rebalance(&paused, &pool(5000), &position(), now_ms),
+            Verdict::Blocked(_)
+        ));
+        assert!(matches!(
+            check_rebalance(&strategy(), &pool(5000), &position(), now_ms - 1),
+            Verdict::Blocked(_)
+        ));
+
+        // the new range starts above 2000, nothing of it is left within the bounds
+        let bounded = AutoRebalanceStrategy {
+            rebalance_max_tick: I32Wrapper {
+                bits: 2000u32.to_string(),
+            },
+            ..strategy()
+        };
+        assert!(matches!(
+            check_rebalance(&bounded, &pool(5000), &position(), now_ms),
+            Verdict::Blocked(_)
+        ));
+    }
+
+    #[test]
+    fn test_on_swap() {
+        let state = AppState {
+            enclave_id: RwLock::new(Some("0x3".to_string())),
+            ..AppState::for_test(NitroNsm)
+        };
+        let mut keeper = Keeper::new(Arc::new(state));
+        let target = |strategy_id: &str, pool_id: &str| Target {
+            strategy_id: strategy_id.to_string(),
+            pool_id: pool_id.to_string(),
+            position_registry_id: 0,
+        };
+        keeper.targets = vec![
+            target("0xa", "0x1"),
+            target("0xb", "0x1"),
+            target("0xc", "0x2"),
+        ];
+        keeper.bands.insert("0xa".to_string(), (100, 200));
+
+        let swap = |sqrt_price| checkpoints::Swap {
+            pool_id: "0x1".to_string(),
+            sqrt_price,
+        };
+        // 0xb was never evaluated, 0xa stays within its band
+        keeper.on_swap(&swap(150));
+        assert_eq!(keeper.queued, HashSet::from(["0xb".to_string()]));
+
+        keeper.on_swap(&swap(250));
+        assert_eq!(
+            keeper.queued,
+            HashSet::from(["0xa".to_string(), "0xb".to_string()])
+        );
+    }
+}
@@ -7,37 +7,247 @@ use axum::response::Response;
 use axum::Json;
 use fastcrypto::ed25519::Ed25519KeyPair;
 use serde_json::json;
+use nsm::Nsm;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use crate::transactions_builder::helper::SuiUtilsError;
 
 pub mod app;
 pub mod common;
+pub mod config;
+pub mod dex;
+pub mod enclave_key;
+pub mod gas_pool;
+pub mod jobs;
+pub mod keeper;
 pub mod math;
 pub mod parsers;
+pub mod rotation;
+pub mod sponsor;
 pub mod transactions_builder;
 pub mod aggregator;
 
 /// App state, at minimum needs to maintain the ephemeral keypair.  
 pub struct AppState {
-    /// Ephemeral keypair on boot
-    pub eph_kp: Ed25519KeyPair,
+    /// Ephemeral keypair on boot, replaced when the key is rotated
+    pub eph_kp: RwLock<Arc<Ed25519KeyPair>>,
     /// API key when querying api.weatherapi.com
     pub pk_string: String,
+    /// Runtime configuration loaded on boot
+    pub config: config::AppConfig,
+    /// Background `process_data_v2` jobs
+    pub jobs: jobs::JobStore,
+    /// Gas coins leased to in-flight transactions
+    pub gas_pool: gas_pool::GasPool,
+    /// Gas owner co-signing transactions, `None` when the keeper pays its own gas
+    pub sponsor: Option<Arc<dyn sponsor::Sponsor>>,
+    /// Signs attestations, the NSM device or its mock outside an enclave
+    pub nsm: Arc<dyn Nsm>,
+    /// `eph_kp` sealed with KMS, base64 encoded, in sealed key mode
+    pub sealed_key: RwLock<Option<String>>,
+    /// `Enclave` object registered for `eph_kp`, `keeper.enclave_id` until rotated
+    pub enclave_id: RwLock<Option<String>>,
+    /// Admin token and pending key of `/rotate_key`
+    pub rotation: rotation::Rotation,
+}
+
+impl AppState {
+    /// Key the enclave currently signs with.
+    pub fn eph_kp(&self) -> Arc<Ed25519KeyPair> {
+        self.eph_kp.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn sealed_key(&self) -> Option<String> {
+        self.sealed_key.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn enclave_id(&self) -> Option<String> {
+        self.enclave_id.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State with a fresh key, the default config and no signing key, attesting
+    /// with `nsm`.
+    pub(crate) fn for_test(nsm: impl Nsm + 'static) -> Self {
+        use fastcrypto::traits::KeyPair;
+        Self {
+            eph_kp: RwLock::new(Arc::new(Ed25519KeyPair::generate(&mut rand::thread_rng()))),
+            pk_string: "".to_string(),
+            config: Default::default(),
+            jobs: Default::default(),
+            gas_pool: Default::default(),
+            sponsor: None,
+            nsm: Arc::new(nsm),
+            sealed_key: Default::default(),
+            enclave_id: Default::default(),
+            rotation: Default::default(),
+        }
+    }
 }
 
 /// Implement IntoResponse for EnclaveError.
 impl IntoResponse for EnclaveError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            EnclaveError::GenericError(e) => (StatusCode::BAD_REQUEST, e),
-        };
-        let body = Json(json!({
-            "error": error_message,
-        }));
-        (status, body).into_response()
+        let status = self.status_code();
+        let mut body = json!({
+            "code": self.code(),
+            "error": self.to_string(),
+        });
+        if let Some(abort_code) = self.abort_code() {
+            body["abort_code"] = json!(abort_code);
+        }
+        (status, Json(body)).into_response()
     }
 }
 
 /// Enclave errors enum.
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum EnclaveError {
+    #[error("{0}")]
     GenericError(String),
+    /// The fullnode or GraphQL service failed to answer.
+    #[error("Upstream RPC failure: {0}")]
+    UpstreamRpc(String),
+    #[error("Object not found: {0}")]
+    ObjectNotFound(String),
+    /// An on-chain object did not have the layout the parsers expect.
+    #[error("Parse mismatch: {0}")]
+    ParseMismatch(String),
+    /// The strategy has nothing to do for the position right now.
+    #[error("Strategy not eligible: {0}")]
+    StrategyNotEligible(String),
+    #[error("Aggregator failure: {0}")]
+    AggregatorFailure(String),
+    #[error("Gas unavailable: {0}")]
+    GasUnavailable(String),
+    /// The transaction failed on-chain, with the Move abort code when it aborted.
+    #[error("Execution aborted: {message}")]
+    ExecutionAborted {
+        abort_code: Option<u64>,
+        message: String,
+    },
+    /// The simulated transaction broke the preflight policy, so it was never signed.
+    #[error("Preflight rejected: {0}")]
+    PreflightRejected(String),
+    #[error("Signing failure: {0}")]
+    SigningFailure(String),
+    #[error("Job not found: {0}")]
+    JobNotFound(String),
+    /// An admin endpoint was called without its token.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    /// The Nitro Secure Module failed to produce an attestation document.
+    #[error("Attestation failure: {0}")]
+    AttestationFailure(String),
+}
+
+impl EnclaveError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            EnclaveError::GenericError(_) => StatusCode::BAD_REQUEST,
+            EnclaveError::UpstreamRpc(_) => StatusCode::BAD_GATEWAY,
+            EnclaveError::ObjectNotFound(_) => StatusCode::NOT_FOUND,
+            EnclaveError::ParseMismatch(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            EnclaveError::StrategyNotEligible(_) => StatusCode::PRECONDITION_FAILED,
+            EnclaveError::AggregatorFailure(_) => StatusCode::FAILED_DEPENDENCY,
+            EnclaveError::GasUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            EnclaveError::ExecutionAborted { .. } => StatusCode::CONFLICT,
+            EnclaveError::PreflightRejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            EnclaveError::SigningFailure(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            EnclaveError::JobNotFound(_) => StatusCode::NOT_FOUND,
+            EnclaveError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            EnclaveError::AttestationFailure(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Machine readable code returned in the `code` field of the error body.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EnclaveError::GenericError(_) => "BAD_REQUEST",
+            EnclaveError::UpstreamRpc(_) => "UPSTREAM_RPC_FAILURE",
+            EnclaveError::ObjectNotFound(_) => "OBJECT_NOT_FOUND",
+            EnclaveError::ParseMismatch(_) => "PARSE_MISMATCH",
+            EnclaveError::StrategyNotEligible(_) => "STRATEGY_NOT_ELIGIBLE",
+            EnclaveError::AggregatorFailure(_) => "AGGREGATOR_FAILURE",
+            EnclaveError::GasUnavailable(_) => "GAS_UNAVAILABLE",
+            EnclaveError::ExecutionAborted { .. } => "EXECUTION_ABORTED",
+            EnclaveError::PreflightRejected(_) => "PREFLIGHT_REJECTED",
+            EnclaveError::SigningFailure(_) => "SIGNING_FAILURE",
+            EnclaveError::JobNotFound(_) => "JOB_NOT_FOUND",
+            EnclaveError::Unauthorized(_) => "UNAUTHORIZED",
+            EnclaveError::AttestationFailure(_) => "ATTESTATION_FAILURE",
+        }
+    }
+
+    pub fn abort_code(&self) -> Option<u64> {
+        match self {
+            EnclaveError::ExecutionAborted { abort_code, .. } => *abort_code,
+            _ => None,
+        }
+    }
+
+    /// Recover the typed error carried by `err`, the lower layers return `anyhow::Error`
+    /// wrapping either an `EnclaveError` or a `SuiUtilsError`. Anything else is
+    /// reported through `fallback`.
+    pub fn from_anyhow(err: anyhow::Error, fallback: fn(String) -> EnclaveError) -> Self {
+        let err = match err.downcast::<EnclaveError>() {
+            Ok(enclave_error) => return enclave_error,
+            Err(err) => err,
+        };
+        match err.downcast::<SuiUtilsError>() {
+            Ok(sui_error) => sui_error.into(),
+            Err(err) => fallback(format!("{:#}", err)),
+        }
+    }
+}
+
+impl From<SuiUtilsError> for EnclaveError {
+    fn from(err: SuiUtilsError) -> Self {
+        let message = err.to_string();
+        match err {
+            SuiUtilsError::GraphQL(_) | SuiUtilsError::InvalidTransactionEffects => {
+                EnclaveError::UpstreamRpc(message)
+            }
+            SuiUtilsError::ObjectNotFound(id) => EnclaveError::ObjectNotFound(id.to_string()),
+            SuiUtilsError::ObjectContentsNotFound(_) => EnclaveError::ParseMismatch(message),
+            SuiUtilsError::GasCoinNotFound
+            | SuiUtilsError::InvalidGasInput
+            | SuiUtilsError::ReferenceGasPriceError => EnclaveError::GasUnavailable(message),
+            SuiUtilsError::TransactionBuildingError(e) => EnclaveError::GenericError(e),
+            SuiUtilsError::TransactionSigningError(e) => EnclaveError::SigningFailure(e),
+            SuiUtilsError::PreflightRejected(e) => EnclaveError::PreflightRejected(e),
+            SuiUtilsError::TransactionExecutionError { abort, .. } => {
+                EnclaveError::ExecutionAborted {
+                    abort_code: abort.map(|abort| abort.code),
+                    message,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow_keeps_typed_errors() {
+        let err: anyhow::Error = EnclaveError::AggregatorFailure("no route".to_string()).into();
+        let err = EnclaveError::from_anyhow(err, EnclaveError::ParseMismatch);
+        assert_eq!(err.status_code(), StatusCode::FAILED_DEPENDENCY);
+        assert_eq!(err.code(), "AGGREGATOR_FAILURE");
+
+        let err: anyhow::Error = SuiUtilsError::GasCoinNotFound.into();
+        let err = EnclaveError::from_anyhow(err, EnclaveError::ParseMismatch);
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_from_anyhow_fallback() {
+        let err = EnclaveError::from_anyhow(anyhow::anyhow!("Field 'liquidity' not found"), EnclaveError::ParseMismatch);
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.to_string(), "Parse mismatch: Field 'liquidity' not found");
+    }
 }
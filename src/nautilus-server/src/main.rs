@@ -3,34 +3,27 @@
 
 use anyhow::Result;
 use axum::{routing::get, routing::post, Router};
-use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
-use nautilus_server::app::{process_data_v2};
-use nautilus_server::common::{get_attestation, health_check};
+use fastcrypto::traits::KeyPair;
+use nautilus_server::app::{get_job, process_data_v2};
+use nautilus_server::common::{get_attestation, get_sealed_key, health_check, post_attestation};
+use nautilus_server::config::{AppConfig, EnclaveKeyMode};
+use nautilus_server::enclave_key;
+use nautilus_server::gas_pool::{self, GasPool};
+use nautilus_server::jobs::JobStore;
+use nautilus_server::keeper;
+use nautilus_server::rotation::{self, complete_rotation, rotate_key};
+use nautilus_server::sponsor;
 use nautilus_server::AppState;
-use std::sync::Arc;
+use nsm::Nsm;
+use std::sync::{Arc, RwLock};
 use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 use once_cell::sync::Lazy;
-use anyhow::Context;
 use hex;
 use fastcrypto::traits::ToFromBytes;
 
-// ---------- static keypair ----------
-
 #[tokio::main]
 async fn main() -> Result<()> {
-    // let eph_kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
-    // static eph_kp
-    let seed_hex = std::env::var("EPH_ED25519_SEED")
-        .context("EPH_ED25519_SEED not set (expect 32-byte hex seed)")?;
-    let seed_hex = seed_hex.trim_start_matches("0x");
-    let bytes = hex::decode(seed_hex).context("seed not valid hex")?;
-    anyhow::ensure!(bytes.len() == 32, "seed must be exactly 32 bytes");
-
-    // fastcrypto's Ed25519KeyPair can be deterministically derived from a 32-byte seed:
-    let seed: [u8; 32] = bytes.try_into().expect("length checked above");
-    let eph_kp = Ed25519KeyPair::from_bytes(&seed).unwrap();
-
     // This value can be stored with secret-manager. To do that, follow the prompt `sh configure_enclave.sh`
     // Answer `y` to `Do you want to use a secret?` and finish.
     // Then uncomment this code instead to fetch from env var API_KEY, which is fetched from secret manager.
@@ -38,21 +31,67 @@ async fn main() -> Result<()> {
 
     let pk_string = std::env::var("SUI_PK")
         .map_err(|_| anyhow::anyhow!("SUI_PK environment variable not set"))?;
-    let state = Arc::new(AppState { eph_kp, pk_string });
+    let config = AppConfig::load()?;
+    info!("loaded config: {:?}", config);
+    let listen_addr = config.listen_addr.clone();
+    let sponsor = sponsor::from_config(&config.sponsor, |key| std::env::var(key).ok())?;
+    let nsm: Arc<dyn Nsm> = nsm::from_mode(config.nsm.mode, config.nsm.pcrs()?)?.into();
+    // The signing key is generated inside the enclave, or unsealed by it in sealed mode.
+    // An unsealed key must be the one registered on chain for the configured enclave,
+    // so KEEPER_ENCLAVE_ID has to follow the enclave id a rotation registers.
+    let unsealing = config.enclave_key.mode == EnclaveKeyMode::Sealed
+        && std::env::var(enclave_key::SEALED_KEY_VAR).is_ok();
+    let registered_pk = match &config.keeper.enclave_id {
+        Some(enclave_id) if unsealing => Some(rotation::registered_pk(&config, enclave_id).await?),
+        _ => None,
+    };
+    let (eph_kp, sealed_key) = enclave_key::load(
+        &config.enclave_key,
+        nsm.as_ref(),
+        registered_pk.as_deref(),
+        |key| std::env::var(key).ok(),
+    )
+    .await?;
+    println!(
+        "enclave public key: {}",
+        hex::encode(eph_kp.public().as_bytes())
+    );
+    let enclave_id = config.keeper.enclave_id.clone();
+    let state = Arc::new(AppState {
+        eph_kp: RwLock::new(Arc::new(eph_kp)),
+        pk_string,
+        config,
+        jobs: JobStore::default(),
+        gas_pool: GasPool::default(),
+        sponsor,
+        nsm,
+        sealed_key: RwLock::new(sealed_key),
+        enclave_id: RwLock::new(enclave_id),
+        rotation: rotation::Rotation::new(std::env::var(rotation::ADMIN_TOKEN_VAR).ok()),
+    });
+    tokio::spawn(gas_pool::maintain(state.clone()));
+    tokio::spawn(keeper::run(state.clone()));
 
     // Define your own restricted CORS policy here if needed.
     let cors = CorsLayer::new().allow_methods(Any).allow_headers(Any);
 
     let app = Router::new()
         .route("/", get(ping))
-        .route("/get_attestation", get(get_attestation))
+        .route(
+            "/get_attestation",
+            get(get_attestation).post(post_attestation),
+        )
         .route("/process_data_v2", post(process_data_v2))
+        .route("/jobs/:id", get(get_job))
+        .route("/sealed_key", get(get_sealed_key))
+        .route("/rotate_key", post(rotate_key))
+        .route("/rotate_key/complete", post(complete_rotation))
         .route("/health_check", get(health_check))
         .route("/health_check_post", post(health_check_post))
         .with_state(state)
         .layer(cors);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    let listener = tokio::net::TcpListener::bind(&listen_addr).await?;
     println!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app.into_make_service())
         .await
@@ -1,10 +1,12 @@
 use bigdecimal::BigDecimal;
 use num_bigint::BigInt;
 use crate::math::tick_math;
+use crate::math::swap_math::{self, PoolTicks};
 use num_traits::ToPrimitive;
 use crate::aggregator;
 use sui_sdk_types::TypeTag;
 use std::io::{Error, ErrorKind};
+use tracing::warn;
 
 pub struct CalculateAmountsByLiquidityRequest {
     pub tick_lower_index: i32,
@@ -17,6 +19,9 @@ pub struct CalculateAddLiquidityOnlyCoinARequest {
     pub tick_lower_index: i32,
     pub tick_upper_index: i32,
     pub sqrt_price_x64: u128,
+    pub pool_liquidity: u128,
+    pub pool_ticks: PoolTicks,
+    pub fee_rate: u64, // scale 1,000,000
     pub coin_a_amount: u64,
     pub coin_a_type: TypeTag,
     pub coin_b_type: TypeTag,
@@ -38,8 +43,8 @@ pub struct EstimateLiquidityAndAmountBFromAmountARequest {
 }
 
 impl CalculateAddLiquidityOnlyCoinARequest {
-    pub fn new(tick_lower_index: i32, tick_upper_index: i32, sqrt_price_x64: u128, coin_a_amount: u64, coin_a_type: TypeTag, coin_b_type: TypeTag, max_remain_rate: u64) -> Self {
-        Self { tick_lower_index, tick_upper_index, sqrt_price_x64, coin_a_amount, coin_a_type, coin_b_type, max_remain_rate }
+    pub fn new(tick_lower_index: i32, tick_upper_index: i32, sqrt_price_x64: u128, pool_liquidity: u128, pool_ticks: PoolTicks, fee_rate: u64, coin_a_amount: u64, coin_a_type: TypeTag, coin_b_type: TypeTag, max_remain_rate: u64) -> Self {
+        Self { tick_lower_index, tick_upper_index, sqrt_price_x64, pool_liquidity, pool_ticks, fee_rate, coin_a_amount, coin_a_type, coin_b_type, max_remain_rate }
     }
 }
 
@@ -128,68 +133,185 @@ pub fn est_liquidity_from_amount_a(request: EstimateLiquidityAndAmountBFromAmoun
     return Ok(liquidity.to_u128().unwrap());
 }
 
-pub async fn calculate_add_liquidity_only_coin_a_liquidity(request: CalculateAddLiquidityOnlyCoinARequest) -> (u128, Option<aggregator::cetus::RouteData>) {
-    let cetus_aggregator = aggregator::cetus::CetusAggregator::new();
-    let mark_price = cetus_aggregator.get_mark_price(request.coin_a_type.to_string(), request.coin_b_type.to_string(), request.coin_a_amount).await.unwrap();
-    let (amount_a_ratio, amount_b_ratio) = get_amount_ratio(request.tick_lower_index, request.tick_upper_index, request.sqrt_price_x64);
-    let mut best_swap_amount = request.coin_a_amount * (amount_a_ratio * 1000000000f64) as u64 / 1000000000; // floor
-    let max_loop = 200;
-    let mut low = 0;
-    let mut high = request.coin_a_amount;
-    let mut liquidity = 0;
-    for i in 0..max_loop {
-        best_swap_amount = if i == 0 { best_swap_amount } else { (low + high) / 2 };
-        let receive_amount = (best_swap_amount as u128 * mark_price / 1000000000u128) as u64;
-        let rem_a = request.coin_a_amount - best_swap_amount;
-        let rem_b = receive_amount;
-        let est_liquidity = est_liquidity_from_amount_a(EstimateLiquidityAndAmountBFromAmountARequest {
-            tick_lower_index: request.tick_lower_index,
-            tick_upper_index: request.tick_upper_index,
-            sqrt_price_x64: request.sqrt_price_x64,
-            coin_a_amount: rem_a,
-        }).unwrap();
-        let amounts = calculate_amounts_by_liquidity(CalculateAmountsByLiquidityRequest {
-            tick_lower_index: request.tick_lower_index,
-            tick_upper_index: request.tick_upper_index,
-            sqrt_price_x64: request.sqrt_price_x64,
-            liquidity: est_liquidity,
-        }).unwrap();
-        println!("liquidity: {}", est_liquidity);
-        println!("amounts: {:?}", amounts);
-        println!("rem_a: {}", rem_a);
-        println!("rem_b: {}", rem_b);
-        println!("best_swap_amount: {}", best_swap_amount);
-        if rem_a >= amounts.0 && rem_b >= amounts.1 {
-            let not_add_amount_a = rem_a - amounts.0;
-            let not_add_amount_b = rem_b - amounts.1;
-            let max_remain_amount_a = rem_a * request.max_remain_rate / 1000000000;
-            let max_remain_amount_b = rem_b * request.max_remain_rate / 1000000000;
-            println!("not_add_amount_a: {}", not_add_amount_a);
-            println!("not_add_amount_b: {}", not_add_amount_b);
-            println!("max_remain_amount_a: {}", max_remain_amount_a);
-            println!("max_remain_amount_b: {}", max_remain_amount_b);
-            if not_add_amount_a > max_remain_amount_a || not_add_amount_b > max_remain_amount_b {
-                high = best_swap_amount - 1;
-            } else {
-                liquidity = est_liquidity;
-                break;
-            }
-        } else {
-            low = best_swap_amount + 1;
-        }
+/// Sizing of a zap funded only by coin A: swap `swap_amount_a` into B, then add `liquidity`
+/// at `sqrt_price_after_swap`, leaving `remain_a`/`remain_b` unadded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZapSolution {
+    pub swap_amount_a: u64,
+    pub expected_amount_b: u64,
+    pub sqrt_price_after_swap: u128,
+    pub liquidity: u128,
+    pub amount_a_added: u64,
+    pub amount_b_added: u64,
+    pub remain_a: u64,
+    pub remain_b: u64,
+}
+
+fn get_liquidity_from_amount_a(amount_a: u64, sqrt_price_0: u128, sqrt_price_1: u128) -> u128 {
+    if sqrt_price_1 <= sqrt_price_0 {
+        return 0;
+    }
+    let num = BigInt::from(amount_a) * BigInt::from(sqrt_price_0) * BigInt::from(sqrt_price_1);
+    let denom = (BigInt::from(sqrt_price_1) - BigInt::from(sqrt_price_0)) * BigInt::from(1u128 << 64);
+    (num / denom).to_u128().unwrap_or(u128::MAX)
+}
+
+fn get_liquidity_from_amount_b(amount_b: u64, sqrt_price_0: u128, sqrt_price_1: u128) -> u128 {
+    if sqrt_price_1 <= sqrt_price_0 {
+        return 0;
+    }
+    let num = BigInt::from(amount_b) * BigInt::from(1u128 << 64);
+    let denom = BigInt::from(sqrt_price_1) - BigInt::from(sqrt_price_0);
+    (num / denom).to_u128().unwrap_or(u128::MAX)
+}
+
+/// Largest liquidity the two amounts can fund in [sqrt_price_lower, sqrt_price_upper] at `sqrt_price`.
+pub fn get_liquidity_from_amounts(sqrt_price: u128, sqrt_price_lower: u128, sqrt_price_upper: u128, amount_a: u64, amount_b: u64) -> u128 {
+    if sqrt_price <= sqrt_price_lower {
+        get_liquidity_from_amount_a(amount_a, sqrt_price_lower, sqrt_price_upper)
+    } else if sqrt_price >= sqrt_price_upper {
+        get_liquidity_from_amount_b(amount_b, sqrt_price_lower, sqrt_price_upper)
+    } else {
+        get_liquidity_from_amount_a(amount_a, sqrt_price, sqrt_price_upper)
+            .min(get_liquidity_from_amount_b(amount_b, sqrt_price_lower, sqrt_price))
+    }
+}
+
+/// Price and output of an A to B swap, walking the pool's initialized ticks so a zap that
+/// pushes the price out of the active range is priced with the liquidity it meets there.
+fn estimate_swap_a_to_b(request: &CalculateAddLiquidityOnlyCoinARequest, amount_in: u64) -> Result<(u128, u64), Error> {
+    if amount_in == 0 {
+        return Ok((request.sqrt_price_x64, 0));
+    }
+    let result = swap_math::simulate_swap(request.sqrt_price_x64, request.pool_liquidity, request.fee_rate, &request.pool_ticks, true, amount_in)?;
+    Ok((result.after_sqrt_price, result.amount_out))
+}
+
+fn get_zap_solution(
+    request: &CalculateAddLiquidityOnlyCoinARequest,
+    swap_amount_a: u64,
+    amount_b: u64,
+    sqrt_price_after_swap: u128,
+) -> Result<ZapSolution, Error> {
+    let amount_a = request.coin_a_amount - swap_amount_a;
+    let liquidity = get_liquidity_from_amounts(
+        sqrt_price_after_swap,
+        tick_math::get_sqrt_price_at_tick(request.tick_lower_index),
+        tick_math::get_sqrt_price_at_tick(request.tick_upper_index),
+        amount_a,
+        amount_b,
+    );
+    let (amount_a_added, amount_b_added) = calculate_amounts_by_liquidity(CalculateAmountsByLiquidityRequest {
+        tick_lower_index: request.tick_lower_index,
+        tick_upper_index: request.tick_upper_index,
+        sqrt_price_x64: sqrt_price_after_swap,
+        liquidity,
+    })?;
+    Ok(ZapSolution {
+        swap_amount_a,
+        expected_amount_b: amount_b,
+        sqrt_price_after_swap,
+        liquidity,
+        amount_a_added,
+        amount_b_added,
+        remain_a: amount_a.saturating_sub(amount_a_added),
+        remain_b: amount_b.saturating_sub(amount_b_added),
+    })
+}
 
-        if low > high {
-            break;
+// one unit of slack on top of the rate absorbs the rounding of the liquidity math
+fn is_within_max_remain_rate(solution: &ZapSolution, coin_a_amount: u64, max_remain_rate: u64) -> bool {
+    let max_remain = |amount: u64| (amount as u128 * max_remain_rate as u128 / 1000000000u128) as u64 + 1;
+    solution.remain_a <= max_remain(coin_a_amount - solution.swap_amount_a)
+        && solution.remain_b <= max_remain(solution.expected_amount_b)
+}
+
+/// Size a coin A only zap from the pool's own state: find the smallest swap whose B output
+/// covers what the remaining A needs at the post-swap price. Fails if the leftovers exceed
+/// `max_remain_rate`.
+pub fn solve_add_liquidity_only_coin_a(request: &CalculateAddLiquidityOnlyCoinARequest) -> Result<ZapSolution, Error> {
+    let sqrt_price_lower = tick_math::get_sqrt_price_at_tick(request.tick_lower_index);
+    let sqrt_price_upper = tick_math::get_sqrt_price_at_tick(request.tick_upper_index);
+
+    let solution = if request.sqrt_price_x64 <= sqrt_price_lower {
+        // the range is above the price, it only takes coin A
+        get_zap_solution(request, 0, 0, request.sqrt_price_x64)?
+    } else {
+        if request.pool_liquidity == 0 {
+            return Err(Error::new(ErrorKind::Other, "Pool has no active liquidity to swap against"));
         }
+        let swap_amount_a = if request.sqrt_price_x64 >= sqrt_price_upper {
+            // the range is below the price, it only takes coin B
+            request.coin_a_amount
+        } else {
+            // the swap moves the price down and the B needed shrinks as the swap grows,
+            // so the smallest swap that covers it can be bisected
+            let mut low = 0u64;
+            let mut high = request.coin_a_amount;
+            while low < high {
+                let mid = low + (high - low) / 2;
+                let (sqrt_price_after, amount_b) = estimate_swap_a_to_b(request, mid)?;
+                let enough_b = if sqrt_price_after <= sqrt_price_lower {
+                    true
+                } else {
+                    let liquidity = get_liquidity_from_amount_a(request.coin_a_amount - mid, sqrt_price_after, sqrt_price_upper);
+                    amount_b as u128 >= swap_math::get_delta_b(sqrt_price_lower, sqrt_price_after, liquidity, true)?
+                };
+                if enough_b {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+            low
+        };
+        let (sqrt_price_after, amount_b) = estimate_swap_a_to_b(request, swap_amount_a)?;
+        get_zap_solution(request, swap_amount_a, amount_b, sqrt_price_after)?
     };
 
-    let route = cetus_aggregator.swap_by_amount_in(aggregator::cetus::SwapByAmountInRequest {
+    if !is_within_max_remain_rate(&solution, request.coin_a_amount, request.max_remain_rate) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "Zap leaves {} A and {} B unadded, above the max remain rate {}",
+                solution.remain_a, solution.remain_b, request.max_remain_rate
+            ),
+        ));
+    }
+    Ok(solution)
+}
+
+/// Solve the zap on pool state, then ask the aggregator for a route of the solved swap.
+/// The quote is optional: without it the swap is routed later, and when it quotes less B
+/// than the pool estimate the added amounts are sized down to the quote.
+pub async fn calculate_add_liquidity_only_coin_a_liquidity(request: CalculateAddLiquidityOnlyCoinARequest) -> Result<(ZapSolution, Option<aggregator::cetus::RouteData>), Error> {
+    let solution = solve_add_liquidity_only_coin_a(&request)?;
+    if solution.swap_amount_a == 0 {
+        return Ok((solution, None));
+    }
+
+    let cetus_aggregator = aggregator::cetus::CetusAggregator::new();
+    let route = match cetus_aggregator.swap_by_amount_in(aggregator::cetus::SwapByAmountInRequest {
         from: request.coin_a_type.to_string(),
         to: request.coin_b_type.to_string(),
-        amount_in: best_swap_amount,
-    }).await.unwrap();
+        amount_in: solution.swap_amount_a,
+    }).await {
+        Ok(route) => route.data,
+        Err(e) => {
+            warn!("aggregator quote unavailable, using the pool estimate: {:?}", e);
+            None
+        }
+    };
+    let Some(route_data) = route else {
+        return Ok((solution, None));
+    };
 
-    return (liquidity as u128, route.data);
+    let quoted_amount_b = route_data.routes.iter().map(|route| route.amount_out).sum::<u64>();
+    if quoted_amount_b >= solution.expected_amount_b {
+        return Ok((solution, Some(route_data)));
+    }
+    let solution = get_zap_solution(&request, solution.swap_amount_a, quoted_amount_b, solution.sqrt_price_after_swap)?;
+    Ok((solution, Some(route_data)))
 }
 
 mod test {
@@ -204,4 +326,70 @@ mod test {
         assert_eq!(amount_a, 1000000000000000000);
         assert_eq!(amount_b, 0);
     }
+
+    fn zap_request(tick_lower_index: i32, tick_upper_index: i32) -> CalculateAddLiquidityOnlyCoinARequest {
+        CalculateAddLiquidityOnlyCoinARequest::new(
+            tick_lower_index,
+            tick_upper_index,
+            1u128 << 64, // price 1, tick 0
+            1000000000000000000,
+            PoolTicks::default(),
+            2500,
+            1000000000,
+            TypeTag::U64,
+            TypeTag::U64,
+            2000000,
+        )
+    }
+
+    #[test]
+    fn test_solve_add_liquidity_only_coin_a_in_range() {
+        let solution = solve_add_liquidity_only_coin_a(&zap_request(-1000, 1000)).unwrap();
+        // a symmetric range around price 1 takes about as much B as A
+        assert!(solution.swap_amount_a > 490000000 && solution.swap_amount_a < 510000000);
+        assert!(solution.liquidity > 0);
+        assert!(solution.remain_a <= (1000000000 - solution.swap_amount_a) * 2000000 / 1000000000 + 1);
+        assert!(solution.remain_b <= solution.expected_amount_b * 2000000 / 1000000000 + 1);
+    }
+
+    #[test]
+    fn test_solve_add_liquidity_only_coin_a_out_of_range() {
+        // range above the price only takes A, nothing is swapped
+        let above = solve_add_liquidity_only_coin_a(&zap_request(1000, 2000)).unwrap();
+        assert_eq!(above.swap_amount_a, 0);
+        assert_eq!(above.amount_b_added, 0);
+        // range below the price only takes B, everything is swapped
+        let below = solve_add_liquidity_only_coin_a(&zap_request(-2000, -1000)).unwrap();
+        assert_eq!(below.swap_amount_a, 1000000000);
+        assert_eq!(below.amount_a_added, 0);
+    }
+
+    #[test]
+    fn test_solve_add_liquidity_only_coin_a_crossing_tick() {
+        // thin liquidity that drops tenfold below tick -2: the zap swap crosses it
+        let mut request = zap_request(-1000, 1000);
+        request.pool_liquidity = 1000000000000;
+        request.pool_ticks = PoolTicks {
+            current_tick_index: 0,
+            ticks: vec![swap_math::SwapTick {
+                index: -2,
+                sqrt_price: tick_math::get_sqrt_price_at_tick(-2),
+                liquidity_net: 900000000000,
+            }],
+        };
+        let crossing = solve_add_liquidity_only_coin_a(&request).unwrap();
+        request.pool_ticks = PoolTicks::default();
+        let single_range = solve_add_liquidity_only_coin_a(&request).unwrap();
+        // past the tick the same A moves the price further and buys less B
+        assert!(crossing.sqrt_price_after_swap < tick_math::get_sqrt_price_at_tick(-2));
+        assert!(crossing.sqrt_price_after_swap < single_range.sqrt_price_after_swap);
+        assert!(crossing.liquidity > 0);
+    }
+
+    #[test]
+    fn test_solve_add_liquidity_only_coin_a_without_pool_liquidity() {
+        let mut request = zap_request(-1000, 1000);
+        request.pool_liquidity = 0;
+        assert!(solve_add_liquidity_only_coin_a(&request).is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,330 @@
+use crate::math::full_math_u128;
+use crate::parsers::{CetusPoolData, CetusPosition, CetusPositionInfo, CetusTick};
+use num_traits::ToPrimitive;
+
+/// Fees and rewards a position can collect right now, rewards in pool rewarder order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionFeesAndRewards {
+    pub fee_a: u64,
+    pub fee_b: u64,
+    pub rewards: Vec<u64>,
+}
+
+/// Growth accumulated inside [tick_lower_index, tick_upper_index), following the
+/// Uniswap v3 "outside" bookkeeping. Uninitialized ticks have zero growth outside.
+pub fn get_growth_inside(
+    current_tick_index: i32,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    growth_global: u128,
+    lower_growth_outside: u128,
+    upper_growth_outside: u128,
+) -> u128 {
+    let growth_below = if current_tick_index >= tick_lower_index {
+        lower_growth_outside
+    } else {
+        growth_global.wrapping_sub(lower_growth_outside)
+    };
+    let growth_above = if current_tick_index < tick_upper_index {
+        upper_growth_outside
+    } else {
+        growth_global.wrapping_sub(upper_growth_outside)
+    };
+    growth_global.wrapping_sub(growth_below).wrapping_sub(growth_above)
+}
+
+/// `amount_owned` plus what `liquidity` earned since the growth snapshot, growth is Q64.64.
+pub fn get_pending_amount(amount_owned: u64, liquidity: u128, growth_inside: u128, growth_inside_last: u128) -> u64 {
+    let delta = full_math_u128::full_mul(liquidity, growth_inside.wrapping_sub(growth_inside_last)) >> 64usize;
+    let earned = delta.to_u64().unwrap_or(u64::MAX);
+    amount_owned.saturating_add(earned)
+}
+
+/// Rewarder global growths brought up to `current_time_secs`, the same way
+/// `rewarder::settle` would do on-chain before collecting.
+pub fn get_rewarder_growths_global(pool: &CetusPoolData, current_time_secs: u64) -> Result<Vec<u128>, anyhow::Error> {
+    let last_updated_time = pool.rewarder_manager.last_updated_time.parse::<u64>()?;
+    let liquidity = pool.liquidity.parse::<u128>()?;
+    let elapsed = current_time_secs.saturating_sub(last_updated_time) as u128;
+    pool.rewarder_manager
+        .rewarders
+        .iter()
+        .map(|rewarder| {
+            let growth_global = rewarder.growth_global.parse::<u128>()?;
+            if liquidity == 0 || elapsed == 0 {
+                return Ok(growth_global);
+            }
+            let emissions_per_second = rewarder.emissions_per_second.parse::<u128>()?;
+            let growth_delta = full_math_u128::mul_div_floor(elapsed, emissions_per_second, liquidity);
+            Ok(growth_global.wrapping_add(growth_delta))
+        })
+        .collect()
+}
+
+fn tick_growth_outside(tick: Option<&CetusTick>, growth: impl Fn(&CetusTick) -> Result<u128, anyhow::Error>) -> Result<u128, anyhow::Error> {
+    match tick {
+        Some(tick) => growth(tick),
+        None => Ok(0),
+    }
+}
+
+/// Pending fee A/B and rewards of `position`. `tick_lower` and `tick_upper` are the
+/// position's boundary ticks, `None` when not initialized in the pool.
+pub fn get_position_fees_and_rewards(
+    pool: &CetusPoolData,
+    tick_lower: Option<&CetusTick>,
+    tick_upper: Option<&CetusTick>,
+    position: &CetusPosition,
+    position_info: &CetusPositionInfo,
+    current_time_secs: u64,
+) -> Result<PositionFeesAndRewards, anyhow::Error> {
+    let current_tick_index = pool.current_tick_index.bits.parse::<u32>()? as i32;
+    let tick_lower_index = position.tick_lower_index.bits.parse::<u32>()? as i32;
+    let tick_upper_index = position.tick_upper_index.bits.parse::<u32>()? as i32;
+    let growth_inside = |growth_global: u128, lower_outside: u128, upper_outside: u128| {
+        get_growth_inside(
+            current_tick_index,
+            tick_lower_index,
+            tick_upper_index,
+            growth_global,
+            lower_outside,
+            upper_outside,
+        )
+    };
+
+    let fee_growth_inside_a = growth_inside(
+        pool.fee_growth_global_a.parse::<u128>()?,
+        tick_growth_outside(tick_lower, |tick| Ok(tick.fee_growth_outside_a.parse::<u128>()?))?,
+        tick_growth_outside(tick_upper, |tick| Ok(tick.fee_growth_outside_a.parse::<u128>()?))?,
+    );
+    let fee_growth_inside_b = growth_inside(
+        pool.fee_growth_global_b.parse::<u128>()?,
+        tick_growth_outside(tick_lower, |tick| Ok(tick.fee_growth_outside_b.parse::<u128>()?))?,
+        tick_growth_outside(tick_upper, |tick| Ok(tick.fee_growth_outside_b.parse::<u128>()?))?,
+    );
+
+    let rewards = get_rewarder_growths_global(pool, current_time_secs)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, growth_global)| {
+            let reward_growth_outside = |tick: &CetusTick| -> Result<u128, anyhow::Error> {
+                match tick.rewards_growth_outside.get(i) {
+                    Some(growth) => Ok(growth.parse::<u128>()?),
+                    None => Ok(0),
+                }
+            };
+            let reward_growth_inside = growth_inside(
+                growth_global,
+                tick_growth_outside(tick_lower, reward_growth_outside)?,
+                tick_growth_outside(tick_upper, reward_growth_outside)?,
+            );
+            // a rewarder added after the position's last update has no snapshot yet
+            let growth_inside_last = position_info.rewards_growth_inside.get(i).copied().unwrap_or(0);
+            let amount_owned = position_info.rewards_amount_owned.get(i).copied().unwrap_or(0);
+            Ok(get_pending_amount(
+                amount_owned,
+                position_info.liquidity,
+                reward_growth_inside,
+                growth_inside_last,
+            ))
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    Ok(PositionFeesAndRewards {
+        fee_a: get_pending_amount(
+            position_info.fee_owned_a,
+            position_info.liquidity,
+            fee_growth_inside_a,
+            position_info.fee_growth_inside_a,
+        ),
+        fee_b: get_pending_amount(
+            position_info.fee_owned_b,
+            position_info.liquidity,
+            fee_growth_inside_b,
+            position_info.fee_growth_inside_b,
+        ),
+        rewards,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsers::{I32Wrapper, TypeName};
+    use serde_json::json;
+
+    const Q64: u128 = 1 << 64;
+
+    fn bits(index: i32) -> String {
+        (index as u32).to_string()
+    }
+
+    fn tick(
+        index: i32,
+        fee_growth_outside_a: u128,
+        fee_growth_outside_b: u128,
+        rewards_growth_outside: &[u128],
+    ) -> CetusTick {
+        serde_json::from_value(json!({
+            "index": { "bits": bits(index) },
+            "sqrt_price": "0",
+            "liquidity_net": { "bits": "0" },
+            "liquidity_gross": "0",
+            "fee_growth_outside_a": fee_growth_outside_a.to_string(),
+            "fee_growth_outside_b": fee_growth_outside_b.to_string(),
+            "rewards_growth_outside": rewards_growth_outside.iter().map(u128::to_string).collect::<Vec<_>>(),
+        }))
+        .unwrap()
+    }
+
+    fn pool(current_tick_index: i32) -> CetusPoolData {
+        let rewarder = |emissions_per_second: u128, growth_global: u128| {
+            json!({
+                "emissions_per_second": emissions_per_second.to_string(),
+                "growth_global": growth_global.to_string(),
+                "reward_coin": { "name": "0x2::sui::SUI" },
+            })
+        };
+        serde_json::from_value(json!({
+            "coin_a": "0",
+            "coin_b": "0",
+            "current_sqrt_price": "0",
+            "current_tick_index": { "bits": bits(current_tick_index) },
+            "fee_growth_global_a": (100 * Q64).to_string(),
+            "fee_growth_global_b": (40 * Q64).to_string(),
+            "fee_protocol_coin_a": "0",
+            "fee_protocol_coin_b": "0",
+            "fee_rate": "2500",
+            "id": "0x1",
+            "index": "0",
+            "is_pause": false,
+            "liquidity": "1000",
+            "position_manager": {
+                "position_index": "0",
+                "positions": { "head": "", "id": "0x2", "size": "0", "tail": "" },
+                "tick_spacing": 10,
+            },
+            "rewarder_manager": {
+                "last_updated_time": "1000",
+                "points_growth_global": "0",
+                "points_released": "0",
+                "rewarders": [rewarder(10 * Q64, 5 * Q64), rewarder(0, 2 * Q64)],
+            },
+            "tick_manager": {
+                "tick_spacing": 10,
+                "ticks": {
+                    "head": [],
+                    "id": "0x3",
+                    "level": "0",
+                    "list_p": "0",
+                    "max_level": "0",
+                    "random": { "seed": "0" },
+                    "size": "0",
+                    "tail": { "is_none": true, "v": "0" },
+                },
+            },
+            "tick_spacing": 10,
+            "url": "",
+        }))
+        .unwrap()
+    }
+
+    fn position(tick_lower_index: i32, tick_upper_index: i32) -> CetusPosition {
+        let type_name = || TypeName { name: String::new() };
+        CetusPosition {
+            id: "0x4".to_string(),
+            pool: "0x1".to_string(),
+            index: 1,
+            coin_type_a: type_name(),
+            coin_type_b: type_name(),
+            name: String::new(),
+            description: String::new(),
+            url: String::new(),
+            tick_lower_index: I32Wrapper { bits: bits(tick_lower_index) },
+            tick_upper_index: I32Wrapper { bits: bits(tick_upper_index) },
+            liquidity: 500,
+        }
+    }
+
+    #[test]
+    fn test_get_growth_inside_in_range() {
+        // current tick inside the range: inside = global - lower_outside - upper_outside
+        assert_eq!(get_growth_inside(0, -10, 10, 100 * Q64, 30 * Q64, 20 * Q64), 50 * Q64);
+    }
+
+    #[test]
+    fn test_get_growth_inside_out_of_range() {
+        // below the range, all growth since the lower tick was crossed is outside
+        assert_eq!(get_growth_inside(-20, -10, 10, 100 * Q64, 30 * Q64, 20 * Q64), 10 * Q64);
+        // above the range, the upper tick outside growth is the growth below it
+        assert_eq!(get_growth_inside(20, -10, 10, 100 * Q64, 30 * Q64, 80 * Q64), 50 * Q64);
+    }
+
+    #[test]
+    fn test_get_growth_inside_wraps() {
+        // growth counters are allowed to overflow, only differences matter
+        let inside = get_growth_inside(0, -10, 10, 5, u128::MAX, 0);
+        assert_eq!(inside, 6);
+    }
+
+    #[test]
+    fn test_get_pending_amount() {
+        assert_eq!(get_pending_amount(7, 1_000, 3 * Q64, Q64), 2_007);
+        assert_eq!(get_pending_amount(7, 1_000, Q64, Q64), 7);
+        // wrapped growth since the snapshot
+        assert_eq!(get_pending_amount(0, 1_000, Q64 - 1, u128::MAX), 1_000);
+    }
+
+    #[test]
+    fn test_get_position_fees_and_rewards() {
+        let pool = pool(0);
+        let position = position(-100, 100);
+        let tick_lower = tick(-100, 30 * Q64, 10 * Q64, &[Q64]);
+        // the second rewarder was added after the ticks were last crossed
+        let tick_upper = tick(100, 20 * Q64, 5 * Q64, &[2 * Q64]);
+        let position_info = CetusPositionInfo {
+            position_id: position.id.clone(),
+            liquidity: 500,
+            fee_growth_inside_a: 40 * Q64,
+            fee_owned_a: 7,
+            fee_growth_inside_b: 25 * Q64,
+            fee_owned_b: 3,
+            rewards_growth_inside: vec![Q64],
+            rewards_amount_owned: vec![11],
+        };
+
+        // 100s after the last update the first rewarder grew by 100 * 10 / 1000 = 1 per liquidity
+        let fees = get_position_fees_and_rewards(
+            &pool,
+            Some(&tick_lower),
+            Some(&tick_upper),
+            &position,
+            &position_info,
+            1_100,
+        )
+        .unwrap();
+        assert_eq!(
+            fees,
+            PositionFeesAndRewards {
+                // inside a: 100 - 30 - 20 = 50, 10 more than the snapshot
+                fee_a: 7 + 500 * 10,
+                // inside b: 40 - 10 - 5 = 25, same as the snapshot
+                fee_b: 3,
+                // inside: 6 - 1 - 2 = 3, 2 more than the snapshot
+                // inside the second rewarder: 2 - 0 - 0, no snapshot yet
+                rewards: vec![11 + 500 * 2, 500 * 2],
+            }
+        );
+
+        // without initialized ticks all global growth counts as inside
+        let fees = get_position_fees_and_rewards(&pool, None, None, &position, &position_info, 1_000).unwrap();
+        assert_eq!(
+            fees,
+            PositionFeesAndRewards {
+                fee_a: 7 + 500 * 60,
+                fee_b: 3 + 500 * 15,
+                rewards: vec![11 + 500 * 4, 500 * 2],
+            }
+        );
+    }
+}
@@ -1,3 +1,5 @@
 pub mod tick_math;
 pub mod full_math_u128;
-pub mod clmm_math;
\ No newline at end of file
+pub mod clmm_math;
+pub mod fee_math;
+pub mod swap_math;
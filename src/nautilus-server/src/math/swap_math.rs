@@ -0,0 +1,330 @@
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+use crate::math::full_math_u128;
+use crate::math::tick_math;
+use crate::parsers::CetusTick;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+const FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+const MIN_SQRT_PRICE_X64: u128 = 4295048016;
+const MAX_SQRT_PRICE_X64: u128 = 79226673515401279992447579055;
+
+/// Outcome of swapping `amount_in` (fee included) through the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapResult {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    pub after_sqrt_price: u128,
+    pub after_tick_index: i32,
+    // the price limit was reached before the whole input was consumed
+    pub is_exceed: bool,
+}
+
+struct SwapStepResult {
+    amount_in: u128,
+    amount_out: u128,
+    fee_amount: u128,
+    next_sqrt_price: u128,
+}
+
+/// An initialized tick, `liquidity_net` is what enters the active range crossing it upwards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapTick {
+    pub index: i32,
+    pub sqrt_price: u128,
+    pub liquidity_net: i128,
+}
+
+/// Initialized ticks of a pool, sorted by index, and the tick its price currently sits in.
+/// Adapters that cannot list their ticks leave `ticks` empty: swaps then assume the
+/// active liquidity spans the whole price range.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolTicks {
+    pub current_tick_index: i32,
+    pub ticks: Vec<SwapTick>,
+}
+
+impl PoolTicks {
+    pub fn from_cetus(current_tick_index: i32, ticks: &[CetusTick]) -> Result<Self, anyhow::Error> {
+        let mut ticks = ticks
+            .iter()
+            .map(|tick| {
+                Ok(SwapTick {
+                    index: tick.index.bits.parse::<u32>()? as i32,
+                    sqrt_price: tick.sqrt_price.parse::<u128>()?,
+                    liquidity_net: tick.liquidity_net.bits.parse::<u128>()? as i128,
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        ticks.sort_by_key(|tick| tick.index);
+        Ok(Self {
+            current_tick_index,
+            ticks,
+        })
+    }
+}
+
+fn div_round(num: BigUint, denom: BigUint, round_up: bool) -> BigUint {
+    let quotient = &num / &denom;
+    if round_up && !(num % denom).is_zero() {
+        quotient + 1u32
+    } else {
+        quotient
+    }
+}
+
+fn to_u128(value: BigUint) -> Result<u128, Error> {
+    value.to_u128().ok_or_else(|| Error::new(ErrorKind::Other, "Value overflows u128"))
+}
+
+/// Amount of coin A between two sqrt prices for `liquidity`.
+pub fn get_delta_a(sqrt_price_0: u128, sqrt_price_1: u128, liquidity: u128, round_up: bool) -> Result<u128, Error> {
+    let sqrt_price_diff = sqrt_price_0.abs_diff(sqrt_price_1);
+    if sqrt_price_diff == 0 || liquidity == 0 {
+        return Ok(0);
+    }
+    let numerator = full_math_u128::full_mul(liquidity, sqrt_price_diff) << 64;
+    let denominator = full_math_u128::full_mul(sqrt_price_0, sqrt_price_1);
+    to_u128(div_round(numerator, denominator, round_up))
+}
+
+/// Amount of coin B between two sqrt prices for `liquidity`.
+pub fn get_delta_b(sqrt_price_0: u128, sqrt_price_1: u128, liquidity: u128, round_up: bool) -> Result<u128, Error> {
+    let sqrt_price_diff = sqrt_price_0.abs_diff(sqrt_price_1);
+    if sqrt_price_diff == 0 || liquidity == 0 {
+        return Ok(0);
+    }
+    let product = full_math_u128::full_mul(liquidity, sqrt_price_diff);
+    to_u128(div_round(product, BigUint::from(1u128 << 64), round_up))
+}
+
+/// Sqrt price after `amount` of the input coin is added at `sqrt_price`.
+pub fn get_next_sqrt_price_from_input(sqrt_price: u128, liquidity: u128, amount: u128, a2b: bool) -> Result<u128, Error> {
+    if amount == 0 {
+        return Ok(sqrt_price);
+    }
+    if a2b {
+        // coin A in moves the price down, rounded up so the pool never gives away too much
+        let numerator = full_math_u128::full_mul(liquidity, sqrt_price) << 64;
+        let denominator = (BigUint::from(liquidity) << 64) + full_math_u128::full_mul(amount, sqrt_price);
+        to_u128(div_round(numerator, denominator, true))
+    } else {
+        let delta = div_round(BigUint::from(amount) << 64, BigUint::from(liquidity), false);
+        to_u128(BigUint::from(sqrt_price) + delta)
+    }
+}
+
+fn compute_swap_step(
+    current_sqrt_price: u128,
+    target_sqrt_price: u128,
+    liquidity: u128,
+    amount_remaining: u128,
+    fee_rate: u64,
+    a2b: bool,
+) -> Result<SwapStepResult, Error> {
+    if liquidity == 0 {
+        return Ok(SwapStepResult {
+            amount_in: 0,
+            amount_out: 0,
+            fee_amount: 0,
+            next_sqrt_price: target_sqrt_price,
+        });
+    }
+    let fee_rate = fee_rate as u128;
+    let denominator = FEE_RATE_DENOMINATOR as u128;
+    let amount_remaining_less_fee = full_math_u128::mul_div_floor(amount_remaining, denominator - fee_rate, denominator);
+    let max_amount_in = if a2b {
+        get_delta_a(target_sqrt_price, current_sqrt_price, liquidity, true)?
+    } else {
+        get_delta_b(current_sqrt_price, target_sqrt_price, liquidity, true)?
+    };
+
+    let (amount_in, fee_amount, next_sqrt_price) = if max_amount_in > amount_remaining_less_fee {
+        let next_sqrt_price = get_next_sqrt_price_from_input(current_sqrt_price, liquidity, amount_remaining_less_fee, a2b)?;
+        (amount_remaining_less_fee, amount_remaining - amount_remaining_less_fee, next_sqrt_price)
+    } else {
+        let fee_amount = full_math_u128::mul_div_ceil(max_amount_in, fee_rate, denominator - fee_rate);
+        (max_amount_in, fee_amount, target_sqrt_price)
+    };
+    let amount_out = if a2b {
+        get_delta_b(next_sqrt_price, current_sqrt_price, liquidity, false)?
+    } else {
+        get_delta_a(current_sqrt_price, next_sqrt_price, liquidity, false)?
+    };
+
+    Ok(SwapStepResult {
+        amount_in,
+        amount_out,
+        fee_amount,
+        next_sqrt_price,
+    })
+}
+
+/// Simulate an exact-input swap of `amount_in` from a pool at `sqrt_price` with active
+/// `liquidity`, crossing the initialized `ticks` the same way `pool::swap_in_pool` does
+/// on-chain. Past the last tick the swap runs on towards the price limit.
+pub fn simulate_swap(
+    sqrt_price: u128,
+    liquidity: u128,
+    fee_rate: u64,
+    ticks: &PoolTicks,
+    a2b: bool,
+    amount_in: u64,
+) -> Result<SwapResult, Error> {
+    let mut sqrt_price = sqrt_price;
+    let mut liquidity = liquidity;
+    let mut tick_index = ticks.current_tick_index;
+
+    // a2b walks down from the tick at or below the current one, b2a walks up from the next one
+    let start_tick_index = tick_index;
+    let mut next_ticks: Box<dyn Iterator<Item = &SwapTick>> = if a2b {
+        Box::new(ticks.ticks.iter().rev().filter(move |tick| tick.index <= start_tick_index))
+    } else {
+        Box::new(ticks.ticks.iter().filter(move |tick| tick.index > start_tick_index))
+    };
+    let price_limit = if a2b { MIN_SQRT_PRICE_X64 } else { MAX_SQRT_PRICE_X64 };
+
+    let mut amount_remaining = amount_in as u128;
+    let mut amount_out = 0u128;
+    let mut fee_amount = 0u128;
+    while amount_remaining > 0 && sqrt_price != price_limit {
+        let next_tick = next_ticks.next();
+        let target_sqrt_price = match next_tick {
+            Some(tick) if a2b => tick.sqrt_price.max(price_limit),
+            Some(tick) => tick.sqrt_price.min(price_limit),
+            None => price_limit,
+        };
+        let step = compute_swap_step(sqrt_price, target_sqrt_price, liquidity, amount_remaining, fee_rate, a2b)?;
+        amount_remaining = amount_remaining.saturating_sub(step.amount_in + step.fee_amount);
+        amount_out += step.amount_out;
+        fee_amount += step.fee_amount;
+
+        match next_tick {
+            Some(tick) if step.next_sqrt_price == tick.sqrt_price => {
+                // crossing the tick, liquidity_net is what enters the range going up
+                let liquidity_after = if a2b {
+                    liquidity as i128 - tick.liquidity_net
+                } else {
+                    liquidity as i128 + tick.liquidity_net
+                };
+                liquidity = u128::try_from(liquidity_after)
+                    .map_err(|_| Error::new(ErrorKind::Other, "Liquidity underflows crossing a tick"))?;
+                tick_index = if a2b { tick.index - 1 } else { tick.index };
+            }
+            _ => tick_index = tick_math::get_tick_at_sqrt_price(step.next_sqrt_price),
+        }
+        sqrt_price = step.next_sqrt_price;
+    }
+
+    let to_u64 = |value: u128| u64::try_from(value).map_err(|_| Error::new(ErrorKind::Other, "Value overflows u64"));
+    Ok(SwapResult {
+        amount_in: amount_in - amount_remaining as u64,
+        amount_out: to_u64(amount_out)?,
+        fee_amount: to_u64(fee_amount)?,
+        after_sqrt_price: sqrt_price,
+        after_tick_index: tick_index,
+        is_exceed: amount_remaining > 0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const Q64: u128 = 1 << 64;
+
+    #[test]
+    fn test_get_delta_b() {
+        // price 1 -> 4 (sqrt 1 -> 2) with liquidity 1000 needs 1000 of coin B
+        assert_eq!(get_delta_b(Q64, 2 * Q64, 1_000, false).unwrap(), 1_000);
+        assert_eq!(get_delta_b(2 * Q64, Q64, 1_000, true).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn test_get_delta_a() {
+        // L * (1/sqrt_p0 - 1/sqrt_p1) = 1000 * (1 - 1/2)
+        assert_eq!(get_delta_a(Q64, 2 * Q64, 1_000, false).unwrap(), 500);
+    }
+
+    #[test]
+    fn test_get_next_sqrt_price_from_input() {
+        assert_eq!(get_next_sqrt_price_from_input(Q64, 1_000, 1_000, false).unwrap(), 2 * Q64);
+        assert_eq!(get_next_sqrt_price_from_input(2 * Q64, 1_000, 500, true).unwrap(), Q64);
+    }
+
+    #[test]
+    fn test_compute_swap_step_within_range() {
+        // no fee, the whole input fits before the target price
+        let step = compute_swap_step(Q64, 4 * Q64, 1_000, 1_000, 0, false).unwrap();
+        assert_eq!(step.amount_in, 1_000);
+        assert_eq!(step.next_sqrt_price, 2 * Q64);
+        assert_eq!(step.amount_out, 500);
+        assert_eq!(step.fee_amount, 0);
+    }
+
+    #[test]
+    fn test_compute_swap_step_reaches_target() {
+        // 0.3% fee, the step stops at the target and charges the fee on what it used
+        let step = compute_swap_step(Q64, 2 * Q64, 1_000, 10_000, 3_000, false).unwrap();
+        assert_eq!(step.next_sqrt_price, 2 * Q64);
+        assert_eq!(step.amount_in, 1_000);
+        assert_eq!(step.fee_amount, 4);
+        assert_eq!(step.amount_out, 500);
+    }
+
+    // a position over [Q64, 2 * Q64] with liquidity 500 on top of one over the whole range
+    fn two_range_ticks(current_tick_index: i32) -> PoolTicks {
+        PoolTicks {
+            current_tick_index,
+            ticks: vec![
+                SwapTick { index: 0, sqrt_price: Q64, liquidity_net: 500 },
+                SwapTick { index: 13_863, sqrt_price: 2 * Q64, liquidity_net: -500 },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_crosses_tick_b2a() {
+        // 1000 of B reach 2 * Q64 with liquidity 1000, the rest runs on with 500
+        let result = simulate_swap(Q64, 1_000, 0, &two_range_ticks(0), false, 2_000).unwrap();
+        assert_eq!(result.amount_in, 2_000);
+        assert_eq!(result.amount_out, 500 + 125);
+        assert_eq!(result.after_sqrt_price, 4 * Q64);
+        assert_eq!(result.after_tick_index, tick_math::get_tick_at_sqrt_price(4 * Q64));
+        assert!(!result.is_exceed);
+    }
+
+    #[test]
+    fn test_simulate_swap_crosses_tick_a2b() {
+        // from 4 * Q64 down: 125 of A with liquidity 500, crossing into 1000 for 500 more,
+        // then crossing back out to 500 for the last 375
+        let result = simulate_swap(4 * Q64, 500, 0, &two_range_ticks(27_726), true, 1_000).unwrap();
+        assert_eq!(result.amount_in, 1_000);
+        assert_eq!(result.amount_out, 1_000 + 1_000 + 214);
+        assert_eq!(result.after_tick_index, tick_math::get_tick_at_sqrt_price(result.after_sqrt_price));
+        assert!(!result.is_exceed);
+
+        // the single-range estimate misprices the same swap
+        let single = simulate_swap(4 * Q64, 500, 0, &PoolTicks::default(), true, 1_000).unwrap();
+        assert!(single.amount_out < result.amount_out);
+    }
+
+    #[test]
+    fn test_simulate_swap_stops_without_liquidity() {
+        // only a position over [Q64, 4 * Q64]: below its lower tick there is nothing to swap against
+        let ticks = PoolTicks {
+            current_tick_index: 13_863,
+            ticks: vec![
+                SwapTick { index: 0, sqrt_price: Q64, liquidity_net: 1_000 },
+                SwapTick { index: 27_726, sqrt_price: 4 * Q64, liquidity_net: -1_000 },
+            ],
+        };
+        let result = simulate_swap(2 * Q64, 1_000, 0, &ticks, true, 1_000).unwrap();
+        assert_eq!(result.amount_in, 500);
+        assert_eq!(result.amount_out, 1_000);
+        assert_eq!(result.after_sqrt_price, MIN_SQRT_PRICE_X64);
+        assert!(result.is_exceed);
+    }
+}
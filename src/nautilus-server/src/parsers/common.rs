@@ -89,6 +89,63 @@ pub fn extract_nested_id_from_fields(fields: &BTreeMap<String, Value>, key: &str
     }
 }
 
+pub fn extract_struct_fields(value: &Value) -> Result<&BTreeMap<String, Value>, anyhow::Error> {
+    let struct_value = match &value.kind {
+        Some(prost_types::value::Kind::StructValue(s)) => s,
+        _ => return Err(anyhow::anyhow!("Expected StructValue, got {:?}", value.kind)),
+    };
+
+    // Check if there's a "fields" wrapper
+    match struct_value.fields.get("fields") {
+        Some(fields_value) => match &fields_value.kind {
+            Some(prost_types::value::Kind::StructValue(s)) => Ok(&s.fields),
+            _ => Err(anyhow::anyhow!("Expected StructValue for 'fields', got {:?}", fields_value.kind)),
+        },
+        None => Ok(&struct_value.fields),
+    }
+}
+
+pub fn extract_nested_fields<'a>(fields: &'a BTreeMap<String, Value>, key: &str) -> Result<&'a BTreeMap<String, Value>, anyhow::Error> {
+    let value = fields.get(key)
+        .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", key))?;
+    extract_struct_fields(value)
+}
+
+pub fn extract_list_from_fields<'a>(fields: &'a BTreeMap<String, Value>, key: &str) -> Result<&'a Vec<Value>, anyhow::Error> {
+    let value = fields.get(key)
+        .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", key))?;
+
+    match &value.kind {
+        Some(prost_types::value::Kind::ListValue(list)) => Ok(&list.values),
+        _ => Err(anyhow::anyhow!("Expected ListValue for field '{}', got {:?}", key, value.kind)),
+    }
+}
+
+/// Same value as serde json, for the parsers that read GraphQL json.
+pub fn to_json_value(value: &Value) -> JsonValue {
+    match &value.kind {
+        Some(prost_types::value::Kind::NullValue(_)) | None => JsonValue::Null,
+        Some(prost_types::value::Kind::BoolValue(b)) => JsonValue::Bool(*b),
+        // protobuf numbers are all doubles, keep the whole ones integers
+        Some(prost_types::value::Kind::NumberValue(n)) if n.fract() == 0.0 && *n >= 0.0 && *n < u64::MAX as f64 => {
+            JsonValue::from(*n as u64)
+        }
+        Some(prost_types::value::Kind::NumberValue(n)) => serde_json::Number::from_f64(*n)
+            .map(JsonValue::Number)
+            .unwrap_or(JsonValue::Null),
+        Some(prost_types::value::Kind::StringValue(s)) => JsonValue::String(s.clone()),
+        Some(prost_types::value::Kind::ListValue(list)) => {
+            JsonValue::Array(list.values.iter().map(to_json_value).collect())
+        }
+        Some(prost_types::value::Kind::StructValue(s)) => JsonValue::Object(
+            s.fields
+                .iter()
+                .map(|(key, value)| (key.clone(), to_json_value(value)))
+                .collect(),
+        ),
+    }
+}
+
 // Helper functions for serde_json::Value
 pub fn extract_string_from_json_fields(fields: &Map<String, JsonValue>, key: &str) -> Result<String, anyhow::Error> {
     let value = fields.get(key)
@@ -105,7 +162,9 @@ pub fn extract_number_from_json_fields(fields: &Map<String, JsonValue>, key: &st
         .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", key))?;
     
     match value {
-        JsonValue::Number(n) => Ok(n.as_u64().unwrap_or(0)),
+        JsonValue::Number(n) => n
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Field '{}' is not a u64: {}", key, n)),
         JsonValue::String(s) => {
             s.parse::<u64>().map_err(|e| anyhow::anyhow!("Failed to parse string '{}' as u64: {}", s, e))
         },
@@ -113,6 +172,22 @@ pub fn extract_number_from_json_fields(fields: &Map<String, JsonValue>, key: &st
     }
 }
 
+pub fn extract_u128_from_json_fields(fields: &Map<String, JsonValue>, key: &str) -> Result<u128, anyhow::Error> {
+    let value = fields.get(key)
+        .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", key))?;
+    
+    match value {
+        JsonValue::Number(n) => {
+            // as_u64 would reject numbers past u64::MAX, the decimal form keeps them
+            n.to_string().parse::<u128>().map_err(|e| anyhow::anyhow!("Field '{}' is not a u128: {}: {}", key, n, e))
+        },
+        JsonValue::String(s) => {
+            s.parse::<u128>().map_err(|e| anyhow::anyhow!("Failed to parse string '{}' as u128: {}", s, e))
+        },
+        _ => Err(anyhow::anyhow!("Expected Number or String for field '{}', got {:?}", key, value)),
+    }
+}
+
 pub fn extract_nested_string_from_json_fields(fields: &Map<String, JsonValue>, parent_key: &str, child_key: &str) -> Result<String, anyhow::Error> {
     let parent_value = fields.get(parent_key)
         .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", parent_key))?;
@@ -148,4 +223,38 @@ pub fn extract_nested_id_from_json_fields(fields: &Map<String, JsonValue>, key:
         },
         _ => Err(anyhow::anyhow!("Expected String or Object for field '{}', got {:?}", key, value)),
     }
-}
\ No newline at end of file
+}
+/// Read `item_key` from every object of the list stored under `list_key`.
+pub fn extract_u64_list_from_json_fields(fields: &Map<String, JsonValue>, list_key: &str, item_key: &str) -> Result<Vec<u64>, anyhow::Error> {
+    let value = fields.get(list_key)
+        .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", list_key))?;
+
+    let items = match value {
+        JsonValue::Array(items) => items,
+        _ => return Err(anyhow::anyhow!("Expected Array for field '{}', got {:?}", list_key, value)),
+    };
+
+    items.iter()
+        .map(|item| match item {
+            JsonValue::Object(obj) => extract_number_from_json_fields(obj, item_key),
+            _ => Err(anyhow::anyhow!("Expected Object in '{}', got {:?}", list_key, item)),
+        })
+        .collect()
+}
+
+pub fn extract_u128_list_from_json_fields(fields: &Map<String, JsonValue>, list_key: &str, item_key: &str) -> Result<Vec<u128>, anyhow::Error> {
+    let value = fields.get(list_key)
+        .ok_or_else(|| anyhow::anyhow!("Field '{}' not found", list_key))?;
+
+    let items = match value {
+        JsonValue::Array(items) => items,
+        _ => return Err(anyhow::anyhow!("Expected Array for field '{}', got {:?}", list_key, value)),
+    };
+
+    items.iter()
+        .map(|item| match item {
+            JsonValue::Object(obj) => extract_u128_from_json_fields(obj, item_key),
+            _ => Err(anyhow::anyhow!("Expected Object in '{}', got {:?}", list_key, item)),
+        })
+        .collect()
+}
@@ -2,10 +2,10 @@ use sui_graphql_client::Client as GraphQLClient;
 use sui_rpc::proto::sui::rpc::v2beta2::Object;
 use sui_sdk_types::Address;
 use sui_sdk_types::TypeTag;
-use sui_sdk_types::StructTag;
-use std::str::FromStr;
-use crate::aggregator::cetus;
-use crate::transactions_builder::constant::REGISTRY_BAG_OBJECT_ID;
+use crate::aggregator;
+use crate::config::AppConfig;
+use crate::dex;
+use crate::EnclaveError;
 
 pub mod common;
 pub mod pools;
@@ -16,7 +16,7 @@ pub mod types;
 pub use types::{SupportedDex, *};
 
 /// Helper function để format coin type string với prefix 0x nếu cần thiết
-fn format_coin_type(coin_type: &str) -> String {
+pub fn format_coin_type(coin_type: &str) -> String {
     if coin_type.starts_with("0x") {
         coin_type.to_string()
     } else {
@@ -32,11 +32,17 @@ fn format_coin_type(coin_type: &str) -> String {
 
 pub async fn into_processed_pool_data<'a>(
     graphql_client: &'a mut GraphQLClient,
+    config: &AppConfig,
     pool_object: Object,
     strategy_object: Object,
+    now_ms: u64,
 ) -> Result<ProcessedPoolData, anyhow::Error> {
-    let pool_json = pool_object.json.unwrap();
-    let strategy_json = strategy_object.json.unwrap();
+    let pool_json = pool_object
+        .json
+        .ok_or_else(|| EnclaveError::ParseMismatch("Pool object has no json".to_string()))?;
+    let strategy_json = strategy_object
+        .json
+        .ok_or_else(|| EnclaveError::ParseMismatch("Strategy object has no json".to_string()))?;
 
     let pool_data = pools::try_match(pool_json)?;
     let strategy_data = strategies::try_match(strategy_json)?;
@@ -46,62 +52,102 @@ pub async fn into_processed_pool_data<'a>(
         _ => return Err(anyhow::anyhow!("Unknown strategy type")),
     };
     
-    // filter out
-    let position_field = graphql_client
-        .dynamic_field(
-            Address::from_hex(REGISTRY_BAG_OBJECT_ID)
-                .unwrap(),
-            TypeTag::U64,
-            position_registry_id,
-        )
-        .await?
-        .unwrap()
-        .value_as_json
-        .unwrap();
-
-    let position_json = serde_json::to_value(&position_field)?;
-    let position_data = positions::try_match(Box::new(position_json.clone()))?; 
+    let position_json =
+        fetch_registry_position(graphql_client, config, position_registry_id).await?;
+    let adapter = dex::get_adapter(pool_data.dex())?;
+    let position_data = adapter.parse_position(&Box::new(position_json.clone()))?;
     let balances_bag = positions::map_position_balances_data(&Box::new(position_json.clone()))?;
 
-    let (current_tick, current_sqrt_price, tick_spacing, dex, rewarder_coin_types) =
-        match pool_data {
-            Pool::Cetus(cetus_pool) => (
-                cetus_pool.current_tick_index.bits.parse::<u32>()?,
-                cetus_pool.current_sqrt_price.parse::<u128>()?,
-                cetus_pool.tick_spacing as u32,
-                SupportedDex::Cetus,
-                cetus_pool.rewarder_manager.rewarders.iter().map(|rewarder| TypeTag::Struct(Box::new(StructTag::from_str(&format!("0x{}", rewarder.reward_coin.name)).unwrap()))).collect::<Vec<_>>(),
-            ),
-        };
-
-    let (tick_lower, tick_upper, coin_a_type, coin_b_type, position_liquidity) = match position_data {
-        Position::Cetus(cetus_position) => {
-            println!("cetus_position: {:?}", cetus_position);
-            let formatted_coin_a = format_coin_type(&cetus_position.coin_type_a.name);
-            let formatted_coin_b = format_coin_type(&cetus_position.coin_type_b.name);
-            (
-                cetus_position.tick_lower_index.bits.parse::<u32>()?,
-                cetus_position.tick_upper_index.bits.parse::<u32>()?,
-                TypeTag::Struct(Box::new(StructTag::from_str(&formatted_coin_a)?)),
-                TypeTag::Struct(Box::new(StructTag::from_str(&formatted_coin_b)?)),
-                cetus_position.liquidity,
-            )
-        },
-    };
+    let PoolSummary {
+        current_tick_u32: current_tick,
+        current_sqrt_price,
+        tick_spacing,
+        fee_rate,
+        liquidity: pool_liquidity,
+        rewarder_coin_types,
+    } = adapter.pool_summary(&pool_data)?;
+    let dex = adapter.dex();
+
+    let PositionSummary {
+        tick_lower_index_u32: tick_lower,
+        tick_upper_index_u32: tick_upper,
+        coin_a_type,
+        coin_b_type,
+        liquidity: position_liquidity,
+    } = adapter.position_summary(&position_data)?;
 
-    // Create request based on strategy type and pool type
+    let strategy_id = Address::from_hex(strategy_object.object_id.unwrap_or_default())?;
+
+    // Create request based on strategy type and pool type:
+    // an out of range position is rebalanced once the strategy guardrails allow it,
+    // otherwise its rewards are compounded once they are worth at least the strategy
+    // threshold
+    let mut new_tick_range = None;
+    let mut pending_rewards = PendingRewards::default();
     let request = match &strategy_data {
-        Strategy::AutoRebalance(_auto_rebalance) => Request::Rebalance(RebalanceRequest {
-            strategy_id: Address::from_hex(strategy_object.object_id.unwrap_or_default()).unwrap(),
-            current_tick_u32: current_tick as u32,
-            current_sqrt_price: current_sqrt_price,
-            tick_spacing: tick_spacing,
-            tick_lower_index_u32: tick_lower as u32,
-            tick_upper_index_u32: tick_upper as u32,
-        }),
+        Strategy::AutoRebalance(auto_rebalance) => {
+            let band = strategies::auto_rebalance::acceptable_sqrt_price_band(
+                tick_lower as i32,
+                tick_upper as i32,
+                auto_rebalance.lower_sqrt_price_change_threshold_bps,
+                auto_rebalance.upper_sqrt_price_change_threshold_bps,
+                auto_rebalance.lower_sqrt_price_change_threshold_direction,
+                auto_rebalance.upper_sqrt_price_change_threshold_direction,
+            );
+            if strategies::auto_rebalance::is_out_of_range(current_sqrt_price, band) {
+                strategies::auto_rebalance::validate_rebalance(auto_rebalance, now_ms)?;
+                new_tick_range = Some(strategies::auto_rebalance::rebalance_tick_range(
+                    auto_rebalance,
+                    current_sqrt_price,
+                    current_tick,
+                    tick_lower as i32,
+                    tick_upper as i32,
+                    tick_spacing,
+                )?);
+                Request::Rebalance(RebalanceRequest {
+                    strategy_id,
+                    current_tick_u32: current_tick as u32,
+                    current_sqrt_price: current_sqrt_price,
+                    tick_spacing: tick_spacing,
+                    tick_lower_index_u32: tick_lower as u32,
+                    tick_upper_index_u32: tick_upper as u32,
+                })
+            } else {
+                let Some(compound_min_value) = auto_rebalance.compound_min_value else {
+                    return Err(EnclaveError::StrategyNotEligible(
+                        "Position is in range and compounding is disabled".to_string(),
+                    )
+                    .into());
+                };
+                // only a compound needs them, fetching costs a position and two tick reads
+                pending_rewards = adapter
+                    .pending_rewards(graphql_client, &pool_data, &position_data)
+                    .await?;
+                let pending_rewards_value =
+                    pending_rewards_value(&pending_rewards, &coin_a_type, &coin_b_type).await?;
+                if pending_rewards_value < compound_min_value {
+                    return Err(EnclaveError::StrategyNotEligible(format!(
+                        "Position is in range and pending rewards value {} is below the compound threshold {}",
+                        pending_rewards_value,
+                        compound_min_value
+                    ))
+                    .into());
+                }
+                Request::Compound(CompoundRequest {
+                    strategy_id,
+                    current_sqrt_price,
+                    tick_lower_index_u32: tick_lower as u32,
+                    tick_upper_index_u32: tick_upper as u32,
+                    pending_rewards_value,
+                })
+            }
+        }
         _ => return Err(anyhow::anyhow!("Unknown strategy type")),
     };
 
+    // both a rebalance and a compound zap coin A in, the swap is priced across these
+    let pool_ticks = adapter.pool_ticks(graphql_client, &pool_data).await?;
+
     let auto_rebalance_strategy = match &strategy_data {
         Strategy::AutoRebalance(auto_rebalance) => Some(auto_rebalance.clone()),
         _ => None,
@@ -117,5 +163,67 @@ pub async fn into_processed_pool_data<'a>(
         position_liquidity,
         balances_bag,
         rewarder_coin_types,
+        fee_rate,
+        pool_liquidity,
+        pool_ticks,
+        pending_rewards,
+        new_tick_range,
     })
 }
+
+/// Json of the position stored under `position_registry_id` in the registry bag.
+pub async fn fetch_registry_position(
+    graphql_client: &GraphQLClient,
+    config: &AppConfig,
+    position_registry_id: u64,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let position_field = graphql_client
+        .dynamic_field(
+            Address::from_hex(&config.kurage.registry_bag_object_id)?,
+            TypeTag::U64,
+            position_registry_id,
+        )
+        .await
+        .map_err(|e| EnclaveError::UpstreamRpc(e.to_string()))?
+        .ok_or_else(|| {
+            EnclaveError::ObjectNotFound(format!("Position {} in the registry", position_registry_id))
+        })?
+        .value_as_json
+        .ok_or_else(|| {
+            EnclaveError::ParseMismatch(format!("Position {} has no json", position_registry_id))
+        })?;
+    Ok(serde_json::to_value(&position_field)?)
+}
+
+/// Value of the pending fees and rewards in coin A, priced with the aggregator mark price.
+pub async fn pending_rewards_value(
+    pending_rewards: &PendingRewards,
+    coin_a_type: &TypeTag,
+    coin_b_type: &TypeTag,
+) -> Result<u64, anyhow::Error> {
+    let cetus_aggregator = aggregator::cetus::CetusAggregator::new();
+    let mut amounts = vec![(coin_b_type.clone(), pending_rewards.fee_b)];
+    amounts.extend(
+        pending_rewards
+            .rewards
+            .iter()
+            .map(|reward| (reward.coin_type.clone(), reward.amount)),
+    );
+
+    let mut value = pending_rewards.fee_a as u128;
+    for (coin_type, amount) in amounts {
+        if amount == 0 {
+            continue;
+        }
+        if &coin_type == coin_a_type {
+            value += amount as u128;
+            continue;
+        }
+        // mark price is coin A out per coin in, scaled by 1e9
+        let price = cetus_aggregator
+            .get_mark_price(coin_type.to_string(), coin_a_type.to_string(), amount)
+            .await?;
+        value += amount as u128 * price / 1000000000u128;
+    }
+    Ok(value.min(u64::MAX as u128) as u64)
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use prost_types::Value;
+use crate::parsers::common::*;
+use crate::parsers::types::*;
+
+pub fn map_pool_data(value: &Box<Value>) -> Result<BluefinPoolData, anyhow::Error> {
+    // Extract struct fields from prost Value
+    let fields = extract_struct_fields(value)?;
+    let ticks_manager_fields = extract_nested_fields(fields, "ticks_manager")?;
+
+    let pool_data = BluefinPoolData {
+        id: extract_nested_id_from_fields(fields, "id")?,
+        name: extract_string_from_fields(fields, "name")?,
+        fee_rate: extract_string_or_number_from_fields(fields, "fee_rate")?,
+        current_sqrt_price: extract_string_or_number_from_fields(fields, "current_sqrt_price")?,
+        current_tick_index: I32Wrapper {
+            bits: extract_nested_string_from_fields(fields, "current_tick_index", "bits")?,
+        },
+        liquidity: extract_string_or_number_from_fields(fields, "liquidity")?,
+        tick_spacing: extract_number_from_fields(ticks_manager_fields, "tick_spacing")?,
+        fee_growth_global_coin_a: extract_string_or_number_from_fields(fields, "fee_growth_global_coin_a")?,
+        fee_growth_global_coin_b: extract_string_or_number_from_fields(fields, "fee_growth_global_coin_b")?,
+        reward_infos: parse_reward_infos_from_fields(fields)?,
+        is_paused: extract_bool_from_fields(fields, "is_paused")?,
+    };
+
+    Ok(pool_data)
+}
+
+fn parse_reward_infos_from_fields(fields: &BTreeMap<String, Value>) -> Result<Vec<BluefinPoolRewardInfo>, anyhow::Error> {
+    let mut reward_infos = Vec::new();
+    for reward_info_value in extract_list_from_fields(fields, "reward_infos")? {
+        let reward_info_fields = extract_struct_fields(reward_info_value)?;
+
+        reward_infos.push(BluefinPoolRewardInfo {
+            reward_coin_type: extract_string_from_fields(reward_info_fields, "reward_coin_type")?,
+            reward_per_seconds: extract_string_or_number_from_fields(reward_info_fields, "reward_per_seconds")?,
+            reward_growth_global: extract_string_or_number_from_fields(reward_info_fields, "reward_growth_global")?,
+            last_update_time: extract_string_or_number_from_fields(reward_info_fields, "last_update_time")?,
+            ended_at_seconds: extract_string_or_number_from_fields(reward_info_fields, "ended_at_seconds")?,
+        });
+    }
+
+    Ok(reward_infos)
+}
@@ -1,10 +1,11 @@
 use std::collections::BTreeMap;
 use prost_types::Value;
-use sui_sdk_types::StructTag;
+use sui_graphql_client::Client as GraphQLClient;
+use sui_sdk_types::Address;
 use sui_sdk_types::TypeTag;
 use crate::parsers::common::*;
 use crate::parsers::types::*;
-use std::str::FromStr;
+use crate::transactions_builder::helper;
 
 pub fn map_pool_data(value: &Box<Value>) -> Result<CetusPoolData, anyhow::Error> {
     // Extract struct fields from prost Value
@@ -38,7 +39,7 @@ pub fn map_pool_data(value: &Box<Value>) -> Result<CetusPoolData, anyhow::Error>
         url: extract_string_from_fields(fields, "url")?,
     };
 
-    println!("pool_data: {:?}", pool_data.rewarder_manager.rewarders.iter().map(|rewarder| TypeTag::Struct(Box::new(StructTag::from_str(&format!("0x{}", rewarder.reward_coin.name)).unwrap()))).collect::<Vec<_>>());
+    println!("pool_data: {:?}", pool_data.rewarder_manager.rewarders.iter().map(|rewarder| &rewarder.reward_coin.name).collect::<Vec<_>>());
     
     Ok(pool_data)
 }
@@ -293,4 +294,81 @@ fn parse_tick_manager_from_fields(fields: &BTreeMap<String, Value>) -> Result<Ti
             },
         },
     })
-}
\ No newline at end of file
+}
+// Skip list scores are the tick index shifted by the max tick so they are never negative.
+const TICK_SCORE_OFFSET: i64 = 443636;
+
+pub fn tick_score(tick_index: i32) -> u64 {
+    (tick_index as i64 + TICK_SCORE_OFFSET) as u64
+}
+
+/// Parse a skip list node of `tick_manager.ticks`; the tick itself lives under `value`.
+pub fn map_tick_data(value: &Box<serde_json::Value>) -> Result<CetusTick, anyhow::Error> {
+    let fields = match value.get("value") {
+        Some(serde_json::Value::Object(s)) => s,
+        _ => return Err(anyhow::anyhow!("Expected Object for 'value', got {:?}", value.get("value"))),
+    };
+
+    let rewards_growth_outside = match fields.get("rewards_growth_outside") {
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                serde_json::Value::String(s) => Ok(s.clone()),
+                serde_json::Value::Number(n) => Ok(n.to_string()),
+                _ => Err(anyhow::anyhow!("Expected String in 'rewards_growth_outside', got {:?}", item)),
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?,
+        other => return Err(anyhow::anyhow!("Expected Array for 'rewards_growth_outside', got {:?}", other)),
+    };
+
+    Ok(CetusTick {
+        index: TickIndex {
+            bits: extract_nested_string_from_json_fields(fields, "index", "bits")?,
+        },
+        sqrt_price: extract_u128_from_json_fields(fields, "sqrt_price")?.to_string(),
+        liquidity_net: I128Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "liquidity_net", "bits")?,
+        },
+        liquidity_gross: extract_u128_from_json_fields(fields, "liquidity_gross")?.to_string(),
+        fee_growth_outside_a: extract_u128_from_json_fields(fields, "fee_growth_outside_a")?.to_string(),
+        fee_growth_outside_b: extract_u128_from_json_fields(fields, "fee_growth_outside_b")?.to_string(),
+        rewards_growth_outside,
+    })
+}
+
+/// Fetch a tick of `pool` by index. Returns `None` when the tick is not initialized.
+pub async fn fetch_tick(
+    client: &GraphQLClient,
+    pool: &CetusPoolData,
+    tick_index: i32,
+) -> Result<Option<CetusTick>, anyhow::Error> {
+    let tick_field = client
+        .dynamic_field(
+            Address::from_hex(&pool.tick_manager.ticks.id)?,
+            TypeTag::U64,
+            tick_score(tick_index),
+        )
+        .await?;
+    let Some(tick_field) = tick_field else {
+        return Ok(None);
+    };
+    let tick_json = tick_field
+        .value_as_json
+        .ok_or_else(|| anyhow::anyhow!("Tick {} of pool {} has no json", tick_index, pool.id))?;
+    let tick_json = serde_json::to_value(&tick_json)?;
+    Ok(Some(map_tick_data(&Box::new(tick_json))?))
+}
+
+/// Fetch every initialized tick of `pool` from the tick skip list, in no particular order.
+pub async fn fetch_ticks(client: &GraphQLClient, pool: &CetusPoolData) -> Result<Vec<CetusTick>, anyhow::Error> {
+    let tick_fields = helper::get_dynamic_fields(client, Address::from_hex(&pool.tick_manager.ticks.id)?).await?;
+    tick_fields
+        .into_iter()
+        .map(|tick_field| {
+            let tick_json = tick_field
+                .value_as_json
+                .ok_or_else(|| anyhow::anyhow!("Tick of pool {} has no json", pool.id))?;
+            map_tick_data(&Box::new(serde_json::to_value(&tick_json)?))
+        })
+        .collect()
+}
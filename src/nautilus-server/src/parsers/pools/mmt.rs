@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+use prost_types::Value;
+use crate::parsers::common::*;
+use crate::parsers::types::*;
+
+pub fn map_pool_data(value: &Box<Value>) -> Result<MmtPoolData, anyhow::Error> {
+    // Extract struct fields from prost Value
+    let fields = extract_struct_fields(value)?;
+
+    let pool_data = MmtPoolData {
+        id: extract_nested_id_from_fields(fields, "id")?,
+        type_x: TypeName {
+            name: extract_string_from_fields(extract_nested_fields(fields, "type_x")?, "name")?,
+        },
+        type_y: TypeName {
+            name: extract_string_from_fields(extract_nested_fields(fields, "type_y")?, "name")?,
+        },
+        sqrt_price: extract_string_or_number_from_fields(fields, "sqrt_price")?,
+        liquidity: extract_string_or_number_from_fields(fields, "liquidity")?,
+        tick_index: I32Wrapper {
+            bits: extract_nested_string_from_fields(fields, "tick_index", "bits")?,
+        },
+        tick_spacing: extract_number_from_fields(fields, "tick_spacing")?,
+        swap_fee_rate: extract_string_or_number_from_fields(fields, "swap_fee_rate")?,
+        fee_growth_global_x: extract_string_or_number_from_fields(fields, "fee_growth_global_x")?,
+        fee_growth_global_y: extract_string_or_number_from_fields(fields, "fee_growth_global_y")?,
+        reward_infos: parse_reward_infos_from_fields(fields)?,
+    };
+
+    Ok(pool_data)
+}
+
+fn parse_reward_infos_from_fields(fields: &BTreeMap<String, Value>) -> Result<Vec<MmtPoolRewardInfo>, anyhow::Error> {
+    let mut reward_infos = Vec::new();
+    for reward_info_value in extract_list_from_fields(fields, "reward_infos")? {
+        let reward_info_fields = extract_struct_fields(reward_info_value)?;
+        let reward_coin_type_fields = extract_nested_fields(reward_info_fields, "reward_coin_type")?;
+
+        reward_infos.push(MmtPoolRewardInfo {
+            reward_coin_type: TypeName {
+                name: extract_string_from_fields(reward_coin_type_fields, "name")?,
+            },
+            reward_per_seconds: extract_string_or_number_from_fields(reward_info_fields, "reward_per_seconds")?,
+            reward_growth_global: extract_string_or_number_from_fields(reward_info_fields, "reward_growth_global")?,
+            last_update_time: extract_string_or_number_from_fields(reward_info_fields, "last_update_time")?,
+            ended_at_seconds: extract_string_or_number_from_fields(reward_info_fields, "ended_at_seconds")?,
+        });
+    }
+
+    Ok(reward_infos)
+}
@@ -0,0 +1,32 @@
+use serde_json::Value;
+use crate::parsers::common::*;
+use crate::parsers::types::*;
+
+pub fn map_position_data(value: &Box<Value>) -> Result<BluefinPosition, anyhow::Error> {
+    // Extract position fields from JSON value
+    let position_value = match value.get("position") {
+        Some(Value::Object(s)) => s,
+        _ => return Err(anyhow::anyhow!("Expected Object for 'position', got {:?}", value.get("position"))),
+    };
+
+    let fields = position_value;
+
+    let position_data = BluefinPosition {
+        id: extract_nested_id_from_json_fields(fields, "id")?,
+        pool_id: extract_string_from_json_fields(fields, "pool_id")?,
+        coin_type_a: extract_string_from_json_fields(fields, "coin_type_a")?,
+        coin_type_b: extract_string_from_json_fields(fields, "coin_type_b")?,
+        lower_tick: I32Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "lower_tick", "bits")?,
+        },
+        upper_tick: I32Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "upper_tick", "bits")?,
+        },
+        liquidity: extract_u128_from_json_fields(fields, "liquidity")?,
+        token_a_fee: extract_number_from_json_fields(fields, "token_a_fee")?,
+        token_b_fee: extract_number_from_json_fields(fields, "token_b_fee")?,
+        coins_owed_reward: extract_u64_list_from_json_fields(fields, "reward_infos", "coins_owed_reward")?,
+    };
+
+    Ok(position_data)
+}
@@ -34,4 +34,26 @@ pub fn map_position_data(value: &Box<Value>) -> Result<CetusPosition, anyhow::Er
     };
     
     Ok(position_data)
-}
\ No newline at end of file
+}
+pub fn map_position_info_data(value: &Box<Value>) -> Result<CetusPositionInfo, anyhow::Error> {
+    // Linked table node, the position info sits under 'value'
+    let position_info_value = match value.get("value") {
+        Some(Value::Object(s)) => s,
+        _ => return Err(anyhow::anyhow!("Expected Object for 'value', got {:?}", value.get("value"))),
+    };
+
+    let fields = position_info_value;
+
+    let position_info_data = CetusPositionInfo {
+        position_id: extract_string_from_json_fields(fields, "position_id")?,
+        liquidity: extract_u128_from_json_fields(fields, "liquidity")?,
+        fee_growth_inside_a: extract_u128_from_json_fields(fields, "fee_growth_inside_a")?,
+        fee_owned_a: extract_number_from_json_fields(fields, "fee_owned_a")?,
+        fee_growth_inside_b: extract_u128_from_json_fields(fields, "fee_growth_inside_b")?,
+        fee_owned_b: extract_number_from_json_fields(fields, "fee_owned_b")?,
+        rewards_growth_inside: extract_u128_list_from_json_fields(fields, "rewards", "growth_inside")?,
+        rewards_amount_owned: extract_u64_list_from_json_fields(fields, "rewards", "amount_owned")?,
+    };
+
+    Ok(position_info_data)
+}
@@ -0,0 +1,37 @@
+use serde_json::Value;
+use crate::parsers::common::*;
+use crate::parsers::types::*;
+
+pub fn map_position_data(value: &Box<Value>) -> Result<FlowXPosition, anyhow::Error> {
+    // Extract position fields from JSON value
+    let position_value = match value.get("position") {
+        Some(Value::Object(s)) => s,
+        _ => return Err(anyhow::anyhow!("Expected Object for 'position', got {:?}", value.get("position"))),
+    };
+
+    let fields = position_value;
+
+    let position_data = FlowXPosition {
+        id: extract_nested_id_from_json_fields(fields, "id")?,
+        pool_id: extract_string_from_json_fields(fields, "pool_id")?,
+        fee_rate: extract_number_from_json_fields(fields, "fee_rate")?,
+        coin_type_x: TypeName {
+            name: extract_nested_string_from_json_fields(fields, "coin_type_x", "name")?,
+        },
+        coin_type_y: TypeName {
+            name: extract_nested_string_from_json_fields(fields, "coin_type_y", "name")?,
+        },
+        tick_lower_index: I32Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "tick_lower_index", "bits")?,
+        },
+        tick_upper_index: I32Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "tick_upper_index", "bits")?,
+        },
+        liquidity: extract_u128_from_json_fields(fields, "liquidity")?,
+        coins_owed_x: extract_number_from_json_fields(fields, "coins_owed_x")?,
+        coins_owed_y: extract_number_from_json_fields(fields, "coins_owed_y")?,
+        coins_owed_reward: extract_u64_list_from_json_fields(fields, "reward_infos", "coins_owed_reward")?,
+    };
+
+    Ok(position_data)
+}
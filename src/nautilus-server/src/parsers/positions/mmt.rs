@@ -0,0 +1,37 @@
+use serde_json::Value;
+use crate::parsers::common::*;
+use crate::parsers::types::*;
+
+pub fn map_position_data(value: &Box<Value>) -> Result<MmtPosition, anyhow::Error> {
+    // Extract position fields from JSON value
+    let position_value = match value.get("position") {
+        Some(Value::Object(s)) => s,
+        _ => return Err(anyhow::anyhow!("Expected Object for 'position', got {:?}", value.get("position"))),
+    };
+
+    let fields = position_value;
+
+    let position_data = MmtPosition {
+        id: extract_nested_id_from_json_fields(fields, "id")?,
+        pool_id: extract_string_from_json_fields(fields, "pool_id")?,
+        fee_rate: extract_number_from_json_fields(fields, "fee_rate")?,
+        type_x: TypeName {
+            name: extract_nested_string_from_json_fields(fields, "type_x", "name")?,
+        },
+        type_y: TypeName {
+            name: extract_nested_string_from_json_fields(fields, "type_y", "name")?,
+        },
+        tick_lower_index: I32Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "tick_lower_index", "bits")?,
+        },
+        tick_upper_index: I32Wrapper {
+            bits: extract_nested_string_from_json_fields(fields, "tick_upper_index", "bits")?,
+        },
+        liquidity: extract_u128_from_json_fields(fields, "liquidity")?,
+        owed_coin_x: extract_number_from_json_fields(fields, "owed_coin_x")?,
+        owed_coin_y: extract_number_from_json_fields(fields, "owed_coin_y")?,
+        coins_owed_reward: extract_u64_list_from_json_fields(fields, "reward_infos", "coins_owed_reward")?,
+    };
+
+    Ok(position_data)
+}
@@ -1,19 +1,20 @@
 use serde_json::Value;
+use crate::dex;
 use crate::parsers::types::*;
 use std::collections::HashMap;
 
+pub mod bluefin;
 pub mod cetus;
+pub mod flowx;
+pub mod mmt;
 
 pub fn try_match(json: Box<Value>) -> Result<Position, anyhow::Error> {
-    // Try Cetus position first
-    if let Ok(cetus_position_data) = cetus::map_position_data(&json) {
-        return Ok(Position::Cetus(cetus_position_data));
+    // Try every registered DEX adapter in order
+    for adapter in dex::adapters() {
+        if let Ok(position_data) = adapter.parse_position(&json) {
+            return Ok(position_data);
+        }
     }
-    
-    // Add more DEX position types here in the future
-    // if let Ok(other_dex_position_data) = other_dex::map_position_data(&json) {
-    //     return Ok(Position::OtherDex(other_dex_position_data));
-    // }
 
     // If no position type matches, return error
     Err(anyhow::anyhow!("Unknown position type - could not match any known DEX position patterns"))
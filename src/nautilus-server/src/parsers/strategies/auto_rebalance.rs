@@ -2,6 +2,7 @@ use crate::math::tick_math;
 use crate::math::tick_math::*;
 use crate::parsers::common::*;
 use crate::parsers::types::*;
+use crate::EnclaveError;
 use prost_types::Value;
 
 pub fn map_strategy_data(value: &Box<Value>) -> Result<AutoRebalanceStrategy, anyhow::Error> {
@@ -50,6 +51,7 @@ pub fn map_strategy_data(value: &Box<Value>) -> Result<AutoRebalanceStrategy, an
         },
         rebalance_paused: extract_bool_from_fields(fields, "rebalance_paused")?,
         lp_slippage_tolerance_bps: extract_number_from_fields(fields, "lp_slippage_tolerance_bps")?,
+        compound_min_value: extract_number_from_fields(fields, "compound_min_value").ok(),
         last_rebalance_timestamp: extract_number_from_fields(fields, "last_rebalance_timestamp")?,
     };
 
@@ -116,6 +118,42 @@ pub fn map_strategy_data(value: &Box<Value>) -> Result<AutoRebalanceStrategy, an
 const MAX_SQRT_PRICE_X64: u128 = 79226673515401279992447579055;
 const MIN_SQRT_PRICE_X64: u128 = 4295048016;
 
+/// Sqrt prices between which a position over `[position_lower_tick, position_upper_tick]`
+/// is left alone, `get_new_tick_range` only picks a new range outside of them.
+pub fn acceptable_sqrt_price_band(
+    position_lower_tick: i32,
+    position_upper_tick: i32,
+    lower_sqrt_price_change_threshold_bps: u64,
+    upper_sqrt_price_change_threshold_bps: u64,
+    lower_sqrt_price_change_threshold_direction: bool,
+    upper_sqrt_price_change_threshold_direction: bool,
+) -> (u128, u128) {
+    let sqrt_price_lower = tick_math::get_sqrt_price_at_tick(position_lower_tick);
+    let sqrt_price_upper = tick_math::get_sqrt_price_at_tick(position_upper_tick);
+    let lower_sqrt_price_change =
+        sqrt_price_lower * (lower_sqrt_price_change_threshold_bps as u128) / 10000u128;
+    let upper_sqrt_price_change =
+        sqrt_price_upper * (upper_sqrt_price_change_threshold_bps as u128) / 10000u128;
+    let min_acceptable_sqrt_price = if lower_sqrt_price_change_threshold_direction {
+        sqrt_price_lower + lower_sqrt_price_change
+    } else {
+        sqrt_price_lower - lower_sqrt_price_change
+    };
+    let max_acceptable_sqrt_price = if upper_sqrt_price_change_threshold_direction {
+        sqrt_price_upper - upper_sqrt_price_change
+    } else {
+        sqrt_price_upper + upper_sqrt_price_change
+    };
+    (min_acceptable_sqrt_price, max_acceptable_sqrt_price)
+}
+
+/// Whether `current_sqrt_price` left `band`, the `acceptable_sqrt_price_band` of a
+/// position, so the position is due for a new range.
+pub fn is_out_of_range(current_sqrt_price: u128, band: (u128, u128)) -> bool {
+    let (min_acceptable_sqrt_price, max_acceptable_sqrt_price) = band;
+    current_sqrt_price < min_acceptable_sqrt_price || current_sqrt_price > max_acceptable_sqrt_price
+}
+
 pub fn get_new_tick_range(
     current_sqrt_price: u128,
     current_tick_index: u32,
@@ -153,22 +191,14 @@ pub fn get_new_tick_range(
     println!("range_multiplier_upper: {:?}", range_multiplier_upper);
     println!("tick_spacing: {:?}", tick_spacing);
 
-    let sqrt_price_lower = tick_math::get_sqrt_price_at_tick(position_lower_tick);
-    let sqrt_price_upper = tick_math::get_sqrt_price_at_tick(position_upper_tick);
-    let lower_sqrt_price_change =
-        sqrt_price_lower * (lower_sqrt_price_change_threshold_bps as u128) / 10000u128;
-    let upper_sqrt_price_change =
-        sqrt_price_upper * (upper_sqrt_price_change_threshold_bps as u128) / 10000u128;
-    let _min_acceptable_sqrt_price = if lower_sqrt_price_change_threshold_direction {
-        sqrt_price_lower + lower_sqrt_price_change
-    } else {
-        sqrt_price_lower - lower_sqrt_price_change
-    };
-    let _max_acceptable_sqrt_price = if upper_sqrt_price_change_threshold_direction {
-        sqrt_price_upper - upper_sqrt_price_change
-    } else {
-        sqrt_price_upper + upper_sqrt_price_change
-    };
+    let (_min_acceptable_sqrt_price, _max_acceptable_sqrt_price) = acceptable_sqrt_price_band(
+        position_lower_tick,
+        position_upper_tick,
+        lower_sqrt_price_change_threshold_bps,
+        upper_sqrt_price_change_threshold_bps,
+        lower_sqrt_price_change_threshold_direction,
+        upper_sqrt_price_change_threshold_direction,
+    );
     println!(
         "_min_acceptable_sqrt_price: {:?}",
         _min_acceptable_sqrt_price
@@ -177,9 +207,10 @@ pub fn get_new_tick_range(
         "_max_acceptable_sqrt_price: {:?}",
         _max_acceptable_sqrt_price
     );
-    if current_sqrt_price < _min_acceptable_sqrt_price
-        || current_sqrt_price > _max_acceptable_sqrt_price
-    {
+    if is_out_of_range(
+        current_sqrt_price,
+        (_min_acceptable_sqrt_price, _max_acceptable_sqrt_price),
+    ) {
         let mut new_sqrt_price_lower =
             current_sqrt_price - current_sqrt_price * (range_multiplier_lower as u128) / 10000u128;
         let mut new_sqrt_price_upper =
@@ -205,3 +236,209 @@ pub fn get_new_tick_range(
         return Err(anyhow::anyhow!("Invalid sqrt price"));
     }
 }
+
+/// Rejects a rebalance the `auto_rebalance` module would abort at `now_ms`, so no
+/// gas is spent on it: the strategy must not be paused, its cooldown must have
+/// elapsed and its tick bounds must leave room for a range.
+pub fn validate_rebalance(
+    strategy: &AutoRebalanceStrategy,
+    now_ms: u64,
+) -> Result<(), EnclaveError> {
+    if strategy.rebalance_paused {
+        return Err(EnclaveError::StrategyNotEligible(format!(
+            "Strategy {} has rebalancing paused",
+            strategy.id
+        )));
+    }
+    let ready_at_ms = strategy
+        .last_rebalance_timestamp
+        .saturating_add(strategy.rebalance_cooldown_secs.saturating_mul(1000));
+    if now_ms < ready_at_ms {
+        return Err(EnclaveError::StrategyNotEligible(format!(
+            "Strategy {} was rebalanced at {} ms and its cooldown of {} s ends at {} ms, {} ms from now",
+            strategy.id,
+            strategy.last_rebalance_timestamp,
+            strategy.rebalance_cooldown_secs,
+            ready_at_ms,
+            ready_at_ms - now_ms
+        )));
+    }
+    let (min_tick, max_tick) = tick_bounds(strategy)?;
+    if min_tick >= max_tick {
+        return Err(EnclaveError::StrategyNotEligible(format!(
+            "Strategy {} has rebalance_min_tick {} not below rebalance_max_tick {}",
+            strategy.id, min_tick, max_tick
+        )));
+    }
+    Ok(())
+}
+
+/// `rebalance_min_tick` and `rebalance_max_tick` of the strategy.
+pub fn tick_bounds(strategy: &AutoRebalanceStrategy) -> Result<(i32, i32), EnclaveError> {
+    let parse = |name: &str, tick: &I32Wrapper| {
+        tick.bits
+            .parse::<u32>()
+            .map(|bits| bits as i32)
+            .map_err(|_| {
+                EnclaveError::ParseMismatch(format!(
+                    "Strategy {} has an invalid {}: {}",
+                    strategy.id, name, tick.bits
+                ))
+            })
+    };
+    Ok((
+        parse("rebalance_min_tick", &strategy.rebalance_min_tick)?,
+        parse("rebalance_max_tick", &strategy.rebalance_max_tick)?,
+    ))
+}
+
+/// The range `get_new_tick_range` picks for an out of range position, clamped to the
+/// strategy tick bounds.
+pub fn rebalance_tick_range(
+    strategy: &AutoRebalanceStrategy,
+    current_sqrt_price: u128,
+    current_tick_index: u32,
+    position_lower_tick: i32,
+    position_upper_tick: i32,
+    tick_spacing: u32,
+) -> Result<(i32, i32), EnclaveError> {
+    let new_range = get_new_tick_range(
+        current_sqrt_price,
+        current_tick_index,
+        position_lower_tick,
+        position_upper_tick,
+        strategy.lower_sqrt_price_change_threshold_bps,
+        strategy.upper_sqrt_price_change_threshold_bps,
+        strategy.lower_sqrt_price_change_threshold_direction,
+        strategy.upper_sqrt_price_change_threshold_direction,
+        strategy.range_multiplier_lower,
+        strategy.range_multiplier_upper,
+        tick_spacing,
+    )
+    .map_err(|e| {
+        EnclaveError::StrategyNotEligible(format!("Failed to get new tick range: {}", e))
+    })?;
+    clamp_tick_range(new_range, tick_bounds(strategy)?, tick_spacing)
+}
+
+/// `range` clamped to `bounds`, with clamped ends moved inwards onto a multiple of
+/// `tick_spacing`. Fails when nothing of the range is left.
+pub fn clamp_tick_range(
+    range: (i32, i32),
+    bounds: (i32, i32),
+    tick_spacing: u32,
+) -> Result<(i32, i32), EnclaveError> {
+    let spacing = tick_spacing.max(1) as i32;
+    let (min_tick, max_tick) = bounds;
+    let lower = if range.0 < min_tick {
+        min_tick + (spacing - min_tick.rem_euclid(spacing)) % spacing
+    } else {
+        range.0
+    };
+    let upper = if range.1 > max_tick {
+        max_tick - max_tick.rem_euclid(spacing)
+    } else {
+        range.1
+    };
+    if lower >= upper {
+        return Err(EnclaveError::StrategyNotEligible(format!(
+            "New range [{}, {}] leaves no range of tick spacing {} within the bounds [{}, {}]",
+            range.0, range.1, tick_spacing, min_tick, max_tick
+        )));
+    }
+    Ok((lower, upper))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strategy() -> AutoRebalanceStrategy {
+        AutoRebalanceStrategy {
+            id: "0x1".to_string(),
+            owner: "0x2".to_string(),
+            position_registry_id: 7,
+            description: String::new(),
+            lower_sqrt_price_change_threshold_bps: 0,
+            upper_sqrt_price_change_threshold_bps: 0,
+            lower_sqrt_price_change_threshold_direction: true,
+            upper_sqrt_price_change_threshold_direction: true,
+            rebalance_cooldown_secs: 3600,
+            range_multiplier_lower: 1000,
+            range_multiplier_upper: 1000,
+            rebalance_max_tick: I32Wrapper {
+                bits: 6000u32.to_string(),
+            },
+            rebalance_min_tick: I32Wrapper {
+                bits: (-6000i32 as u32).to_string(),
+            },
+            rebalance_paused: false,
+            lp_slippage_tolerance_bps: 50,
+            compound_min_value: None,
+            last_rebalance_timestamp: 1000000,
+        }
+    }
+
+    #[test]
+    fn test_validate_rebalance() {
+        let ready_at_ms = 1000000 + 3600 * 1000;
+        assert!(validate_rebalance(&strategy(), ready_at_ms).is_ok());
+        assert!(validate_rebalance(&strategy(), ready_at_ms - 1)
+            .unwrap_err()
+            .to_string()
+            .contains("1 ms from now"));
+
+        let paused = AutoRebalanceStrategy {
+            rebalance_paused: true,
+            ..strategy()
+        };
+        assert!(validate_rebalance(&paused, ready_at_ms).is_err());
+
+        let empty = AutoRebalanceStrategy {
+            rebalance_max_tick: I32Wrapper {
+                bits: (-6000i32 as u32).to_string(),
+            },
+            ..strategy()
+        };
+        assert!(validate_rebalance(&empty, ready_at_ms).is_err());
+    }
+
+    #[test]
+    fn test_is_out_of_range() {
+        let band = acceptable_sqrt_price_band(-600, 600, 0, 0, true, true);
+        let at_tick = |tick: i32| tick_math::get_sqrt_price_at_tick(tick);
+        assert!(!is_out_of_range(at_tick(0), band));
+        assert!(!is_out_of_range(at_tick(600), band));
+        assert!(is_out_of_range(at_tick(601), band));
+        assert!(is_out_of_range(at_tick(-601), band));
+
+        // a 1% sqrt price threshold towards the inside shrinks the band to about +-400
+        let band = acceptable_sqrt_price_band(-600, 600, 100, 100, true, true);
+        assert!(is_out_of_range(at_tick(590), band));
+        assert!(!is_out_of_range(at_tick(0), band));
+    }
+
+    #[test]
+    fn test_clamp_tick_range() {
+        assert_eq!(
+            clamp_tick_range((-120, 120), (-6000, 6000), 60).unwrap(),
+            (-120, 120)
+        );
+        assert_eq!(
+            clamp_tick_range((-6600, 7200), (-5990, 5990), 60).unwrap(),
+            (-5940, 5940)
+        );
+        assert!(clamp_tick_range((6600, 7200), (-6000, 6000), 60).is_err());
+    }
+
+    #[test]
+    fn test_rebalance_tick_range() {
+        let at_tick = |tick: i32| tick_math::get_sqrt_price_at_tick(tick);
+        // the price left [-600, 600], the new range around tick 5000 reaches past 6000
+        let (lower, upper) =
+            rebalance_tick_range(&strategy(), at_tick(5000), 5000, -600, 600, 60).unwrap();
+        assert!(lower < 5000 && lower % 60 == 0);
+        assert_eq!(upper, 6000);
+        assert!(rebalance_tick_range(&strategy(), at_tick(0), 0, -600, 600, 60).is_err());
+    }
+}
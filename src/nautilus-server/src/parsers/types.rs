@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use sui_sdk_types::{Address, TypeTag};
 use std::collections::HashMap;
+use crate::math::swap_math::PoolTicks;
 
 // ============================================================================
 // REQUEST TYPES
@@ -18,8 +19,11 @@ pub struct RebalanceRequest {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CompoundRequest {
-    pub strategy_id: String,
-    pub pending_rewards_value: u64,
+    pub strategy_id: Address,
+    pub current_sqrt_price: u128,
+    pub tick_lower_index_u32: u32,
+    pub tick_upper_index_u32: u32,
+    pub pending_rewards_value: u64, // in coin A
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,6 +43,37 @@ pub struct ProcessedPoolData {
     pub coin_b_type: TypeTag,
     pub balances_bag: BalancesBag,
     pub rewarder_coin_types: Vec<TypeTag>,
+    pub fee_rate: u64,
+    pub pool_liquidity: u128,
+    pub pool_ticks: PoolTicks,
+    /// Fetched for a compound only, empty on a rebalance.
+    pub pending_rewards: PendingRewards,
+    /// Range a rebalance moves the position to, clamped to the strategy tick bounds.
+    pub new_tick_range: Option<(i32, i32)>,
+}
+
+/// Fees and rewards a position can collect, in the smallest unit of each coin.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PendingRewards {
+    pub fee_a: u64,
+    pub fee_b: u64,
+    pub rewards: Vec<PendingReward>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PendingReward {
+    pub coin_type: TypeTag,
+    pub amount: u64,
+}
+
+impl PendingRewards {
+    pub fn reward_amount(&self, coin_type: &TypeTag) -> u64 {
+        self.rewards
+            .iter()
+            .filter(|reward| &reward.coin_type == coin_type)
+            .map(|reward| reward.amount)
+            .sum()
+    }
 }
 
 // ============================================================================
@@ -131,14 +166,127 @@ pub struct TickOption {
     pub v: String,
 }
 
+/// A tick of the Cetus tick skip list, stored as a dynamic field of `ticks.id`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CetusTick {
+    pub index: TickIndex,
+    pub sqrt_price: String,
+    pub liquidity_net: I128Wrapper,
+    pub liquidity_gross: String,
+    pub fee_growth_outside_a: String,
+    pub fee_growth_outside_b: String,
+    pub rewards_growth_outside: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RandomSeed {
     pub seed: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MmtPoolData {
+    pub id: String,
+    pub type_x: TypeName,
+    pub type_y: TypeName,
+    pub sqrt_price: String,
+    pub liquidity: String,
+    pub tick_index: I32Wrapper,
+    pub tick_spacing: u64,
+    pub swap_fee_rate: String,
+    pub fee_growth_global_x: String,
+    pub fee_growth_global_y: String,
+    pub reward_infos: Vec<MmtPoolRewardInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MmtPoolRewardInfo {
+    pub reward_coin_type: TypeName,
+    pub reward_per_seconds: String,
+    pub reward_growth_global: String,
+    pub last_update_time: String,
+    pub ended_at_seconds: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BluefinPoolData {
+    pub id: String,
+    pub name: String,
+    pub fee_rate: String,
+    pub current_sqrt_price: String,
+    pub current_tick_index: I32Wrapper,
+    pub liquidity: String,
+    pub tick_spacing: u64,
+    pub fee_growth_global_coin_a: String,
+    pub fee_growth_global_coin_b: String,
+    pub reward_infos: Vec<BluefinPoolRewardInfo>,
+    pub is_paused: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BluefinPoolRewardInfo {
+    /// Coin type as an ascii string, without the `0x` prefix.
+    pub reward_coin_type: String,
+    pub reward_per_seconds: String,
+    pub reward_growth_global: String,
+    pub last_update_time: String,
+    pub ended_at_seconds: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowXPoolData {
+    pub id: String,
+    pub coin_type_x: TypeName,
+    pub coin_type_y: TypeName,
+    pub sqrt_price: String,
+    pub tick_index: I32Wrapper,
+    pub tick_spacing: u64,
+    pub swap_fee_rate: String,
+    pub fee_growth_global_x: String,
+    pub fee_growth_global_y: String,
+    pub liquidity: String,
+    pub reward_infos: Vec<FlowXPoolRewardInfo>,
+    pub locked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowXPoolRewardInfo {
+    pub reward_coin_type: TypeName,
+    pub reward_per_seconds: String,
+    pub reward_growth_global: String,
+    pub last_update_time: String,
+    pub ended_at_seconds: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Pool {
     Cetus(CetusPoolData),
+    Mmt(MmtPoolData),
+    Bluefin(BluefinPoolData),
+    FlowX(FlowXPoolData),
+}
+
+impl Pool {
+    pub fn dex(&self) -> SupportedDex {
+        match self {
+            Pool::Cetus(_) => SupportedDex::Cetus,
+            Pool::Mmt(_) => SupportedDex::MMT,
+            Pool::Bluefin(_) => SupportedDex::Bluefin,
+            Pool::FlowX(_) => SupportedDex::FlowX,
+        }
+    }
+}
+
+/// DEX independent view of a pool, produced by `DexAdapter::pool_summary`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PoolSummary {
+    pub current_tick_u32: u32,
+    pub current_sqrt_price: u128,
+    pub tick_spacing: u32,
+    /// Swap fee rate as stored by the DEX, some DEXes key their pools by it.
+    pub fee_rate: u64,
+    /// Liquidity active at the current price.
+    pub liquidity: u128,
+    pub rewarder_coin_types: Vec<TypeTag>,
 }
 
 // ============================================================================
@@ -165,9 +313,104 @@ pub struct TypeName {
     pub name: String,
 }
 
+/// Per position accounting Cetus keeps in the pool's `position_manager`, not in the position NFT.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CetusPositionInfo {
+    pub position_id: String,
+    pub liquidity: u128,
+    pub fee_growth_inside_a: u128,
+    pub fee_owned_a: u64,
+    pub fee_growth_inside_b: u128,
+    pub fee_owned_b: u64,
+    // per rewarder, in `rewarder_manager.rewarders` order
+    pub rewards_growth_inside: Vec<u128>,
+    pub rewards_amount_owned: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MmtPosition {
+    pub id: String,
+    pub pool_id: String,
+    pub fee_rate: u64,
+    pub type_x: TypeName,
+    pub type_y: TypeName,
+    pub tick_lower_index: I32Wrapper,
+    pub tick_upper_index: I32Wrapper,
+    pub liquidity: u128,
+    pub owed_coin_x: u64,
+    pub owed_coin_y: u64,
+    // owed amount per pool reward, in pool `reward_infos` order
+    pub coins_owed_reward: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BluefinPosition {
+    pub id: String,
+    pub pool_id: String,
+    pub coin_type_a: String,
+    pub coin_type_b: String,
+    pub lower_tick: I32Wrapper,
+    pub upper_tick: I32Wrapper,
+    pub liquidity: u128,
+    pub token_a_fee: u64,
+    pub token_b_fee: u64,
+    // owed amount per pool reward, in pool `reward_infos` order
+    pub coins_owed_reward: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowXPosition {
+    pub id: String,
+    pub pool_id: String,
+    pub fee_rate: u64,
+    pub coin_type_x: TypeName,
+    pub coin_type_y: TypeName,
+    pub tick_lower_index: I32Wrapper,
+    pub tick_upper_index: I32Wrapper,
+    pub liquidity: u128,
+    pub coins_owed_x: u64,
+    pub coins_owed_y: u64,
+    // owed amount per pool reward, in pool `reward_infos` order
+    pub coins_owed_reward: Vec<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Position {
     Cetus(CetusPosition),
+    Mmt(MmtPosition),
+    Bluefin(BluefinPosition),
+    FlowX(FlowXPosition),
+}
+
+impl Position {
+    pub fn dex(&self) -> SupportedDex {
+        match self {
+            Position::Cetus(_) => SupportedDex::Cetus,
+            Position::Mmt(_) => SupportedDex::MMT,
+            Position::Bluefin(_) => SupportedDex::Bluefin,
+            Position::FlowX(_) => SupportedDex::FlowX,
+        }
+    }
+
+    /// Pool the position provides liquidity to.
+    pub fn pool_id(&self) -> &str {
+        match self {
+            Position::Cetus(position) => &position.pool,
+            Position::Mmt(position) => &position.pool_id,
+            Position::Bluefin(position) => &position.pool_id,
+            Position::FlowX(position) => &position.pool_id,
+        }
+    }
+}
+
+/// DEX independent view of a position, produced by `DexAdapter::position_summary`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PositionSummary {
+    pub tick_lower_index_u32: u32,
+    pub tick_upper_index_u32: u32,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub liquidity: u128,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -196,6 +439,8 @@ pub struct AutoRebalanceStrategy {
     pub rebalance_min_tick: I32Wrapper,
     pub rebalance_paused: bool,
     pub lp_slippage_tolerance_bps: u64,
+    // minimum pending fees + rewards, valued in coin A, worth compounding. None disables compounding
+    pub compound_min_value: Option<u64>,
     // state
     pub last_rebalance_timestamp: u64,
 }
@@ -214,6 +459,11 @@ pub struct I32Wrapper {
     pub bits: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct I128Wrapper {
+    pub bits: String,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SupportedDex {
     MMT,
@@ -0,0 +1,559 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Moving the enclave to a new signing key.
+//!
+//! `POST /rotate_key` draws a new key, attests it and signs a handover to the new
+//! public key with the current one. With `submit` set it also registers the new key
+//! against the enclave config and destroys the old `Enclave` object in the same
+//! transaction, and signs with the new key once that transaction is finalized.
+//! Otherwise, or when that transaction fails, the key is held as pending until the
+//! operator registered it and calls `POST /rotate_key/complete` with the new enclave
+//! id.
+
+use crate::common::{
+    construct_kp_from_bech32_string, to_signed_response, IntentMessage, IntentScope,
+    ProcessedDataResponse,
+};
+use crate::config::AppConfig;
+use crate::enclave_key;
+use crate::transactions_builder::argument;
+use crate::transactions_builder::constant::CLOCK_OBJECT_ID;
+use crate::transactions_builder::helper::{self, SuiUtilsError};
+use crate::AppState;
+use crate::EnclaveError;
+use axum::extract::State;
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use axum::Json;
+use fastcrypto::ed25519::Ed25519KeyPair;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::traits::{KeyPair, ToFromBytes};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use sui_graphql_client::Client;
+use sui_sdk_types::{Address, IdOperation, Identifier, ObjectData, TransactionEffects, TypeTag};
+use sui_transaction_builder::Function;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Environment variable holding the bearer token the rotation endpoints require.
+pub const ADMIN_TOKEN_VAR: &str = "ROTATION_ADMIN_TOKEN";
+
+/// Key drawn by `/rotate_key`, signed with once its enclave is registered.
+struct PendingKey {
+    kp: Ed25519KeyPair,
+    sealed_key: Option<String>,
+}
+
+/// Rotation state held in `AppState`.
+#[derive(Default)]
+pub struct Rotation {
+    /// Token the rotation endpoints require, they are disabled when `None`.
+    admin_token: Option<String>,
+    /// Held for the whole rotation, so two never interleave.
+    pending: Mutex<Option<PendingKey>>,
+}
+
+impl Rotation {
+    pub fn new(admin_token: Option<String>) -> Self {
+        Self {
+            admin_token: admin_token.filter(|token| !token.is_empty()),
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// Check the `Authorization: Bearer` header against the admin token.
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), EnclaveError> {
+        let Some(admin_token) = &self.admin_token else {
+            return Err(EnclaveError::Unauthorized(format!(
+                "Key rotation is disabled, {} is not set",
+                ADMIN_TOKEN_VAR
+            )));
+        };
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !constant_time_eq(token.as_bytes(), admin_token.as_bytes()) {
+            return Err(EnclaveError::Unauthorized(
+                "Invalid admin token".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Request of `/rotate_key`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RotateKeyRequest {
+    /// Register the new key on chain and switch to it, needs the `rotation` config.
+    pub submit: bool,
+    /// `Enclave` object of the current key to destroy, defaults to the one the server
+    /// signs for. Nothing is destroyed when neither is set.
+    pub old_enclave_id: Option<String>,
+}
+
+/// Public keys of a rotation, signed by the old key with `IntentScope::KeyRotation`
+/// so a verifier trusting the old key can trust the new one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyHandover {
+    pub old_public_key: Vec<u8>,
+    pub new_public_key: Vec<u8>,
+}
+
+/// Response of `/rotate_key`.
+#[derive(Serialize, Deserialize)]
+pub struct RotateKeyResponse {
+    /// Hex encoded public key of the new key.
+    pub public_key: String,
+    /// Attestation document committed to the new key, hex encoded. It is what
+    /// `register_enclave` is called with.
+    pub attestation: String,
+    pub handover: ProcessedDataResponse<IntentMessage<KeyHandover>>,
+    /// Digest of the registration transaction, when submitted.
+    pub digest: Option<String>,
+    /// `Enclave` object of the new key, when submitted. The server signs with the new
+    /// key from then on, otherwise it waits for `/rotate_key/complete`.
+    pub enclave_id: Option<String>,
+}
+
+/// Request of `/rotate_key/complete`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompleteRotationRequest {
+    /// `Enclave` object registered with the attestation of the pending key.
+    pub enclave_id: String,
+}
+
+/// Response of `/rotate_key/complete`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompleteRotationResponse {
+    /// Hex encoded public key the server now signs with.
+    pub public_key: String,
+    pub enclave_id: String,
+}
+
+/// BCS layout of `enclave::Enclave<T>`, fields are positional.
+#[derive(Debug, Deserialize)]
+struct EnclaveObject {
+    _id: Address,
+    pk: Vec<u8>,
+    config_version: u64,
+    _owner: Address,
+}
+
+/// BCS layout of `enclave::EnclaveConfig<T>`.
+#[derive(Debug, Deserialize)]
+struct EnclaveConfigObject {
+    _id: Address,
+    _name: String,
+    _pcrs: (Vec<u8>, Vec<u8>, Vec<u8>),
+    _capability_id: Address,
+    version: u64,
+}
+
+/// Endpoint that rotates the enclave key, see the module docs. A rotation replaces
+/// the key pending from an earlier one, and the new key stays pending when `submit`
+/// fails.
+pub async fn rotate_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<RotateKeyRequest>,
+) -> Result<Json<RotateKeyResponse>, EnclaveError> {
+    state.rotation.authorize(&headers)?;
+    let mut pending = state.rotation.pending.try_lock().map_err(|_| {
+        EnclaveError::GenericError("A key rotation is already in progress".to_string())
+    })?;
+
+    let (kp, sealed_key) =
+        enclave_key::create(&state.config.enclave_key, state.nsm.as_ref(), |key| {
+            std::env::var(key).ok()
+        })
+        .await
+        .map_err(|e| {
+            EnclaveError::GenericError(format!("Failed to create the new key: {:#}", e))
+        })?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Failed to get current timestamp: {}", e)))?
+        .as_millis() as u64;
+    let (attestation, handover) = handover(&state, &kp, timestamp_ms)?;
+    let mut response = RotateKeyResponse {
+        public_key: Hex::encode(kp.public().as_bytes()),
+        attestation: Hex::encode(&attestation),
+        handover,
+        digest: None,
+        enclave_id: None,
+    };
+    // Held as pending before submitting, so that if registration lands on chain but
+    // its outcome is lost, `complete_rotation` can still switch to the key.
+    *pending = Some(PendingKey { kp, sealed_key });
+    if !request.submit {
+        return Ok(Json(response));
+    }
+    let old_enclave_id = request.old_enclave_id.or_else(|| state.enclave_id());
+    let (digest, enclave_id) = register(&state, &attestation, old_enclave_id.as_deref())
+        .await
+        .map_err(|e| EnclaveError::from_anyhow(e, EnclaveError::GenericError))?;
+    let key = pending.take().expect("set above");
+    switch(&state, key, enclave_id.clone());
+    response.digest = Some(digest);
+    response.enclave_id = Some(enclave_id);
+    Ok(Json(response))
+}
+
+/// Endpoint that switches to the pending key once `enclave_id` is registered for it.
+pub async fn complete_rotation(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<CompleteRotationRequest>,
+) -> Result<Json<CompleteRotationResponse>, EnclaveError> {
+    state.rotation.authorize(&headers)?;
+    let mut pending = state.rotation.pending.try_lock().map_err(|_| {
+        EnclaveError::GenericError("A key rotation is already in progress".to_string())
+    })?;
+    let public_key = pending
+        .as_ref()
+        .map(|key| key.kp.public().as_bytes().to_vec())
+        .ok_or_else(|| EnclaveError::GenericError("No key rotation is pending".to_string()))?;
+
+    let client = state
+        .config
+        .graphql_client()
+        .map_err(|e| EnclaveError::UpstreamRpc(format!("Failed to create client: {}", e)))?;
+    let enclave_id = Address::from_hex(&request.enclave_id)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid enclave id: {}", e)))?;
+    let enclave: EnclaveObject = read(&client, enclave_id)
+        .await
+        .map_err(|e| EnclaveError::from_anyhow(e, EnclaveError::ParseMismatch))?;
+    if enclave.pk != public_key {
+        return Err(EnclaveError::GenericError(format!(
+            "Enclave {} is not registered for the pending key",
+            request.enclave_id
+        )));
+    }
+
+    let key = pending.take().expect("checked above");
+    switch(&state, key, request.enclave_id.clone());
+    Ok(Json(CompleteRotationResponse {
+        public_key: Hex::encode(&public_key),
+        enclave_id: request.enclave_id,
+    }))
+}
+
+/// Attestation document committed to `new_kp`, and the handover to it signed with
+/// the current key.
+fn handover(
+    state: &AppState,
+    new_kp: &Ed25519KeyPair,
+    timestamp_ms: u64,
+) -> Result<(Vec<u8>, ProcessedDataResponse<IntentMessage<KeyHandover>>), EnclaveError> {
+    let new_public_key = new_kp.public().as_bytes().to_vec();
+    let attestation = state
+        .nsm
+        .attestation(Some(new_public_key.clone()), None, None)
+        .map_err(|e| EnclaveError::AttestationFailure(e.to_string()))?;
+    let old_kp = state.eph_kp();
+    let handover = to_signed_response(
+        &old_kp,
+        KeyHandover {
+            old_public_key: old_kp.public().as_bytes().to_vec(),
+            new_public_key,
+        },
+        timestamp_ms,
+        IntentScope::KeyRotation,
+    )?;
+    Ok((attestation, handover))
+}
+
+/// Sign with `key` from now on, as the enclave `enclave_id`. Each lock only ever
+/// holds a value replaced whole, so a poisoned one is taken over as is.
+fn switch(state: &AppState, key: PendingKey, enclave_id: String) {
+    info!(
+        public_key = %Hex::encode(key.kp.public().as_bytes()),
+        enclave_id = %enclave_id,
+        "enclave key rotated"
+    );
+    *state.eph_kp.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key.kp);
+    *state.sealed_key.write().unwrap_or_else(|e| e.into_inner()) = key.sealed_key;
+    *state.enclave_id.write().unwrap_or_else(|e| e.into_inner()) = Some(enclave_id);
+}
+
+/// Register the key `attestation` commits to and destroy `old_enclave_id` in one
+/// transaction signed by the keeper address, returning its digest and the new
+/// `Enclave` object. The old enclave is destroyed by config version when the config
+/// was updated since it registered, by owner otherwise.
+async fn register(
+    state: &AppState,
+    attestation: &[u8],
+    old_enclave_id: Option<&str>,
+) -> Result<(String, String), anyhow::Error> {
+    let config = &state.config.rotation;
+    let (Some(package_id), Some(config_id), Some(enclave_type)) = (
+        &config.enclave_package_id,
+        &config.enclave_config_id,
+        &config.enclave_type,
+    ) else {
+        return Err(EnclaveError::GenericError(
+            "rotation.enclave_package_id, enclave_config_id and enclave_type are required to submit"
+                .to_string(),
+        )
+        .into());
+    };
+    let package_id = Address::from_hex(package_id)?;
+    let config_id = Address::from_hex(config_id)?;
+    let enclave_type = TypeTag::from_str(enclave_type)?;
+    let kp = construct_kp_from_bech32_string(&state.pk_string)
+        .map_err(|e| EnclaveError::SigningFailure(format!("Failed to construct keypair: {}", e)))?;
+    let sender = kp.public_key().derive_address();
+    let client = state
+        .config
+        .graphql_client()
+        .map_err(|e| EnclaveError::UpstreamRpc(format!("Failed to create client: {}", e)))?;
+
+    // gas comes from the pool like the jobs take it, so no job spends the same coin
+    let gas_budget = state.config.gas_budget;
+    let mut gas_lease = None;
+    let mut builder = match &state.sponsor {
+        Some(sponsor) => {
+            helper::new_sponsored(&client, sender, sponsor.address(), gas_budget).await?
+        }
+        None if state.config.gas_pool.size > 0 => {
+            let lease = state
+                .gas_pool
+                .lease(
+                    gas_budget,
+                    Duration::from_secs(state.config.gas_pool.lease_timeout_secs),
+                )
+                .await?;
+            let gas_coin = lease.coin().object_ref.clone();
+            gas_lease = Some(lease);
+            helper::new_with_gas_coins(&client, sender, &[gas_coin], gas_budget).await?
+        }
+        None => helper::new_with_gas(&client, sender, gas_budget).await?,
+    };
+    let attestation_arg = argument::pure(&mut builder, attestation.to_vec())?;
+    let clock_arg =
+        argument::shared_ref(&client, &mut builder, Address::from_hex(CLOCK_OBJECT_ID)?).await?;
+    let config_arg = argument::shared_ref(&client, &mut builder, config_id).await?;
+    let document = builder.move_call(
+        Function::new(
+            Address::from_hex("0x2")?,
+            Identifier::new("nitro_attestation")?,
+            Identifier::new("load_nitro_attestation")?,
+            vec![],
+        ),
+        vec![attestation_arg, clock_arg],
+    );
+    builder.move_call(
+        Function::new(
+            package_id,
+            Identifier::new("enclave")?,
+            Identifier::new("register_enclave")?,
+            vec![enclave_type.clone()],
+        ),
+        vec![config_arg, document],
+    );
+    if let Some(old_enclave_id) = old_enclave_id {
+        let old_enclave_id = Address::from_hex(old_enclave_id)?;
+        let old_enclave: EnclaveObject = read(&client, old_enclave_id).await?;
+        let enclave_config: EnclaveConfigObject = read(&client, config_id).await?;
+        let old_enclave_arg = argument::shared_mut(&client, &mut builder, old_enclave_id).await?;
+        let (function, arguments) = if old_enclave.config_version < enclave_config.version {
+            ("destroy_old_enclave", vec![old_enclave_arg, config_arg])
+        } else {
+            ("deploy_old_enclave_by_owner", vec![old_enclave_arg])
+        };
+        builder.move_call(
+            Function::new(
+                package_id,
+                Identifier::new("enclave")?,
+                Identifier::new(function)?,
+                vec![enclave_type],
+            ),
+            arguments,
+        );
+    }
+
+    let tx = helper::finish(builder)?;
+    helper::dry_run(&client, &tx, None).await?;
+    let sig = helper::sign_transaction(&tx, &kp)?;
+    let signatures = match &state.sponsor {
+        Some(sponsor) => {
+            let sponsor_sig = sponsor.sign(&tx, &sig).await?;
+            vec![sig, sponsor_sig]
+        }
+        None => vec![sig],
+    };
+    if let Some(lease) = &mut gas_lease {
+        lease.mark_submitted();
+    }
+    let effects = helper::execute(&client, &tx, signatures).await?;
+    if let Some(lease) = gas_lease {
+        lease.release(&effects);
+    }
+    helper::wait_for_finalization(&client, &tx).await?;
+    let enclave_id = created_object(&effects).ok_or(SuiUtilsError::InvalidTransactionEffects)?;
+    Ok((tx.digest().to_string(), enclave_id.to_string()))
+}
+
+/// The object `effects` created, the new `Enclave` is the only one.
+fn created_object(effects: &TransactionEffects) -> Option<Address> {
+    let TransactionEffects::V2(effects) = effects else {
+        return None;
+    };
+    effects
+        .changed_objects
+        .iter()
+        .find(|changed| changed.id_operation == IdOperation::Created)
+        .map(|changed| changed.object_id)
+}
+
+/// Decode the Move struct `id` from its BCS contents.
+/// Public key registered on chain for the enclave object `enclave_id`.
+pub async fn registered_pk(config: &AppConfig, enclave_id: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let client = config.graphql_client()?;
+    let enclave: EnclaveObject = read(&client, Address::from_hex(enclave_id)?).await?;
+    Ok(enclave.pk)
+}
+
+async fn read<T: DeserializeOwned>(client: &Client, id: Address) -> Result<T, anyhow::Error> {
+    let object = helper::get(client, id).await?;
+    let ObjectData::Struct(contents) = object.data() else {
+        return Err(EnclaveError::ParseMismatch(format!("{} is not a Move object", id)).into());
+    };
+    bcs::from_bytes(contents.contents()).map_err(|e| {
+        EnclaveError::ParseMismatch(format!("{} has an unexpected layout: {}", id, e)).into()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+    use fastcrypto::ed25519::{Ed25519PublicKey, Ed25519Signature};
+    use fastcrypto::traits::VerifyingKey;
+    use nsm::MockNsm;
+    use get_attestation_cli::verifier;
+    use std::collections::BTreeMap;
+    use std::sync::RwLock;
+
+    fn state(nsm: MockNsm) -> AppState {
+        AppState {
+            enclave_id: RwLock::new(Some("0x3".to_string())),
+            rotation: Rotation::new(Some("secret".to_string())),
+            ..AppState::for_test(nsm)
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        let rotation = Rotation::new(Some("secret".to_string()));
+        rotation.authorize(&headers("Bearer secret")).unwrap();
+        assert!(rotation.authorize(&headers("Bearer secreT")).is_err());
+        assert!(rotation.authorize(&headers("secret")).is_err());
+        assert!(rotation.authorize(&HeaderMap::new()).is_err());
+
+        // disabled without a token, even an empty one
+        let err = Rotation::new(Some("".to_string()))
+            .authorize(&headers("Bearer "))
+            .unwrap_err();
+        assert_eq!(err.code(), "UNAUTHORIZED");
+    }
+
+    #[test]
+    fn test_handover_and_switch() {
+        let nsm = MockNsm::new(BTreeMap::new()).unwrap();
+        let root = nsm.root().clone();
+        let state = state(nsm);
+        let old_kp = state.eph_kp();
+        let new_kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
+        let (attestation, handover) = handover(&state, &new_kp, 1000).unwrap();
+
+        // the attestation carries the new key
+        let now_secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let document = verifier::verify(&attestation, &root, now_secs).unwrap();
+        assert_eq!(document.public_key.unwrap(), new_kp.public().as_bytes());
+
+        // the old key signs the handover to it
+        assert_eq!(
+            handover.response.data.old_public_key,
+            old_kp.public().as_bytes()
+        );
+        assert_eq!(
+            handover.response.data.new_public_key,
+            new_kp.public().as_bytes()
+        );
+        let signature = Ed25519Signature::from_bytes(&handover.signature).unwrap();
+        let message = bcs::to_bytes(&handover.response).unwrap();
+        assert_eq!(message[0], IntentScope::KeyRotation as u8);
+        old_kp.public().verify(&message, &signature).unwrap();
+        let new_public_key = Ed25519PublicKey::from_bytes(new_kp.public().as_bytes()).unwrap();
+        assert!(new_public_key.verify(&message, &signature).is_err());
+
+        let key = PendingKey {
+            kp: new_kp,
+            sealed_key: Some("c2VhbGVk".to_string()),
+        };
+        switch(&state, key, "0x4".to_string());
+        assert_eq!(state.eph_kp().public(), &new_public_key);
+        assert_eq!(state.sealed_key().as_deref(), Some("c2VhbGVk"));
+        assert_eq!(state.enclave_id().as_deref(), Some("0x4"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_submit_keeps_key_pending() {
+        let state = Arc::new(state(MockNsm::new(BTreeMap::new()).unwrap()));
+        let old_public_key = state.eph_kp().public().clone();
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+
+        // no rotation config, so registration fails before anything is sent
+        let request = RotateKeyRequest {
+            submit: true,
+            old_enclave_id: None,
+        };
+        assert!(rotate_key(State(state.clone()), headers, Json(request))
+            .await
+            .is_err());
+        assert_eq!(state.eph_kp().public(), &old_public_key);
+        let pending = state.rotation.pending.try_lock().unwrap();
+        assert_ne!(pending.as_ref().unwrap().kp.public(), &old_public_key);
+    }
+
+    #[test]
+    fn test_enclave_layout() {
+        let mut contents = vec![7; 32];
+        contents.extend([3, 1, 2, 3]);
+        contents.extend(5u64.to_le_bytes());
+        contents.extend([9; 32]);
+        let enclave: EnclaveObject = bcs::from_bytes(&contents).unwrap();
+        assert_eq!(enclave.pk, vec![1, 2, 3]);
+        assert_eq!(enclave.config_version, 5);
+
+        let mut contents = vec![7; 32];
+        contents.extend([4, b'n', b'a', b'm', b'e']);
+        contents.extend([1, 0xaa, 0, 2, 0xbb, 0xcc]);
+        contents.extend([8; 32]);
+        contents.extend(6u64.to_le_bytes());
+        let config: EnclaveConfigObject = bcs::from_bytes(&contents).unwrap();
+        assert_eq!(config.version, 6);
+        assert_eq!(config._pcrs.2, vec![0xbb, 0xcc]);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::construct_kp_from_bech32_string;
+use crate::config::{SponsorConfig, SponsorMode};
+use crate::transactions_builder::helper::{self, SuiUtilsError};
+use async_trait::async_trait;
+use base64ct::Encoding;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use sui_crypto::ed25519::Ed25519PrivateKey;
+use sui_sdk_types::{Address, Transaction, UserSignature};
+
+/// Pays the gas of transactions the keeper sends, so the enclave holds no gas funds.
+#[async_trait]
+pub trait Sponsor: Send + Sync {
+    /// Gas owner of sponsored transactions, their gas coins belong to it.
+    fn address(&self) -> Address;
+
+    /// Gas owner signature of `tx`, already signed by its sender with `sender_signature`.
+    async fn sign(
+        &self,
+        tx: &Transaction,
+        sender_signature: &UserSignature,
+    ) -> helper::Result<UserSignature>;
+}
+
+/// Sponsor key held by the server.
+pub struct LocalSponsor {
+    pk: Ed25519PrivateKey,
+}
+
+impl LocalSponsor {
+    pub fn new(pk: Ed25519PrivateKey) -> Self {
+        Self { pk }
+    }
+}
+
+#[async_trait]
+impl Sponsor for LocalSponsor {
+    fn address(&self) -> Address {
+        self.pk.public_key().derive_address()
+    }
+
+    async fn sign(
+        &self,
+        tx: &Transaction,
+        _sender_signature: &UserSignature,
+    ) -> helper::Result<UserSignature> {
+        helper::sign_transaction(tx, &self.pk)
+    }
+}
+
+/// Body POSTed to a remote sponsor.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SponsorRequest {
+    pub sender: String,
+    /// BCS of the unsigned `TransactionData`, base64 encoded.
+    pub tx_bytes: String,
+    /// Sender signature over `tx_bytes`, base64 encoded.
+    pub sender_signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SponsorResponse {
+    /// Sponsor signature over `tx_bytes`, base64 encoded.
+    pub signature: String,
+}
+
+/// Sponsor service reached over HTTP, e.g. outside the enclave.
+pub struct RemoteSponsor {
+    address: Address,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl RemoteSponsor {
+    pub fn new(address: Address, url: String) -> Self {
+        Self {
+            address,
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sponsor for RemoteSponsor {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign(
+        &self,
+        tx: &Transaction,
+        sender_signature: &UserSignature,
+    ) -> helper::Result<UserSignature> {
+        let tx_bytes = bcs::to_bytes(tx)
+            .map_err(|e| SuiUtilsError::TransactionBuildingError(e.to_string()))?;
+        let request = SponsorRequest {
+            sender: tx.sender.to_string(),
+            tx_bytes: base64ct::Base64::encode_string(&tx_bytes),
+            sender_signature: sender_signature.to_base64(),
+        };
+        let sponsor_error =
+            |e: String| SuiUtilsError::TransactionSigningError(format!("Sponsor: {}", e));
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| sponsor_error(e.to_string()))?
+            .json::<SponsorResponse>()
+            .await
+            .map_err(|e| sponsor_error(e.to_string()))?;
+        UserSignature::from_base64(&response.signature).map_err(|e| sponsor_error(e.to_string()))
+    }
+}
+
+/// Sponsor selected by `config`, `None` when the keeper pays its own gas.
+/// `get_var` reads `SPONSOR_PK` in local mode.
+pub fn from_config<F>(
+    config: &SponsorConfig,
+    get_var: F,
+) -> Result<Option<Arc<dyn Sponsor>>, anyhow::Error>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match config.mode {
+        SponsorMode::Disabled => Ok(None),
+        SponsorMode::Local => {
+            let pk_string = get_var("SPONSOR_PK")
+                .ok_or_else(|| anyhow::anyhow!("SPONSOR_PK is required in local sponsor mode"))?;
+            let pk = construct_kp_from_bech32_string(&pk_string)?;
+            Ok(Some(Arc::new(LocalSponsor::new(pk))))
+        }
+        SponsorMode::Remote => {
+            let address = config
+                .address
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("sponsor.address is required in remote mode"))?;
+            let url = config
+                .url
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("sponsor.url is required in remote mode"))?;
+            Ok(Some(Arc::new(RemoteSponsor::new(
+                Address::from_str(address)?,
+                url,
+            ))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_config() {
+        let disabled = SponsorConfig::default();
+        assert!(from_config(&disabled, |_| None).unwrap().is_none());
+
+        let remote = SponsorConfig {
+            mode: SponsorMode::Remote,
+            address: Some("0x5".to_string()),
+            url: None,
+        };
+        assert!(from_config(&remote, |_| None).is_err());
+
+        let remote = SponsorConfig {
+            url: Some("http://localhost:9100".to_string()),
+            ..remote
+        };
+        let sponsor = from_config(&remote, |_| None).unwrap().unwrap();
+        assert_eq!(sponsor.address(), Address::from_str("0x5").unwrap());
+
+        let local = SponsorConfig {
+            mode: SponsorMode::Local,
+            ..SponsorConfig::default()
+        };
+        assert!(from_config(&local, |_| None).is_err());
+    }
+}
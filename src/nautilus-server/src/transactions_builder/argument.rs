@@ -33,6 +33,12 @@ pub fn pure<Pure: serde::Serialize>(
     Ok(value_arg)
 }
 
+/// Result `ix` of a move call returning a tuple.
+pub fn nested(arg: Argument, ix: u16) -> Result<Argument> {
+    arg.nested(ix)
+        .ok_or_else(|| anyhow::anyhow!("Argument {:?} has no nested result {}", arg, ix))
+}
+
 pub async fn owned(
     client: &Client,
     builder: &mut TransactionBuilder,
@@ -152,9 +158,9 @@ pub fn zero_coin(
 ) -> Result<Argument> {
     let zero_coin_arg = builder.move_call(
         Function::new(
-            Address::from_hex("0x2").unwrap(),
-            Identifier::new("coin").unwrap(),
-            Identifier::new("zero").unwrap(),
+            Address::from_hex("0x2")?,
+            Identifier::new("coin")?,
+            Identifier::new("zero")?,
             vec![coin_type],
         ),
         vec![],
@@ -168,9 +174,9 @@ pub fn destroy_zero_coin(
 ) -> Result<Argument> {
     let destroy_zero_coin_arg = builder.move_call(
         Function::new(
-            Address::from_hex("0x2").unwrap(),
-            Identifier::new("coin").unwrap(),
-            Identifier::new("destroy_zero").unwrap(),
+            Address::from_hex("0x2")?,
+            Identifier::new("coin")?,
+            Identifier::new("destroy_zero")?,
             vec![],
         ),
         vec![coin],
@@ -0,0 +1,313 @@
+use sui_sdk_types::Address;
+use sui_sdk_types::Identifier;
+use sui_sdk_types::TypeTag;
+use sui_transaction_builder::{Function, TransactionBuilder};
+
+use crate::config::AppConfig;
+use crate::math::clmm_math;
+use crate::transactions_builder::argument;
+use crate::transactions_builder::argument::ArgCache;
+use crate::transactions_builder::swap;
+use crate::transactions_builder::types::*;
+use crate::transactions_builder::{assert_coin_value, min_amount_with_slippage};
+use crate::transactions_builder::CLOCK_OBJECT_ID;
+use sui_graphql_client::Client;
+use sui_sdk_types::Argument;
+
+pub struct BluefinTransactionBuilder<'a> {
+    pub client: &'a Client,
+    pub tx: &'a mut TransactionBuilder,
+    pub arg_cache: &'a mut ArgCache,
+    pub config: &'a AppConfig,
+}
+
+impl<'a> BluefinTransactionBuilder<'a> {
+    pub async fn new(
+        client: &'a Client,
+        tx: &'a mut TransactionBuilder,
+        arg_cache: &'a mut ArgCache,
+        config: &'a AppConfig,
+    ) -> Self {
+        Self {
+            client,
+            tx,
+            arg_cache,
+            config,
+        }
+    }
+
+    async fn global_config_arg(&mut self) -> Result<Argument, anyhow::Error> {
+        argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(&self.config.bluefin.global_config_id)?,
+        )
+        .await
+    }
+
+    async fn clock_arg(&mut self) -> Result<Argument, anyhow::Error> {
+        argument::shared_ref_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(CLOCK_OBJECT_ID)?,
+        )
+        .await
+    }
+
+    // Bluefin pool functions work on `Balance`, the registry and the aggregator on `Coin`.
+    fn coin_from_balance(
+        &mut self,
+        coin_type: TypeTag,
+        balance: Argument,
+    ) -> Result<Argument, anyhow::Error> {
+        Ok(self.tx.move_call(
+            Function::new(
+                Address::from_hex("0x2")?,
+                Identifier::new("coin")?,
+                Identifier::new("from_balance")?,
+                vec![coin_type],
+            ),
+            vec![balance],
+        ))
+    }
+
+    fn coin_into_balance(
+        &mut self,
+        coin_type: TypeTag,
+        coin: Argument,
+    ) -> Result<Argument, anyhow::Error> {
+        Ok(self.tx.move_call(
+            Function::new(
+                Address::from_hex("0x2")?,
+                Identifier::new("coin")?,
+                Identifier::new("into_balance")?,
+                vec![coin_type],
+            ),
+            vec![coin],
+        ))
+    }
+
+    pub async fn collect_fees_and_rewards(
+        &mut self,
+        request: CollectFeesAndRewardsRequest,
+    ) -> Result<Vec<CollectFeesAndRewardsResult>, anyhow::Error> {
+        let mut result = vec![];
+        let global_config_arg = self.global_config_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        if request.is_collect_fees {
+            // returns (amount_a, amount_b, balance_a, balance_b)
+            let fees = self.tx.move_call(
+                Function::new(
+                    Address::from_hex(&self.config.bluefin.package_id)?,
+                    Identifier::new("pool")?,
+                    Identifier::new("collect_fee")?,
+                    vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+                ),
+                vec![clock_arg, global_config_arg, request.pool_arg, request.position],
+            );
+            let coin_a = self.coin_from_balance(request.coin_a_type.clone(), argument::nested(fees, 2)?)?;
+            let coin_b = self.coin_from_balance(request.coin_b_type.clone(), argument::nested(fees, 3)?)?;
+            result.push(CollectFeesAndRewardsResult {
+                coin_type: request.coin_a_type.clone(),
+                coin: coin_a,
+            });
+            result.push(CollectFeesAndRewardsResult {
+                coin_type: request.coin_b_type.clone(),
+                coin: coin_b,
+            });
+        }
+        if request.is_collect_rewards {
+            for rewarder_coin_type in &request.rewarder_coin_types {
+                let reward_balance = self.tx.move_call(
+                    Function::new(
+                        Address::from_hex(&self.config.bluefin.package_id)?,
+                        Identifier::new("pool")?,
+                        Identifier::new("collect_reward")?,
+                        vec![
+                            request.coin_a_type.clone(),
+                            request.coin_b_type.clone(),
+                            rewarder_coin_type.clone(),
+                        ],
+                    ),
+                    vec![clock_arg, global_config_arg, request.pool_arg, request.position],
+                );
+                let reward_coin = self.coin_from_balance(rewarder_coin_type.clone(), reward_balance)?;
+                result.push(CollectFeesAndRewardsResult {
+                    coin_type: rewarder_coin_type.clone(),
+                    coin: reward_coin,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    pub async fn swap_by_amount_in(
+        &mut self,
+        swap_request: SwapByAmountInRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        swap::swap_by_amount_in(self.client, self.tx, self.arg_cache, self.config, swap_request).await
+    }
+
+    pub async fn zap_out(
+        &mut self,
+        request: ZapOutRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        let global_config_arg = self.global_config_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        let (amount_a, amount_b) = clmm_math::calculate_amounts_by_liquidity(clmm_math::CalculateAmountsByLiquidityRequest {
+            tick_lower_index: request.tick_lower_index,
+            tick_upper_index: request.tick_upper_index,
+            sqrt_price_x64: request.sqrt_price_x64,
+            liquidity: request.liquidity,
+        })?;
+        let liquidity_arg = argument::pure(&mut self.tx, request.liquidity)?;
+        // returns (amount_a, amount_b, balance_a, balance_b)
+        let removed = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.bluefin.package_id)?,
+                Identifier::new("pool")?,
+                Identifier::new("remove_liquidity")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                global_config_arg,
+                request.pool_arg,
+                request.position,
+                liquidity_arg,
+                clock_arg,
+            ],
+        );
+        let coin_a = self.coin_from_balance(request.coin_a_type.clone(), argument::nested(removed, 2)?)?;
+        let coin_b = self.coin_from_balance(request.coin_b_type.clone(), argument::nested(removed, 3)?)?;
+
+        // the position is empty at this point, closing it hands back the residual balances
+        let residual = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.bluefin.package_id)?,
+                Identifier::new("pool")?,
+                Identifier::new("close_position_v2")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![clock_arg, global_config_arg, request.pool_arg, request.position],
+        );
+        let residual_a = self.coin_from_balance(request.coin_a_type.clone(), argument::nested(residual, 0)?)?;
+        let residual_b = self.coin_from_balance(request.coin_b_type.clone(), argument::nested(residual, 1)?)?;
+        self.tx.merge_coins(coin_a, vec![residual_a]);
+        self.tx.merge_coins(coin_b, vec![residual_b]);
+        // remove_liquidity takes no minimum amounts, check what came out instead
+        let min_amount_a = min_amount_with_slippage(amount_a, request.slippage_tolerance);
+        let min_amount_b = min_amount_with_slippage(amount_b, request.slippage_tolerance);
+        assert_coin_value(self.tx, self.config, request.coin_a_type.clone(), coin_a, min_amount_a)?;
+        assert_coin_value(self.tx, self.config, request.coin_b_type.clone(), coin_b, min_amount_b)?;
+
+        let (coin_a_swap, amount_a_swap) = self
+            .swap_by_amount_in(SwapByAmountInRequest::new(
+                request.coin_b_type.clone(),
+                request.coin_a_type.clone(),
+                amount_b,
+                coin_b,
+                None,
+                request.slippage_tolerance,
+            ))
+            .await?;
+        self.tx.merge_coins(coin_a, vec![coin_a_swap]);
+        Ok((coin_a, amount_a_swap + amount_a))
+    }
+
+    pub async fn zap_in(
+        &mut self,
+        request: ZapInRequest,
+    ) -> Result<(Argument, Argument, Argument), anyhow::Error> {
+        let global_config_arg = self.global_config_arg().await?;
+        // ticks are passed as the u32 bits of the i32 index
+        let tick_lower_index_arg = argument::pure(&mut self.tx, request.tick_lower_index as u32)?;
+        let tick_upper_index_arg = argument::pure(&mut self.tx, request.tick_upper_index as u32)?;
+        let position = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.bluefin.package_id)?,
+                Identifier::new("pool")?,
+                Identifier::new("open_position")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                global_config_arg,
+                request.pool_arg,
+                tick_lower_index_arg,
+                tick_upper_index_arg,
+            ],
+        );
+        let (coin_a, coin_b) = self
+            .add_liquidity(AddLiquidityRequest::from_zap_in(position, &request))
+            .await?;
+        Ok((position, coin_a, coin_b))
+    }
+
+    pub async fn add_liquidity(
+        &mut self,
+        request: AddLiquidityRequest,
+    ) -> Result<(Argument, Argument), anyhow::Error> {
+        let (zap, route_data) = clmm_math::calculate_add_liquidity_only_coin_a_liquidity(clmm_math::CalculateAddLiquidityOnlyCoinARequest {
+            tick_lower_index: request.tick_lower_index,
+            tick_upper_index: request.tick_upper_index,
+            sqrt_price_x64: request.sqrt_price_x64,
+            pool_liquidity: request.pool_liquidity,
+            pool_ticks: request.pool_ticks.clone(),
+            fee_rate: request.fee_rate,
+            coin_a_amount: request.coin_a_amount,
+            coin_a_type: request.coin_a_type.clone(),
+            coin_b_type: request.coin_b_type.clone(),
+            max_remain_rate: self.config.zap_max_remain_rate,
+        }).await?;
+        let coin_a_amount_added = zap.amount_a_added;
+        let coin_a_swap_amount = argument::pure(&mut self.tx, zap.swap_amount_a)?;
+        let coin_a_swap = self.tx.split_coins(request.coin_a, vec![coin_a_swap_amount]);
+        let (coin_b_swap, _) = self.swap_by_amount_in(SwapByAmountInRequest::new(
+            request.coin_a_type.clone(),
+            request.coin_b_type.clone(),
+            zap.swap_amount_a,
+            coin_a_swap,
+            route_data,
+            request.slippage_tolerance,
+        )).await?;
+
+        // add_liquidity_with_fixed_amount takes no minimum amounts, check the inputs instead
+        let coin_a_amount_limit = min_amount_with_slippage(coin_a_amount_added, request.slippage_tolerance);
+        let coin_b_amount_limit = min_amount_with_slippage(zap.amount_b_added, request.slippage_tolerance);
+        assert_coin_value(self.tx, self.config, request.coin_a_type.clone(), request.coin_a, coin_a_amount_limit)?;
+        assert_coin_value(self.tx, self.config, request.coin_b_type.clone(), coin_b_swap, coin_b_amount_limit)?;
+
+        let global_config_arg = self.global_config_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        let amount_a_input = argument::pure(&mut self.tx, coin_a_amount_added)?;
+        let coin_a_input = self.tx.split_coins(request.coin_a, vec![amount_a_input]);
+        let balance_a = self.coin_into_balance(request.coin_a_type.clone(), coin_a_input)?;
+        let balance_b = self.coin_into_balance(request.coin_b_type.clone(), coin_b_swap)?;
+        let true_arg = argument::pure(&mut self.tx, true)?;
+        // returns (amount_a, amount_b, leftover_balance_a, leftover_balance_b)
+        let added = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.bluefin.package_id)?,
+                Identifier::new("pool")?,
+                Identifier::new("add_liquidity_with_fixed_amount")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                clock_arg,
+                global_config_arg,
+                request.pool_arg,
+                request.position,
+                balance_a,
+                balance_b,
+                amount_a_input,
+                true_arg,
+            ],
+        );
+        let leftover_a = self.coin_from_balance(request.coin_a_type.clone(), argument::nested(added, 2)?)?;
+        let leftover_b = self.coin_from_balance(request.coin_b_type.clone(), argument::nested(added, 3)?)?;
+        self.tx.merge_coins(request.coin_a, vec![leftover_a]);
+
+        Ok((request.coin_a, leftover_b))
+    }
+}
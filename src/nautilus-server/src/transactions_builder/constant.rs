@@ -25,3 +25,44 @@ pub const CETUS_PARTNER_ID: &str =
     "0x639b5e433da31739e800cd085f356e64cae222966d0f1b11bd9dc76b322ff58b";
 pub const CETUS_AGGREGATOR_V1_PACKAGE_ID: &str =
     "0x8ae871505a80d8bf6bf9c05906cda6edfeea460c85bebe2e26a4313f5e67874a";
+pub const CETUS_POSITION_TYPE: &str =
+    "0x1eabed72c53feb3805120a081dc15963c204dc8d091542592abaf7a35689b2fb::position::Position";
+pub const CETUS_SWAP_EVENT_TYPE: &str =
+    "0x1eabed72c53feb3805120a081dc15963c204dc8d091542592abaf7a35689b2fb::pool::SwapEvent";
+
+// mmt
+
+pub const MMT_PACKAGE_ID: &str =
+    "0xc84b1ef2ac2ba5c3018e2b8c956ba5d0391e0e46d1daa1926d5a99a6a42526b4";
+pub const MMT_VERSION_ID: &str =
+    "0x2375a0b1ec12010aaea3b2545acfa2ad34cfbba03ce4b59f4c39e1e25eed1b2a";
+pub const MMT_POSITION_TYPE: &str =
+    "0x70285592c97965e811e0c6f98dccc3a9c2b4ad854b3594faab9597ada267b860::position::Position";
+pub const MMT_SWAP_EVENT_TYPE: &str =
+    "0x70285592c97965e811e0c6f98dccc3a9c2b4ad854b3594faab9597ada267b860::trade::SwapEvent";
+
+// bluefin
+
+pub const BLUEFIN_PACKAGE_ID: &str =
+    "0x3492c874c1e3b3e2984e8c41b589e642d4d0a5d6459e5a9cfc2d52fd7c89c267";
+pub const BLUEFIN_GLOBAL_CONFIG_ID: &str =
+    "0x03db251ba509a8d5d8777b6338836082335d93eecbdd09a11e190a1cff51c352";
+pub const BLUEFIN_POSITION_TYPE: &str =
+    "0x3492c874c1e3b3e2984e8c41b589e642d4d0a5d6459e5a9cfc2d52fd7c89c267::position::Position";
+pub const BLUEFIN_SWAP_EVENT_TYPE: &str =
+    "0x3492c874c1e3b3e2984e8c41b589e642d4d0a5d6459e5a9cfc2d52fd7c89c267::events::AssetSwap";
+
+// flowx
+
+pub const FLOWX_PACKAGE_ID: &str =
+    "0x25929e7f29e0a30eb4e692952ba1b5b65a3a4d65ab5f2a32e1ba3edcb587f26d";
+pub const FLOWX_POOL_REGISTRY_ID: &str =
+    "0x27565d24a4cd51127ac90e4074a841bbe356cca7bf5759ddc14a975be1632abc";
+pub const FLOWX_POSITION_REGISTRY_ID: &str =
+    "0x7dffe3229d675645564273aa68c67406b6a80aa29e245ac78283acd7ed5e4912";
+pub const FLOWX_VERSIONED_ID: &str =
+    "0x67624a1533b5aff5d0dfcf5e598684350efd38134d2d245f475524c03a64e656";
+pub const FLOWX_POSITION_TYPE: &str =
+    "0x25929e7f29e0a30eb4e692952ba1b5b65a3a4d65ab5f2a32e1ba3edcb587f26d::position::Position";
+pub const FLOWX_SWAP_EVENT_TYPE: &str =
+    "0x25929e7f29e0a30eb4e692952ba1b5b65a3a4d65ab5f2a32e1ba3edcb587f26d::pool::Swap";
@@ -0,0 +1,334 @@
+use sui_sdk_types::Address;
+use sui_sdk_types::Identifier;
+use sui_transaction_builder::{Function, TransactionBuilder};
+
+use crate::config::AppConfig;
+use crate::math::clmm_math;
+use crate::transactions_builder::argument;
+use crate::transactions_builder::argument::ArgCache;
+use crate::transactions_builder::swap;
+use crate::transactions_builder::types::*;
+use crate::transactions_builder::min_amount_with_slippage;
+use crate::transactions_builder::CLOCK_OBJECT_ID;
+use sui_graphql_client::Client;
+use sui_sdk_types::Argument;
+
+/// Builder for FlowX v3 positions. FlowX pools are not shared objects, every call goes
+/// through the shared `PoolRegistry`, which is what `pool_arg` holds for this DEX.
+pub struct FlowXTransactionBuilder<'a> {
+    pub client: &'a Client,
+    pub tx: &'a mut TransactionBuilder,
+    pub arg_cache: &'a mut ArgCache,
+    pub config: &'a AppConfig,
+}
+
+impl<'a> FlowXTransactionBuilder<'a> {
+    pub async fn new(
+        client: &'a Client,
+        tx: &'a mut TransactionBuilder,
+        arg_cache: &'a mut ArgCache,
+        config: &'a AppConfig,
+    ) -> Self {
+        Self {
+            client,
+            tx,
+            arg_cache,
+            config,
+        }
+    }
+
+    async fn versioned_arg(&mut self) -> Result<Argument, anyhow::Error> {
+        argument::shared_ref_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(&self.config.flowx.versioned_id)?,
+        )
+        .await
+    }
+
+    async fn clock_arg(&mut self) -> Result<Argument, anyhow::Error> {
+        argument::shared_ref_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(CLOCK_OBJECT_ID)?,
+        )
+        .await
+    }
+
+    // FlowX ticks are `flowx_clmm::i32::I32`, built from the two's complement bits.
+    fn i32_from_tick(&mut self, tick: i32) -> Result<Argument, anyhow::Error> {
+        let tick_arg = argument::pure(&mut self.tx, tick as u32)?;
+        Ok(self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.package_id)?,
+                Identifier::new("i32")?,
+                Identifier::new("from_u32")?,
+                vec![],
+            ),
+            vec![tick_arg],
+        ))
+    }
+
+    pub async fn collect_fees_and_rewards(
+        &mut self,
+        request: CollectFeesAndRewardsRequest,
+    ) -> Result<Vec<CollectFeesAndRewardsResult>, anyhow::Error> {
+        let mut result = vec![];
+        let versioned_arg = self.versioned_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        // requesting u64::MAX collects everything owed to the position
+        let amount_max_arg = argument::pure(&mut self.tx, u64::MAX)?;
+        if request.is_collect_fees {
+            let fees = self.tx.move_call(
+                Function::new(
+                    Address::from_hex(&self.config.flowx.package_id)?,
+                    Identifier::new("position_manager")?,
+                    Identifier::new("collect")?,
+                    vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+                ),
+                vec![
+                    request.pool_arg,
+                    request.position,
+                    amount_max_arg,
+                    amount_max_arg,
+                    versioned_arg,
+                    clock_arg,
+                ],
+            );
+            result.push(CollectFeesAndRewardsResult {
+                coin_type: request.coin_a_type.clone(),
+                coin: argument::nested(fees, 0)?,
+            });
+            result.push(CollectFeesAndRewardsResult {
+                coin_type: request.coin_b_type.clone(),
+                coin: argument::nested(fees, 1)?,
+            });
+        }
+        if request.is_collect_rewards {
+            for rewarder_coin_type in &request.rewarder_coin_types {
+                let reward_coin = self.tx.move_call(
+                    Function::new(
+                        Address::from_hex(&self.config.flowx.package_id)?,
+                        Identifier::new("position_manager")?,
+                        Identifier::new("collect_pool_reward")?,
+                        vec![
+                            request.coin_a_type.clone(),
+                            request.coin_b_type.clone(),
+                            rewarder_coin_type.clone(),
+                        ],
+                    ),
+                    vec![
+                        request.pool_arg,
+                        request.position,
+                        amount_max_arg,
+                        versioned_arg,
+                        clock_arg,
+                    ],
+                );
+                result.push(CollectFeesAndRewardsResult {
+                    coin_type: rewarder_coin_type.clone(),
+                    coin: reward_coin,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    pub async fn swap_by_amount_in(
+        &mut self,
+        swap_request: SwapByAmountInRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        swap::swap_by_amount_in(self.client, self.tx, self.arg_cache, self.config, swap_request).await
+    }
+
+    pub async fn zap_out(
+        &mut self,
+        request: ZapOutRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        let versioned_arg = self.versioned_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        let (amount_a, amount_b) = clmm_math::calculate_amounts_by_liquidity(clmm_math::CalculateAmountsByLiquidityRequest {
+            tick_lower_index: request.tick_lower_index,
+            tick_upper_index: request.tick_upper_index,
+            sqrt_price_x64: request.sqrt_price_x64,
+            liquidity: request.liquidity,
+        })?;
+        let liquidity_arg = argument::pure(&mut self.tx, request.liquidity)?;
+        let min_amount_a = min_amount_with_slippage(amount_a, request.slippage_tolerance);
+        let min_amount_b = min_amount_with_slippage(amount_b, request.slippage_tolerance);
+        let min_amount_a_arg = argument::pure(&mut self.tx, min_amount_a)?;
+        let min_amount_b_arg = argument::pure(&mut self.tx, min_amount_b)?;
+        let deadline_arg = argument::pure(&mut self.tx, u64::MAX)?;
+        // decreasing liquidity credits the amounts to the position, `collect` pays them out
+        self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.package_id)?,
+                Identifier::new("position_manager")?,
+                Identifier::new("decrease_liquidity")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                request.pool_arg,
+                request.position,
+                liquidity_arg,
+                min_amount_a_arg,
+                min_amount_b_arg,
+                deadline_arg,
+                versioned_arg,
+                clock_arg,
+            ],
+        );
+        let amount_max_arg = argument::pure(&mut self.tx, u64::MAX)?;
+        let result = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.package_id)?,
+                Identifier::new("position_manager")?,
+                Identifier::new("collect")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                request.pool_arg,
+                request.position,
+                amount_max_arg,
+                amount_max_arg,
+                versioned_arg,
+                clock_arg,
+            ],
+        );
+        let coin_a = argument::nested(result, 0)?;
+        let coin_b = argument::nested(result, 1)?;
+        let position_registry_arg = argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(&self.config.flowx.position_registry_id)?,
+        )
+        .await?;
+        self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.package_id)?,
+                Identifier::new("position_manager")?,
+                Identifier::new("close_position")?,
+                vec![],
+            ),
+            vec![position_registry_arg, request.position, versioned_arg],
+        );
+        let (coin_a_swap, amount_a_swap) = self
+            .swap_by_amount_in(SwapByAmountInRequest::new(
+                request.coin_b_type.clone(),
+                request.coin_a_type.clone(),
+                amount_b,
+                coin_b,
+                None,
+                request.slippage_tolerance,
+            ))
+            .await?;
+        self.tx.merge_coins(coin_a, vec![coin_a_swap]);
+        Ok((coin_a, amount_a_swap + amount_a))
+    }
+
+    pub async fn zap_in(
+        &mut self,
+        request: ZapInRequest,
+    ) -> Result<(Argument, Argument, Argument), anyhow::Error> {
+        let versioned_arg = self.versioned_arg().await?;
+        let position_registry_arg = argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(&self.config.flowx.position_registry_id)?,
+        )
+        .await?;
+        let fee_rate_arg = argument::pure(&mut self.tx, request.fee_rate)?;
+        let tick_lower_index = self.i32_from_tick(request.tick_lower_index)?;
+        let tick_upper_index = self.i32_from_tick(request.tick_upper_index)?;
+        let position = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.package_id)?,
+                Identifier::new("position_manager")?,
+                Identifier::new("open_position")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                position_registry_arg,
+                request.pool_arg,
+                fee_rate_arg,
+                tick_lower_index,
+                tick_upper_index,
+                versioned_arg,
+            ],
+        );
+        let (coin_a, coin_b) = self
+            .add_liquidity(AddLiquidityRequest::from_zap_in(position, &request))
+            .await?;
+        Ok((position, coin_a, coin_b))
+    }
+
+    pub async fn add_liquidity(
+        &mut self,
+        request: AddLiquidityRequest,
+    ) -> Result<(Argument, Argument), anyhow::Error> {
+        let (zap, route_data) = clmm_math::calculate_add_liquidity_only_coin_a_liquidity(clmm_math::CalculateAddLiquidityOnlyCoinARequest {
+            tick_lower_index: request.tick_lower_index,
+            tick_upper_index: request.tick_upper_index,
+            sqrt_price_x64: request.sqrt_price_x64,
+            pool_liquidity: request.pool_liquidity,
+            pool_ticks: request.pool_ticks.clone(),
+            fee_rate: request.fee_rate,
+            coin_a_amount: request.coin_a_amount,
+            coin_a_type: request.coin_a_type.clone(),
+            coin_b_type: request.coin_b_type.clone(),
+            max_remain_rate: self.config.zap_max_remain_rate,
+        }).await?;
+        let (coin_a_amount_added, coin_b_amount_added) = (zap.amount_a_added, zap.amount_b_added);
+        let coin_a_swap_amount = argument::pure(&mut self.tx, zap.swap_amount_a)?;
+        let coin_a_swap = self.tx.split_coins(request.coin_a, vec![coin_a_swap_amount]);
+        let (coin_b_swap, _) = self.swap_by_amount_in(SwapByAmountInRequest::new(
+            request.coin_a_type.clone(),
+            request.coin_b_type.clone(),
+            zap.swap_amount_a,
+            coin_a_swap,
+            route_data,
+            request.slippage_tolerance,
+        )).await?;
+
+        let versioned_arg = self.versioned_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        // increase_liquidity consumes both coins and refunds the unused part to the sender,
+        // so only the amounts sized for the range are handed over
+        let amount_a_input = argument::pure(&mut self.tx, coin_a_amount_added)?;
+        let coin_a_input = self.tx.split_coins(request.coin_a, vec![amount_a_input]);
+        let amount_b_input = argument::pure(&mut self.tx, coin_b_amount_added)?;
+        let coin_b_input = self.tx.split_coins(coin_b_swap, vec![amount_b_input]);
+        let min_amount_a =
+            min_amount_with_slippage(coin_a_amount_added, request.slippage_tolerance);
+        let min_amount_b =
+            min_amount_with_slippage(coin_b_amount_added, request.slippage_tolerance);
+        let min_amount_a_arg = argument::pure(&mut self.tx, min_amount_a)?;
+        let min_amount_b_arg = argument::pure(&mut self.tx, min_amount_b)?;
+        let deadline_arg = argument::pure(&mut self.tx, u64::MAX)?;
+        self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.package_id)?,
+                Identifier::new("position_manager")?,
+                Identifier::new("increase_liquidity")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                request.pool_arg,
+                request.position,
+                coin_a_input,
+                coin_b_input,
+                min_amount_a_arg,
+                min_amount_b_arg,
+                deadline_arg,
+                versioned_arg,
+                clock_arg,
+            ],
+        );
+
+        Ok((request.coin_a, coin_b_swap))
+    }
+}
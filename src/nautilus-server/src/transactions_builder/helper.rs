@@ -1,6 +1,9 @@
 use std::time::{Duration, Instant};
 use sui_crypto::{ed25519::Ed25519PrivateKey, SuiSigner};
-use sui_sdk_types::{Address, ExecutionStatus, TransactionEffects};
+use sui_sdk_types::{
+    Address, ExecutionError, ExecutionStatus, ObjectReference, Transaction, TransactionEffects,
+    UserSignature,
+};
 use sui_transaction_builder::{unresolved::Input, TransactionBuilder};
 use thiserror::Error;
 use cynic::QueryBuilder;
@@ -29,10 +32,63 @@ pub enum SuiUtilsError {
     TransactionBuildingError(String),
     #[error("Error while signing transaction")]
     TransactionSigningError(String),
-    #[error("Error while executing transaction")]
-    TransactionExecutionError(String),
+    #[error("Error while executing transaction: {message}")]
+    TransactionExecutionError {
+        message: String,
+        /// Failed execution status, `None` when the transaction never executed.
+        status: Option<ExecutionStatus>,
+        abort: Option<MoveAbort>,
+    },
     #[error("Could not get transaction effects")]
     InvalidTransactionEffects,
+    #[error("Preflight rejected the transaction: {0}")]
+    PreflightRejected(String),
+}
+
+impl SuiUtilsError {
+    /// Execution error raised before the transaction produced effects.
+    pub fn execution(message: impl Into<String>) -> Self {
+        SuiUtilsError::TransactionExecutionError {
+            message: message.into(),
+            status: None,
+            abort: None,
+        }
+    }
+
+    /// Execution error of a transaction that ran and failed with `status`.
+    pub fn execution_failed(status: &ExecutionStatus) -> Self {
+        SuiUtilsError::TransactionExecutionError {
+            message: format!("Transaction failed: {:?}", status),
+            status: Some(status.clone()),
+            abort: MoveAbort::from_status(status),
+        }
+    }
+}
+
+/// Where and with which code a Move call aborted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveAbort {
+    pub package: String,
+    pub module: String,
+    pub function: Option<String>,
+    pub code: u64,
+}
+
+impl MoveAbort {
+    pub fn from_status(status: &ExecutionStatus) -> Option<Self> {
+        match status {
+            ExecutionStatus::Failure {
+                error: ExecutionError::MoveAbort { location, code },
+                ..
+            } => Some(MoveAbort {
+                package: location.package.to_string(),
+                module: location.module.to_string(),
+                function: location.function_name.as_ref().map(|name| name.to_string()),
+                code: *code,
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl From<sui_graphql_client::error::Error> for SuiUtilsError {
@@ -49,33 +105,78 @@ impl From<Vec<cynic::GraphQlError>> for SuiUtilsError {
 
 pub type Result<T> = std::result::Result<T, SuiUtilsError>;
 
+/// Most gas coins a transaction may pay with.
+const MAX_GAS_COINS: usize = 256;
+
 pub async fn new_with_gas(
     client: &Client,
     caller: Address,
     gas_budget: u64,
 ) -> Result<TransactionBuilder> {
-    let mut builder = TransactionBuilder::new();
     // get all sui coins
     let sui_coins =
         get_owned_coins(client, caller, Some("0x2::coin::Coin<0x2::sui::SUI>")).await?;
-    // find the coin with the minimum balance according to the budget
-    let gas_coin = sui_coins
+    let balances = sui_coins.iter().map(|c| c.balance()).collect::<Vec<_>>();
+    let gas_coin_ids = select_gas_coins(&balances, gas_budget)
+        .ok_or(SuiUtilsError::GasCoinNotFound)?
+        .into_iter()
+        .map(|i| sui_coins[i].id().to_owned().into())
+        .collect::<Vec<Address>>();
+    // build the gas inputs from the coins, the network merges them into the first one
+    let gas_objects = get_multi(client, gas_coin_ids.clone()).await?;
+    if gas_objects.len() != gas_coin_ids.len() {
+        return Err(SuiUtilsError::InvalidGasInput);
+    }
+    let gas_inputs = gas_objects
         .iter()
-        .find(|c| c.balance() >= gas_budget)
-        .ok_or(SuiUtilsError::GasCoinNotFound)?;
-    // build the gas input from the coin
-    let gas_input: Input = (&client
-        .object(gas_coin.id().to_owned().into(), None)
-        .await?
-        .ok_or(SuiUtilsError::InvalidGasInput)?)
-        .into();
+        .map(|object| Input::from(object).with_owned_kind())
+        .collect::<Vec<_>>();
+    new_with_gas_inputs(client, caller, gas_inputs, gas_budget).await
+}
+
+/// Builder for a transaction sent by `sender` whose gas is paid from the coins of
+/// `sponsor`, which co-signs it.
+pub async fn new_sponsored(
+    client: &Client,
+    sender: Address,
+    sponsor: Address,
+    gas_budget: u64,
+) -> Result<TransactionBuilder> {
+    let mut builder = new_with_gas(client, sponsor, gas_budget).await?;
+    builder.set_sender(sender);
+    builder.set_sponsor(sponsor);
+    Ok(builder)
+}
+
+/// Builder paying with gas coins whose object refs the caller already knows, e.g. a
+/// coin leased from the gas pool, without reading them from the network.
+pub async fn new_with_gas_coins(
+    client: &Client,
+    caller: Address,
+    gas_coins: &[ObjectReference],
+    gas_budget: u64,
+) -> Result<TransactionBuilder> {
+    let gas_inputs = gas_coins
+        .iter()
+        .map(|coin| Input::owned(*coin.object_id(), coin.version(), *coin.digest()))
+        .collect::<Vec<_>>();
+    new_with_gas_inputs(client, caller, gas_inputs, gas_budget).await
+}
+
+async fn new_with_gas_inputs(
+    client: &Client,
+    caller: Address,
+    gas_inputs: Vec<Input>,
+    gas_budget: u64,
+) -> Result<TransactionBuilder> {
+    let mut builder = TransactionBuilder::new();
     // get the reference gas price
     let gas_price = client
         .reference_gas_price(None)
         .await?
         .ok_or(SuiUtilsError::ReferenceGasPriceError)?;
 
-    builder.add_gas_objects(vec![gas_input.with_owned_kind()]);
+    builder.add_gas_objects(gas_inputs);
     builder.set_gas_price(gas_price);
     builder.set_gas_budget(gas_budget);
     builder.set_sender(caller);
@@ -83,6 +184,40 @@ pub async fn new_with_gas(
     Ok(builder)
 }
 
+/// Indices of the coins to pay a `gas_budget` with: the first coin covering it alone,
+/// otherwise the largest coins until their sum does.
+pub fn select_gas_coins(balances: &[u64], gas_budget: u64) -> Option<Vec<usize>> {
+    if let Some(i) = balances.iter().position(|balance| *balance >= gas_budget) {
+        return Some(vec![i]);
+    }
+    let mut by_balance = (0..balances.len()).collect::<Vec<_>>();
+    by_balance.sort_by_key(|i| std::cmp::Reverse(balances[*i]));
+    let mut selected = vec![];
+    let mut total = 0u64;
+    for i in by_balance.into_iter().take(MAX_GAS_COINS) {
+        selected.push(i);
+        total = total.saturating_add(balances[i]);
+        if total >= gas_budget {
+            return Some(selected);
+        }
+    }
+    None
+}
+
+/// Budget for a transaction whose simulation used `computation_cost` and `storage_cost`,
+/// raised by `margin_bps` and capped at `max_gas_budget`. The storage rebate is only
+/// credited after execution, so it does not lower the budget.
+pub fn estimate_gas_budget(
+    computation_cost: u64,
+    storage_cost: u64,
+    margin_bps: u64,
+    max_gas_budget: u64,
+) -> u64 {
+    let cost = computation_cost as u128 + storage_cost as u128;
+    let budget = cost * (10000 + margin_bps as u128) / 10000;
+    budget.min(max_gas_budget as u128) as u64
+}
+
 pub async fn execute_and_wait_for_effects(
     client: &Client,
     builder: TransactionBuilder,
@@ -90,67 +225,99 @@ pub async fn execute_and_wait_for_effects(
     dry_run: bool,
     skip_checks: Option<bool>,
 ) -> Result<TransactionEffects> {
+    let (tx, sig) = sign(builder, pk)?;
+    if dry_run {
+        self::dry_run(client, &tx, skip_checks).await?;
+    }
+    let effects = execute(client, &tx, vec![sig]).await?;
+    wait_for_finalization(client, &tx).await?;
+    Ok(effects)
+}
+
+/// Finish the transaction and sign it with `pk`.
+pub fn sign(builder: TransactionBuilder, pk: &Ed25519PrivateKey) -> Result<(Transaction, UserSignature)> {
+    let tx = finish(builder)?;
+    let sig = sign_transaction(&tx, pk)?;
+    Ok((tx, sig))
+}
+
+pub fn finish(builder: TransactionBuilder) -> Result<Transaction> {
     let tx = builder
         .finish()
         .map_err(|e| SuiUtilsError::TransactionBuildingError(e.to_string()))?;
-    let sig = pk
-        .sign_transaction(&tx)
-        .map_err(|e| SuiUtilsError::TransactionSigningError(e.to_string()))?;
-    
+
     let _tx_bytes = base64ct::Base64::encode_string(&bcs::to_bytes(&tx).map_err(|e| SuiUtilsError::TransactionBuildingError(e.to_string()))?);
 
     println!("tx: {:?}", tx);
+    Ok(tx)
+}
 
-    if dry_run {
-        println!("dry running");
-        match client.dry_run_tx(&tx, skip_checks).await {
-            Ok(result) => {
-                println!("dry run result: {:?}", result);
-                if let Some(effects) = result.effects {
-                    let status = effects.status();
-                    if status == &ExecutionStatus::Success {
-                        println!("dry run success");
-                    } else {
-                        println!("dry run failed");
-                        return Err(SuiUtilsError::TransactionExecutionError("Dry run failed".to_string()));
-                    }
+pub fn sign_transaction(tx: &Transaction, pk: &Ed25519PrivateKey) -> Result<UserSignature> {
+    pk.sign_transaction(tx)
+        .map_err(|e| SuiUtilsError::TransactionSigningError(e.to_string()))
+}
+
+/// Simulate `tx`, failing unless it would succeed.
+pub async fn dry_run(client: &Client, tx: &Transaction, skip_checks: Option<bool>) -> Result<TransactionEffects> {
+    println!("dry running");
+    match client.dry_run_tx(tx, skip_checks).await {
+        Ok(result) => {
+            println!("dry run result: {:?}", result);
+            if let Some(effects) = result.effects {
+                let status = effects.status();
+                if status == &ExecutionStatus::Success {
+                    println!("dry run success");
+                    Ok(effects)
                 } else {
-                    return Err(SuiUtilsError::TransactionExecutionError("Dry run failed - no effects".to_string()));
+                    println!("dry run failed");
+                    Err(SuiUtilsError::execution_failed(status))
                 }
+            } else {
+                Err(SuiUtilsError::execution("Dry run failed - no effects"))
             }
-            Err(e) => {
-                println!("dry run error: {:?}", e);
-                return Err(SuiUtilsError::TransactionExecutionError(format!("Dry run failed: {:?}", e)));
-            }
         }
+        Err(e) => {
+            println!("dry run error: {:?}", e);
+            Err(SuiUtilsError::execution(format!("Dry run failed: {:?}", e)))
+        }
+    }
+}
+
+/// Submit `tx` with the sender signature, followed by the sponsor one when it is
+/// sponsored, and return its effects, failing if the execution did not succeed.
+pub async fn execute(
+    client: &Client,
+    tx: &Transaction,
+    signatures: Vec<UserSignature>,
+) -> Result<TransactionEffects> {
+    let effects = client
+        .execute_tx(signatures, tx)
+        .await?
+        .ok_or(SuiUtilsError::InvalidTransactionEffects)?;
+    let status = effects.status();
+    if status == &ExecutionStatus::Success {
+        Ok(effects)
+    } else {
+        Err(SuiUtilsError::execution_failed(status))
     }
+}
 
-    let effects = client.execute_tx(vec![sig], &tx).await?;
-    // wait for the transaction to be finalized with timeout
+/// Wait until the executed `tx` can be read back from the network.
+pub async fn wait_for_finalization(client: &Client, tx: &Transaction) -> Result<()> {
     let timeout_duration: Duration = Duration::from_secs(30); // 30 seconds timeout
     let start_time = Instant::now();
-    match effects {
-        Some(effects) => {
-            let status = effects.status();
-            if status == &ExecutionStatus::Success {
-                while client.transaction(tx.digest()).await?.is_none() {
-                    if start_time.elapsed() > timeout_duration {
-                        return Err(SuiUtilsError::TransactionExecutionError(
-                            "Transaction finalization timeout".to_string()
-                        ));
-                    }   
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                }
-                Ok(effects)
-            } else {
-                Err(SuiUtilsError::TransactionExecutionError(format!(
-                    "Transaction failed: {:?}",
-                    status
-                )))
-            }
+    while client.transaction(tx.digest()).await?.is_none() {
+        if start_time.elapsed() > timeout_duration {
+            return Err(SuiUtilsError::execution("Transaction finalization timeout"));
         }
-        None => Err(SuiUtilsError::InvalidTransactionEffects),
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
+    Ok(())
+}
+
+/// Move abort code of a failed execution, `None` if it failed for another reason.
+pub fn abort_code(status: &ExecutionStatus) -> Option<u64> {
+    MoveAbort::from_status(status).map(|abort| abort.code)
 }
 
 pub async fn get(client: &Client, id: Address) -> Result<Object> {
@@ -256,6 +423,37 @@ pub async fn get_owned(
     Ok(objects)
 }
 
+/// Every object of Move type `type_`, whoever owns it.
+pub async fn get_by_type(client: &Client, type_: &str) -> Result<Vec<Object>> {
+    let mut objects = Vec::new();
+    let mut cursor = None;
+    let mut has_next_page = true;
+
+    while has_next_page {
+        let filter = PaginationFilter {
+            direction: Direction::Forward,
+            cursor: cursor.clone(),
+            limit: Some(50),
+        };
+
+        let resp = client
+            .objects(
+                Some(ObjectFilter {
+                    type_: Some(type_),
+                    ..Default::default()
+                }),
+                filter,
+            )
+            .await?;
+        objects.extend(resp.data().iter().cloned());
+
+        cursor = resp.page_info().end_cursor.clone();
+        has_next_page = resp.page_info().has_next_page;
+    }
+
+    Ok(objects)
+}
+
 pub async fn get_owned_coins(
     client: &Client,
     owner: Address,
@@ -354,4 +552,24 @@ pub fn tick_to_i32(tick: u32) -> i32 {
     } else {
         tick as i32
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select_gas_coins() {
+        assert_eq!(select_gas_coins(&[5, 20, 30], 10), Some(vec![1]));
+        // no single coin covers 40, the largest ones are merged
+        assert_eq!(select_gas_coins(&[5, 20, 30], 40), Some(vec![2, 1]));
+        assert_eq!(select_gas_coins(&[5, 20, 30], 60), None);
+        assert_eq!(select_gas_coins(&[], 1), None);
+    }
+
+    #[test]
+    fn test_estimate_gas_budget() {
+        assert_eq!(estimate_gas_budget(1000, 3000, 2000, 100000), 4800);
+        assert_eq!(estimate_gas_budget(1000, 3000, 2000, 4000), 4000);
+    }
+}
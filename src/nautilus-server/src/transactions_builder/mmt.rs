@@ -0,0 +1,274 @@
+use sui_sdk_types::Address;
+use sui_sdk_types::Identifier;
+use sui_transaction_builder::{Function, TransactionBuilder};
+
+use crate::config::AppConfig;
+use crate::math::clmm_math;
+use crate::transactions_builder::argument;
+use crate::transactions_builder::argument::ArgCache;
+use crate::transactions_builder::swap;
+use crate::transactions_builder::types::*;
+use crate::transactions_builder::min_amount_with_slippage;
+use crate::transactions_builder::CLOCK_OBJECT_ID;
+use sui_graphql_client::Client;
+use sui_sdk_types::Argument;
+
+pub struct MmtTransactionBuilder<'a> {
+    pub client: &'a Client,
+    pub tx: &'a mut TransactionBuilder,
+    pub arg_cache: &'a mut ArgCache,
+    pub config: &'a AppConfig,
+}
+
+impl<'a> MmtTransactionBuilder<'a> {
+    pub async fn new(
+        client: &'a Client,
+        tx: &'a mut TransactionBuilder,
+        arg_cache: &'a mut ArgCache,
+        config: &'a AppConfig,
+    ) -> Self {
+        Self {
+            client,
+            tx,
+            arg_cache,
+            config,
+        }
+    }
+
+    async fn version_arg(&mut self) -> Result<Argument, anyhow::Error> {
+        argument::shared_ref_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(&self.config.mmt.version_id)?,
+        )
+        .await
+    }
+
+    async fn clock_arg(&mut self) -> Result<Argument, anyhow::Error> {
+        argument::shared_ref_cached(
+            self.client,
+            &mut self.tx,
+            self.arg_cache,
+            Address::from_hex(CLOCK_OBJECT_ID)?,
+        )
+        .await
+    }
+
+    // MMT ticks are `mmt_v3::i32::I32`, built from the two's complement bits.
+    fn i32_from_tick(&mut self, tick: i32) -> Result<Argument, anyhow::Error> {
+        let tick_arg = argument::pure(&mut self.tx, tick as u32)?;
+        Ok(self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.mmt.package_id)?,
+                Identifier::new("i32")?,
+                Identifier::new("from_u32")?,
+                vec![],
+            ),
+            vec![tick_arg],
+        ))
+    }
+
+    pub async fn collect_fees_and_rewards(
+        &mut self,
+        request: CollectFeesAndRewardsRequest,
+    ) -> Result<Vec<CollectFeesAndRewardsResult>, anyhow::Error> {
+        let mut result = vec![];
+        let version_arg = self.version_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        if request.is_collect_fees {
+            let fees = self.tx.move_call(
+                Function::new(
+                    Address::from_hex(&self.config.mmt.package_id)?,
+                    Identifier::new("collect")?,
+                    Identifier::new("fee")?,
+                    vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+                ),
+                vec![request.pool_arg, request.position, clock_arg, version_arg],
+            );
+            result.push(CollectFeesAndRewardsResult {
+                coin_type: request.coin_a_type.clone(),
+                coin: argument::nested(fees, 0)?,
+            });
+            result.push(CollectFeesAndRewardsResult {
+                coin_type: request.coin_b_type.clone(),
+                coin: argument::nested(fees, 1)?,
+            });
+        }
+        if request.is_collect_rewards {
+            for rewarder_coin_type in &request.rewarder_coin_types {
+                let reward_coin = self.tx.move_call(
+                    Function::new(
+                        Address::from_hex(&self.config.mmt.package_id)?,
+                        Identifier::new("collect")?,
+                        Identifier::new("reward")?,
+                        vec![
+                            request.coin_a_type.clone(),
+                            request.coin_b_type.clone(),
+                            rewarder_coin_type.clone(),
+                        ],
+                    ),
+                    vec![request.pool_arg, request.position, clock_arg, version_arg],
+                );
+                result.push(CollectFeesAndRewardsResult {
+                    coin_type: rewarder_coin_type.clone(),
+                    coin: reward_coin,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    pub async fn swap_by_amount_in(
+        &mut self,
+        swap_request: SwapByAmountInRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        swap::swap_by_amount_in(self.client, self.tx, self.arg_cache, self.config, swap_request).await
+    }
+
+    pub async fn zap_out(
+        &mut self,
+        request: ZapOutRequest,
+    ) -> Result<(Argument, u64), anyhow::Error> {
+        let version_arg = self.version_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        let (amount_a, amount_b) = clmm_math::calculate_amounts_by_liquidity(clmm_math::CalculateAmountsByLiquidityRequest {
+            tick_lower_index: request.tick_lower_index,
+            tick_upper_index: request.tick_upper_index,
+            sqrt_price_x64: request.sqrt_price_x64,
+            liquidity: request.liquidity,
+        })?;
+        let liquidity_arg = argument::pure(&mut self.tx, request.liquidity)?;
+        let min_amount_a = min_amount_with_slippage(amount_a, request.slippage_tolerance);
+        let min_amount_b = min_amount_with_slippage(amount_b, request.slippage_tolerance);
+        let min_amount_a_arg = argument::pure(&mut self.tx, min_amount_a)?;
+        let min_amount_b_arg = argument::pure(&mut self.tx, min_amount_b)?;
+        let result = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.mmt.package_id)?,
+                Identifier::new("liquidity")?,
+                Identifier::new("remove_liquidity")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                request.pool_arg,
+                request.position,
+                liquidity_arg,
+                min_amount_a_arg,
+                min_amount_b_arg,
+                clock_arg,
+                version_arg,
+            ],
+        );
+        let coin_a = argument::nested(result, 0)?;
+        let coin_b = argument::nested(result, 1)?;
+        self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.mmt.package_id)?,
+                Identifier::new("liquidity")?,
+                Identifier::new("close_position")?,
+                vec![],
+            ),
+            vec![request.position, version_arg],
+        );
+        // coin_b holds whatever remove_liquidity returned, at least min_amount_b: quote
+        // the route for that floor and let its last leg swap the whole coin, so rounding
+        // leaves no dust and a short coin cannot abort the split
+        let (coin_a_swap, amount_a_swap) = self
+            .swap_by_amount_in(SwapByAmountInRequest::new(
+                request.coin_b_type.clone(),
+                request.coin_a_type.clone(),
+                min_amount_b,
+                coin_b,
+                None,
+                request.slippage_tolerance,
+            ))
+            .await?;
+        self.tx.merge_coins(coin_a, vec![coin_a_swap]);
+        Ok((coin_a, amount_a_swap + amount_a))
+    }
+
+    pub async fn zap_in(
+        &mut self,
+        request: ZapInRequest,
+    ) -> Result<(Argument, Argument, Argument), anyhow::Error> {
+        let version_arg = self.version_arg().await?;
+        let tick_lower_index = self.i32_from_tick(request.tick_lower_index)?;
+        let tick_upper_index = self.i32_from_tick(request.tick_upper_index)?;
+        let position = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.mmt.package_id)?,
+                Identifier::new("liquidity")?,
+                Identifier::new("open_position")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![request.pool_arg, tick_lower_index, tick_upper_index, version_arg],
+        );
+        let (coin_a, coin_b) = self
+            .add_liquidity(AddLiquidityRequest::from_zap_in(position, &request))
+            .await?;
+        Ok((position, coin_a, coin_b))
+    }
+
+    pub async fn add_liquidity(
+        &mut self,
+        request: AddLiquidityRequest,
+    ) -> Result<(Argument, Argument), anyhow::Error> {
+        let (zap, route_data) = clmm_math::calculate_add_liquidity_only_coin_a_liquidity(clmm_math::CalculateAddLiquidityOnlyCoinARequest {
+            tick_lower_index: request.tick_lower_index,
+            tick_upper_index: request.tick_upper_index,
+            sqrt_price_x64: request.sqrt_price_x64,
+            pool_liquidity: request.pool_liquidity,
+            pool_ticks: request.pool_ticks.clone(),
+            fee_rate: request.fee_rate,
+            coin_a_amount: request.coin_a_amount,
+            coin_a_type: request.coin_a_type.clone(),
+            coin_b_type: request.coin_b_type.clone(),
+            max_remain_rate: self.config.zap_max_remain_rate,
+        }).await?;
+        let (coin_a_amount_added, coin_b_amount_added) = (zap.amount_a_added, zap.amount_b_added);
+        let coin_a_swap_amount = argument::pure(&mut self.tx, zap.swap_amount_a)?;
+        let coin_a_swap = self.tx.split_coins(request.coin_a, vec![coin_a_swap_amount]);
+        let (coin_b_swap, _) = self.swap_by_amount_in(SwapByAmountInRequest::new(
+            request.coin_a_type.clone(),
+            request.coin_b_type.clone(),
+            zap.swap_amount_a,
+            coin_a_swap,
+            route_data,
+            request.slippage_tolerance,
+        )).await?;
+
+        let version_arg = self.version_arg().await?;
+        let clock_arg = self.clock_arg().await?;
+        // add_liquidity takes the coins by value and hands back what it did not use
+        let amount_a_input = argument::pure(&mut self.tx, coin_a_amount_added)?;
+        let coin_a_input = self.tx.split_coins(request.coin_a, vec![amount_a_input]);
+        let min_amount_a =
+            min_amount_with_slippage(coin_a_amount_added, request.slippage_tolerance);
+        let min_amount_b =
+            min_amount_with_slippage(coin_b_amount_added, request.slippage_tolerance);
+        let min_amount_a_arg = argument::pure(&mut self.tx, min_amount_a)?;
+        let min_amount_b_arg = argument::pure(&mut self.tx, min_amount_b)?;
+        let leftovers = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.mmt.package_id)?,
+                Identifier::new("liquidity")?,
+                Identifier::new("add_liquidity")?,
+                vec![request.coin_a_type.clone(), request.coin_b_type.clone()],
+            ),
+            vec![
+                request.pool_arg,
+                request.position,
+                coin_a_input,
+                coin_b_swap,
+                min_amount_a_arg,
+                min_amount_b_arg,
+                clock_arg,
+                version_arg,
+            ],
+        );
+        self.tx.merge_coins(request.coin_a, vec![argument::nested(leftovers, 0)?]);
+
+        Ok((request.coin_a, argument::nested(leftovers, 1)?))
+    }
+}
@@ -1,43 +1,107 @@
 use crate::{
-    parsers::{BalancesBag, CompoundRequest, RebalanceRequest, SupportedDex},
-    transactions_builder::constant::{
-        CLOCK_OBJECT_ID, GLOBAL_CONFIG_OBJECT_ID, INTEGER_MATE_PACKAGE_ID, KURAGE_PACKAGE_ID, REGISTRY_OBJECT_ID
-    },
+    config::AppConfig,
+    dex::{self, DexContext},
+    math::swap_math::PoolTicks,
+    parsers::{BalancesBag, CompoundRequest, PendingRewards, RebalanceRequest, SupportedDex},
+    transactions_builder::constant::CLOCK_OBJECT_ID,
 };
 use std::str::FromStr;
 use sui_graphql_client::Client;
-use sui_sdk_types::{Address, Identifier, StructTag, TypeTag};
+use sui_sdk_types::{Address, Argument, Identifier, ObjectReference, StructTag, TypeTag};
 use sui_transaction_builder::{Function, TransactionBuilder};
 pub mod argument;
+pub mod bluefin;
 pub mod cetus;
 pub mod constant;
+pub mod flowx;
 pub mod helper;
+pub mod mmt;
+pub mod preflight;
+pub mod retry;
 pub mod swap;
+pub mod types;
 
-// array of position types
-pub fn get_position_types(dex: SupportedDex) -> &'static str {
-    match dex {
-        SupportedDex::Cetus => {
-            "0x1eabed72c53feb3805120a081dc15963c204dc8d091542592abaf7a35689b2fb::position::Position"
-        }
-        _ => panic!("Unsupported dex"),
-    }
+// position type of the registered adapter for `dex`
+pub fn get_position_types(config: &AppConfig, dex: SupportedDex) -> Result<String, anyhow::Error> {
+    Ok(dex::get_adapter(dex)?.position_type(config))
+}
+
+/// Lowest acceptable outcome for an expected `amount` under `slippage_tolerance_bps`.
+/// Tolerances above 100% are capped, so the minimum never goes below zero.
+pub fn min_amount_with_slippage(amount: u64, slippage_tolerance_bps: u64) -> u64 {
+    let kept_bps = 10000 - slippage_tolerance_bps.min(10000) as u128;
+    // at most `amount`, so it fits back into a u64
+    (amount as u128 * kept_bps / 10000) as u64
+}
+
+/// Abort the transaction unless `coin` holds at least `min_value`.
+pub fn assert_coin_value(
+    tx: &mut TransactionBuilder,
+    config: &AppConfig,
+    coin_type: TypeTag,
+    coin: Argument,
+    min_value: u64,
+) -> Result<(), anyhow::Error> {
+    let min_value_arg = argument::pure(tx, min_value)?;
+    tx.move_call(
+        Function::new(
+            Address::from_hex(&config.kurage.package_id)?,
+            Identifier::new("utils")?,
+            Identifier::new("assert_coin_value")?,
+            vec![coin_type],
+        ),
+        vec![coin, min_value_arg],
+    );
+    Ok(())
 }
 
 pub struct DexTransactionBuilder<'a> {
     tx: TransactionBuilder,
     client: &'a Client,
+    config: &'a AppConfig,
     arg_cache: argument::ArgCache,
 }
 
 impl<'a> DexTransactionBuilder<'a> {
-    pub async fn new(client: &'a Client, caller: Address, gas_budget: u64) -> Self {
-        let tx = helper::new_with_gas(client, caller, gas_budget)
-            .await
-            .unwrap();
+    pub async fn new(
+        client: &'a Client,
+        config: &'a AppConfig,
+        caller: Address,
+        gas_budget: u64,
+    ) -> helper::Result<Self> {
+        let tx = helper::new_with_gas(client, caller, gas_budget).await?;
+        Ok(Self::from_builder(tx, client, config))
+    }
+
+    /// Builder whose gas is paid by `sponsor` instead of `caller`.
+    pub async fn sponsored(
+        client: &'a Client,
+        config: &'a AppConfig,
+        caller: Address,
+        sponsor: Address,
+        gas_budget: u64,
+    ) -> helper::Result<Self> {
+        let tx = helper::new_sponsored(client, caller, sponsor, gas_budget).await?;
+        Ok(Self::from_builder(tx, client, config))
+    }
+
+    /// Builder paying with `gas_coins` instead of picking a coin of `caller`.
+    pub async fn with_gas_coins(
+        client: &'a Client,
+        config: &'a AppConfig,
+        caller: Address,
+        gas_coins: &[ObjectReference],
+        gas_budget: u64,
+    ) -> helper::Result<Self> {
+        let tx = helper::new_with_gas_coins(client, caller, gas_coins, gas_budget).await?;
+        Ok(Self::from_builder(tx, client, config))
+    }
+
+    fn from_builder(tx: TransactionBuilder, client: &'a Client, config: &'a AppConfig) -> Self {
         Self {
             tx,
             client,
+            config,
             arg_cache: argument::ArgCache::default(),
         }
     }
@@ -51,6 +115,9 @@ impl<'a> DexTransactionBuilder<'a> {
         new_tick_lower_index: u32,
         new_tick_upper_index: u32,
         liquidity: u128,
+        fee_rate: u64,
+        pool_liquidity: u128,
+        pool_ticks: PoolTicks,
         position_registry_id: u64,
         dex: SupportedDex,
         enclave_id: String,
@@ -59,74 +126,74 @@ impl<'a> DexTransactionBuilder<'a> {
         lp_slippage_tolerance_bps: u64,
         balances_bag: BalancesBag,
         rewarder_coin_types: Vec<TypeTag>,
-    ) -> TransactionBuilder {
-        let pos_type = get_position_types(dex);
+    ) -> Result<TransactionBuilder, anyhow::Error> {
+        let adapter = dex::get_adapter(dex)?;
+        let pos_type = adapter.position_type(self.config);
+        let pos_type = pos_type.as_str();
 
         let global_config_arg = argument::shared_mut_cached(
             self.client,
             &mut self.tx,
             &mut self.arg_cache,
-            Address::from_hex(GLOBAL_CONFIG_OBJECT_ID).unwrap(),
+            Address::from_hex(&self.config.kurage.global_config_object_id)?,
         )
-        .await
-        .unwrap();
+        .await?;
         let registry_arg = argument::shared_mut_cached(
             self.client,
             &mut self.tx,
             &mut self.arg_cache,
-            Address::from_hex(REGISTRY_OBJECT_ID).unwrap(),
+            Address::from_hex(&self.config.kurage.registry_object_id)?,
         )
-        .await
-        .unwrap();
+        .await?;
         let strategy_arg = argument::shared_mut_cached(
             self.client,
             &mut self.tx,
             &mut self.arg_cache,
             request.strategy_id.clone(),
         )
-        .await
-        .unwrap();
+        .await?;
         let enclave_arg = argument::shared_mut_cached(
             self.client,
             &mut self.tx,
             &mut self.arg_cache,
-            Address::from_hex(&enclave_id).unwrap(),
+            Address::from_hex(&enclave_id)?,
         )
-        .await
-        .unwrap();
-        let timestamp_arg = argument::pure(&mut self.tx, timestamp_ms).unwrap();
-        let signature_arg = argument::pure(&mut self.tx, signature).unwrap();
+        .await?;
+        let timestamp_arg = argument::pure(&mut self.tx, timestamp_ms)?;
+        let signature_arg = argument::pure(&mut self.tx, signature)?;
         let clock_arg = argument::shared_ref_cached(
             self.client,
             &mut self.tx,
             &mut self.arg_cache,
-            Address::from_hex(CLOCK_OBJECT_ID).unwrap(),
+            Address::from_hex(CLOCK_OBJECT_ID)?,
         )
-        .await
-        .unwrap();
-        let pool_arg = argument::shared_mut_cached(
-            self.client,
-            &mut self.tx,
-            &mut self.arg_cache,
-            Address::from_hex(&pool_id).unwrap(),
-        )
-        .await
-        .unwrap();
-        let strategy_id_arg = argument::pure(&mut self.tx, request.strategy_id.clone()).unwrap();
-        let current_tick_arg = argument::pure(&mut self.tx, request.current_tick_u32).unwrap();
+        .await?;
+        let pool_arg = adapter
+            .pool_arg(
+                &mut DexContext {
+                    client: self.client,
+                    tx: &mut self.tx,
+                    arg_cache: &mut self.arg_cache,
+                    config: self.config,
+                },
+                &pool_id,
+            )
+            .await?;
+        let strategy_id_arg = argument::pure(&mut self.tx, request.strategy_id.clone())?;
+        let current_tick_arg = argument::pure(&mut self.tx, request.current_tick_u32)?;
         let current_sqrt_price_arg =
-            argument::pure(&mut self.tx, request.current_sqrt_price).unwrap();
-        let tick_spacing_arg = argument::pure(&mut self.tx, request.tick_spacing).unwrap();
+            argument::pure(&mut self.tx, request.current_sqrt_price)?;
+        let tick_spacing_arg = argument::pure(&mut self.tx, request.tick_spacing)?;
         let tick_lower_index_arg =
-            argument::pure(&mut self.tx, request.tick_lower_index_u32).unwrap();
+            argument::pure(&mut self.tx, request.tick_lower_index_u32)?;
         let tick_upper_index_arg =
-            argument::pure(&mut self.tx, request.tick_upper_index_u32).unwrap();
+            argument::pure(&mut self.tx, request.tick_upper_index_u32)?;
 
         let construct_req = self.tx.move_call(
             Function::new(
-                Address::from_hex(KURAGE_PACKAGE_ID).unwrap(),
-                Identifier::new("auto_rebalance").unwrap(),
-                Identifier::new("new_auto_rebalance_request").unwrap(),
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("auto_rebalance")?,
+                Identifier::new("new_auto_rebalance_request")?,
                 vec![],
             ),
             vec![
@@ -141,11 +208,11 @@ impl<'a> DexTransactionBuilder<'a> {
 
         let prepare_rebalance_data = self.tx.move_call(
             Function::new(
-                Address::from_hex(KURAGE_PACKAGE_ID).unwrap(),
-                Identifier::new("auto_rebalance").unwrap(),
-                Identifier::new("prepare_rebalance_bot").unwrap(),
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("auto_rebalance")?,
+                Identifier::new("prepare_rebalance_bot")?,
                 vec![TypeTag::Struct(Box::new(
-                    StructTag::from_str(pos_type).unwrap(),
+                    StructTag::from_str(pos_type)?,
                 ))],
             ),
             vec![
@@ -160,68 +227,69 @@ impl<'a> DexTransactionBuilder<'a> {
             ],
         );
 
-        let pos = prepare_rebalance_data.nested(0).unwrap();
-        let receipt = prepare_rebalance_data.nested(1).unwrap();
-        let coin_receipt = prepare_rebalance_data.nested(2).unwrap();
+        let pos = argument::nested(prepare_rebalance_data, 0)?;
+        let receipt = argument::nested(prepare_rebalance_data, 1)?;
+        let coin_receipt = argument::nested(prepare_rebalance_data, 2)?;
 
-        let position = match dex {
-            SupportedDex::Cetus => {
-                let mut cetus_tx = cetus::CetusTransactionBuilder::new(
-                    self.client,
-                    &mut self.tx,
-                    &mut self.arg_cache,
-                )
-                .await;
-                cetus_tx
-                    .rebalance(cetus::RebalanceData::new(
-                        pos,
-                        pool_arg,
-                        coin_a_type.clone(),
-                        coin_b_type.clone(),
-                        helper::tick_to_i32(request.tick_lower_index_u32),
-                        helper::tick_to_i32(request.tick_upper_index_u32),
-                        request.current_sqrt_price,
-                        liquidity,
-                        helper::tick_to_i32(new_tick_lower_index),
-                        helper::tick_to_i32(new_tick_upper_index),
-                        lp_slippage_tolerance_bps,
-                        coin_receipt,
-                        rewarder_coin_types,
-                    ))
-                    .await
-            }
-            _ => panic!("Unsupported dex"),
+        let mut ctx = DexContext {
+            client: self.client,
+            tx: &mut self.tx,
+            arg_cache: &mut self.arg_cache,
+            config: self.config,
         };
+        let position = adapter
+            .rebalance(
+                &mut ctx,
+                types::RebalanceData::new(
+                    pos,
+                    pool_arg,
+                    coin_a_type.clone(),
+                    coin_b_type.clone(),
+                    helper::tick_to_i32(request.tick_lower_index_u32),
+                    helper::tick_to_i32(request.tick_upper_index_u32),
+                    request.current_sqrt_price,
+                    liquidity,
+                    helper::tick_to_i32(new_tick_lower_index),
+                    helper::tick_to_i32(new_tick_upper_index),
+                    fee_rate,
+                    pool_liquidity,
+                    pool_ticks,
+                    lp_slippage_tolerance_bps,
+                    coin_receipt,
+                    rewarder_coin_types,
+                ),
+            )
+            .await?;
 
-        let tick_lower_index_arg = argument::pure(&mut self.tx, new_tick_lower_index).unwrap();
-        let tick_upper_index_arg = argument::pure(&mut self.tx, new_tick_upper_index).unwrap();
+        let tick_lower_index_arg = argument::pure(&mut self.tx, new_tick_lower_index)?;
+        let tick_upper_index_arg = argument::pure(&mut self.tx, new_tick_upper_index)?;
         let tick_lower_index = self.tx.move_call(
             Function::new(
-                Address::from_hex(INTEGER_MATE_PACKAGE_ID).unwrap(),
-                Identifier::new("i32").unwrap(),
-                Identifier::new("from_u32").unwrap(),
+                Address::from_hex(&self.config.kurage.integer_mate_package_id)?,
+                Identifier::new("i32")?,
+                Identifier::new("from_u32")?,
                 vec![],
             ),
             vec![tick_lower_index_arg],
         );
         let tick_upper_index = self.tx.move_call(
             Function::new(
-                Address::from_hex(INTEGER_MATE_PACKAGE_ID).unwrap(),
-                Identifier::new("i32").unwrap(),
-                Identifier::new("from_u32").unwrap(),
+                Address::from_hex(&self.config.kurage.integer_mate_package_id)?,
+                Identifier::new("i32")?,
+                Identifier::new("from_u32")?,
                 vec![],
             ),
             vec![tick_upper_index_arg],
         );
-        let position_registry_id_arg = argument::pure(&mut self.tx, position_registry_id).unwrap();
+        let position_registry_id_arg = argument::pure(&mut self.tx, position_registry_id)?;
 
         self.tx.move_call(
             Function::new(
-                Address::from_hex(KURAGE_PACKAGE_ID).unwrap(),
-                Identifier::new("registry").unwrap(),
-                Identifier::new("return_position").unwrap(),
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("registry")?,
+                Identifier::new("return_position")?,
                 vec![TypeTag::Struct(Box::new(
-                    StructTag::from_str(pos_type).unwrap(),
+                    StructTag::from_str(pos_type)?,
                 ))],
             ),
             vec![registry_arg, position, position_registry_id_arg],
@@ -229,11 +297,11 @@ impl<'a> DexTransactionBuilder<'a> {
 
         self.tx.move_call(
             Function::new(
-                Address::from_hex(KURAGE_PACKAGE_ID).unwrap(),
-                Identifier::new("auto_rebalance").unwrap(),
-                Identifier::new("repay_receipt").unwrap(),
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("auto_rebalance")?,
+                Identifier::new("repay_receipt")?,
                 vec![TypeTag::Struct(Box::new(
-                    StructTag::from_str(pos_type).unwrap(),
+                    StructTag::from_str(pos_type)?,
                 ))],
             ),
             vec![
@@ -246,27 +314,200 @@ impl<'a> DexTransactionBuilder<'a> {
             ],
         );
 
-        self.tx
+        Ok(self.tx)
     }
 
     pub async fn compound(
         mut self,
         request: CompoundRequest,
+        pool_id: String,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        fee_rate: u64,
+        pool_liquidity: u128,
+        pool_ticks: PoolTicks,
+        position_registry_id: u64,
         dex: SupportedDex,
+        enclave_id: String,
         signature: Vec<u8>,
-    ) -> TransactionBuilder {
-        match dex {
-            SupportedDex::Cetus => {
-                let mut cetus_tx = cetus::CetusTransactionBuilder::new(
-                    self.client,
-                    &mut self.tx,
-                    &mut self.arg_cache,
-                )
-                .await;
-                cetus_tx.compound(request, signature).await;
-            }
-            _ => panic!("Unsupported dex"),
-        }
-        self.tx
+        timestamp_ms: u64,
+        lp_slippage_tolerance_bps: u64,
+        rewarder_coin_types: Vec<TypeTag>,
+        pending_rewards: PendingRewards,
+    ) -> Result<TransactionBuilder, anyhow::Error> {
+        let adapter = dex::get_adapter(dex)?;
+        let pos_type = adapter.position_type(self.config);
+        let pos_type = pos_type.as_str();
+
+        let global_config_arg = argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            &mut self.arg_cache,
+            Address::from_hex(&self.config.kurage.global_config_object_id)?,
+        )
+        .await?;
+        let registry_arg = argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            &mut self.arg_cache,
+            Address::from_hex(&self.config.kurage.registry_object_id)?,
+        )
+        .await?;
+        let strategy_arg = argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            &mut self.arg_cache,
+            request.strategy_id.clone(),
+        )
+        .await?;
+        let enclave_arg = argument::shared_mut_cached(
+            self.client,
+            &mut self.tx,
+            &mut self.arg_cache,
+            Address::from_hex(&enclave_id)?,
+        )
+        .await?;
+        let timestamp_arg = argument::pure(&mut self.tx, timestamp_ms)?;
+        let signature_arg = argument::pure(&mut self.tx, signature)?;
+        let clock_arg = argument::shared_ref_cached(
+            self.client,
+            &mut self.tx,
+            &mut self.arg_cache,
+            Address::from_hex(CLOCK_OBJECT_ID)?,
+        )
+        .await?;
+        let pool_arg = adapter
+            .pool_arg(
+                &mut DexContext {
+                    client: self.client,
+                    tx: &mut self.tx,
+                    arg_cache: &mut self.arg_cache,
+                    config: self.config,
+                },
+                &pool_id,
+            )
+            .await?;
+        let strategy_id_arg = argument::pure(&mut self.tx, request.strategy_id.clone())?;
+        let current_sqrt_price_arg =
+            argument::pure(&mut self.tx, request.current_sqrt_price)?;
+        let tick_lower_index_arg =
+            argument::pure(&mut self.tx, request.tick_lower_index_u32)?;
+        let tick_upper_index_arg =
+            argument::pure(&mut self.tx, request.tick_upper_index_u32)?;
+        let pending_rewards_value_arg =
+            argument::pure(&mut self.tx, request.pending_rewards_value)?;
+
+        let construct_req = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("auto_rebalance")?,
+                Identifier::new("new_auto_compound_request")?,
+                vec![],
+            ),
+            vec![
+                strategy_id_arg,
+                current_sqrt_price_arg,
+                tick_lower_index_arg,
+                tick_upper_index_arg,
+                pending_rewards_value_arg,
+            ],
+        );
+
+        let prepare_compound_data = self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("auto_rebalance")?,
+                Identifier::new("prepare_compound_bot")?,
+                vec![TypeTag::Struct(Box::new(
+                    StructTag::from_str(pos_type)?,
+                ))],
+            ),
+            vec![
+                strategy_arg,
+                global_config_arg,
+                registry_arg,
+                enclave_arg,
+                timestamp_arg,
+                construct_req,
+                signature_arg,
+                clock_arg,
+            ],
+        );
+
+        let pos = argument::nested(prepare_compound_data, 0)?;
+        let receipt = argument::nested(prepare_compound_data, 1)?;
+        let coin_receipt = argument::nested(prepare_compound_data, 2)?;
+
+        let mut ctx = DexContext {
+            client: self.client,
+            tx: &mut self.tx,
+            arg_cache: &mut self.arg_cache,
+            config: self.config,
+        };
+        adapter
+            .compound(
+                &mut ctx,
+                types::CompoundData::new(
+                    pos,
+                    pool_arg,
+                    coin_a_type.clone(),
+                    coin_b_type.clone(),
+                    helper::tick_to_i32(request.tick_lower_index_u32),
+                    helper::tick_to_i32(request.tick_upper_index_u32),
+                    request.current_sqrt_price,
+                    fee_rate,
+                    pool_liquidity,
+                    pool_ticks,
+                    lp_slippage_tolerance_bps,
+                    coin_receipt,
+                    rewarder_coin_types,
+                    pending_rewards,
+                ),
+            )
+            .await?;
+
+        let position_registry_id_arg = argument::pure(&mut self.tx, position_registry_id)?;
+        self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("registry")?,
+                Identifier::new("return_position")?,
+                vec![TypeTag::Struct(Box::new(
+                    StructTag::from_str(pos_type)?,
+                ))],
+            ),
+            vec![registry_arg, pos, position_registry_id_arg],
+        );
+
+        self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.kurage.package_id)?,
+                Identifier::new("auto_rebalance")?,
+                Identifier::new("repay_compound_receipt")?,
+                vec![TypeTag::Struct(Box::new(
+                    StructTag::from_str(pos_type)?,
+                ))],
+            ),
+            vec![registry_arg, strategy_arg, receipt, clock_arg],
+        );
+
+        Ok(self.tx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_min_amount_with_slippage() {
+        assert_eq!(min_amount_with_slippage(1000, 50), 995);
+        assert_eq!(min_amount_with_slippage(1000, 0), 1000);
+        assert_eq!(min_amount_with_slippage(999, 1), 998);
+        // no overflow on large amounts, no underflow on tolerances above 100%
+        assert_eq!(min_amount_with_slippage(u64::MAX, 0), u64::MAX);
+        assert_eq!(min_amount_with_slippage(u64::MAX, 5000), u64::MAX / 2);
+        assert_eq!(min_amount_with_slippage(1000, 10000), 0);
+        assert_eq!(min_amount_with_slippage(1000, 20000), 0);
     }
 }
@@ -0,0 +1,356 @@
+use crate::config::PreflightConfig;
+use crate::math::full_math_u128;
+use crate::parsers;
+use crate::transactions_builder::helper::{self, SuiUtilsError};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use std::str::FromStr;
+use sui_rpc::field::FieldMask;
+use sui_rpc::proto::sui::rpc::v2beta2::{BalanceChange, Object, SimulateTransactionRequest};
+use sui_sdk_types::{
+    Address, ExecutionStatus, GasCostSummary, Transaction, TransactionEffects, TypeTag,
+};
+
+/// Net change of one coin type in the coins owned by one address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceDelta {
+    pub address: Address,
+    pub coin_type: TypeTag,
+    pub amount: i128,
+}
+
+/// Outcome of simulating a transaction that would succeed.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub effects: TransactionEffects,
+    pub balance_changes: Vec<BalanceDelta>,
+    /// Objects the transaction would write, with their owner and json.
+    pub output_objects: Vec<Object>,
+}
+
+impl Simulation {
+    pub fn gas_used(&self) -> &GasCostSummary {
+        match &self.effects {
+            TransactionEffects::V1(effects) => &effects.gas_used,
+            TransactionEffects::V2(effects) => &effects.gas_used,
+        }
+    }
+
+    /// Computation and storage cost minus the storage rebate, in MIST.
+    pub fn net_gas_used(&self) -> u64 {
+        let gas_used = self.gas_used();
+        (gas_used.computation_cost + gas_used.storage_cost).saturating_sub(gas_used.storage_rebate)
+    }
+
+    /// Json the transaction would leave in `object_id`, `None` if it does not write it.
+    pub fn output_json(&self, object_id: Address) -> Option<&prost_types::Value> {
+        self.output_objects
+            .iter()
+            .find(|object| parse_address(object.object_id.as_deref()) == Some(object_id))
+            .and_then(|object| object.json.as_deref())
+    }
+
+    /// Value the transaction would leave in the dynamic field `name` of `parent`, for
+    /// `u64` keyed tables and bags. `None` if it does not write the field.
+    pub fn output_dynamic_field(&self, parent: Address, name: u64) -> Option<serde_json::Value> {
+        self.output_objects
+            .iter()
+            .filter(|object| {
+                let owner = object.owner.as_ref().and_then(|owner| owner.address.as_deref());
+                parse_address(owner) == Some(parent)
+            })
+            .filter_map(|object| object.json.as_deref().map(parsers::common::to_json_value))
+            .find(|field| match field.get("name") {
+                Some(serde_json::Value::String(key)) => key.parse::<u64>().ok() == Some(name),
+                Some(serde_json::Value::Number(key)) => key.as_u64() == Some(name),
+                _ => false,
+            })
+            .and_then(|mut field| field.get_mut("value").map(serde_json::Value::take))
+    }
+
+    /// Budget to sign the simulated transaction with, see `helper::estimate_gas_budget`.
+    pub fn gas_budget(&self, margin_bps: u64, max_gas_budget: u64) -> u64 {
+        let gas_used = self.gas_used();
+        helper::estimate_gas_budget(
+            gas_used.computation_cost,
+            gas_used.storage_cost,
+            margin_bps,
+            max_gas_budget,
+        )
+    }
+}
+
+/// Simulate the unsigned `tx` against the latest state, failing unless it would succeed.
+pub async fn simulate(
+    client: &mut sui_rpc::Client,
+    tx: &Transaction,
+) -> helper::Result<Simulation> {
+    let response = client
+        .live_data_client()
+        .simulate_transaction(SimulateTransactionRequest {
+            transaction: Some(tx.clone().into()),
+            read_mask: Some(FieldMask {
+                paths: vec![
+                    "transaction.effects.bcs".into(),
+                    "transaction.balance_changes".into(),
+                    "transaction.output_objects.object_id".into(),
+                    "transaction.output_objects.owner".into(),
+                    "transaction.output_objects.json".into(),
+                ],
+            }),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| SuiUtilsError::execution(format!("Simulation failed: {}", e.message())))?
+        .into_inner();
+    let executed = response
+        .transaction
+        .ok_or_else(|| SuiUtilsError::execution("Simulation returned no transaction"))?;
+    let effects: TransactionEffects = executed
+        .effects
+        .and_then(|effects| effects.bcs)
+        .ok_or(SuiUtilsError::InvalidTransactionEffects)?
+        .deserialize()
+        .map_err(|e| SuiUtilsError::execution(format!("Invalid simulated effects: {}", e)))?;
+    let status = effects.status();
+    if status != &ExecutionStatus::Success {
+        return Err(SuiUtilsError::execution_failed(status));
+    }
+    let balance_changes = executed
+        .balance_changes
+        .iter()
+        .map(parse_balance_change)
+        .collect::<helper::Result<Vec<_>>>()?;
+    Ok(Simulation {
+        effects,
+        balance_changes,
+        output_objects: executed.output_objects,
+    })
+}
+
+fn parse_address(address: Option<&str>) -> Option<Address> {
+    address.and_then(|address| Address::from_str(address).ok())
+}
+
+fn parse_balance_change(change: &BalanceChange) -> helper::Result<BalanceDelta> {
+    let invalid = || SuiUtilsError::execution(format!("Invalid balance change: {:?}", change));
+    Ok(BalanceDelta {
+        address: Address::from_str(change.address.as_deref().ok_or_else(invalid)?)
+            .map_err(|_| invalid())?,
+        coin_type: TypeTag::from_str(change.coin_type.as_deref().ok_or_else(invalid)?)
+            .map_err(|_| invalid())?,
+        amount: change
+            .amount
+            .as_deref()
+            .ok_or_else(invalid)?
+            .parse::<i128>()
+            .map_err(|_| invalid())?,
+    })
+}
+
+/// The strategy position the transaction works on, valued in coin A at the pool price.
+#[derive(Debug, Clone)]
+pub struct PositionMark {
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub sqrt_price: u128,
+    pub value_a: u128,
+}
+
+impl PositionMark {
+    pub fn new(
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        sqrt_price: u128,
+        amount_a: u64,
+        amount_b: u64,
+    ) -> Self {
+        let value_a = amount_a as u128 + b_to_a(amount_b as u128, sqrt_price);
+        Self {
+            coin_a_type,
+            coin_b_type,
+            sqrt_price,
+            value_a,
+        }
+    }
+
+    /// Value of a position holding `amount_a` and `amount_b`, in coin A at the mark price.
+    pub fn value_of_amounts(&self, amount_a: u64, amount_b: u64) -> u128 {
+        amount_a as u128 + b_to_a(amount_b as u128, self.sqrt_price)
+    }
+
+    /// Value of `amount` of `coin_type` in coin A, `None` for coins other than A and B.
+    pub fn value_of(&self, coin_type: &TypeTag, amount: u128) -> Option<u128> {
+        if coin_type == &self.coin_a_type {
+            Some(amount)
+        } else if coin_type == &self.coin_b_type {
+            Some(b_to_a(amount, self.sqrt_price))
+        } else {
+            None
+        }
+    }
+}
+
+// price of A in B is sqrt_price^2 / 2^128
+fn b_to_a(amount_b: u128, sqrt_price: u128) -> u128 {
+    if sqrt_price == 0 {
+        return 0;
+    }
+    let value = (BigUint::from(amount_b) << 128) / full_math_u128::full_mul(sqrt_price, sqrt_price);
+    value.to_u128().unwrap_or(u128::MAX)
+}
+
+fn is_sui(coin_type: &TypeTag) -> bool {
+    TypeTag::from_str("0x2::sui::SUI").is_ok_and(|sui| &sui == coin_type)
+}
+
+/// Checks a simulation against the limits of `PreflightConfig`.
+pub struct PreflightPolicy<'a> {
+    config: &'a PreflightConfig,
+}
+
+impl<'a> PreflightPolicy<'a> {
+    pub fn new(config: &'a PreflightConfig) -> Self {
+        Self { config }
+    }
+
+    /// Every limit a simulation with `net_gas_used` and `balance_changes` breaks,
+    /// empty when the transaction may be signed. `gas_owner` is the sender unless the
+    /// transaction is sponsored, it may lose SUI up to the gas used. `position_value_after`
+    /// is what the simulation leaves in the position, valued like `mark`: together with
+    /// the coins paid out it may fall short of the mark by `max_value_loss_bps`.
+    pub fn check(
+        &self,
+        sender: Address,
+        gas_owner: Address,
+        mark: &PositionMark,
+        position_value_after: u128,
+        net_gas_used: u64,
+        balance_changes: &[BalanceDelta],
+    ) -> Vec<String> {
+        let mut violations = vec![];
+        if net_gas_used > self.config.max_gas_mist {
+            violations.push(format!(
+                "gas used {} exceeds the limit of {} MIST",
+                net_gas_used, self.config.max_gas_mist
+            ));
+        }
+
+        let mut paid_out = 0u128;
+        for delta in balance_changes {
+            if (delta.address == sender || delta.address == gas_owner) && delta.amount < 0 {
+                let outflow = delta.amount.unsigned_abs();
+                if delta.address != gas_owner || !is_sui(&delta.coin_type) {
+                    violations.push(format!(
+                        "{} loses {} of {}",
+                        delta.address, outflow, delta.coin_type
+                    ));
+                } else if outflow > net_gas_used as u128 {
+                    violations.push(format!(
+                        "gas owner loses {} MIST, more than the {} MIST of gas",
+                        outflow, net_gas_used
+                    ));
+                }
+            }
+            if delta.amount > 0 {
+                match mark.value_of(&delta.coin_type, delta.amount as u128) {
+                    Some(value) => paid_out += value,
+                    // a coin the mark cannot price has no business leaving the sender
+                    None if delta.address != sender => violations.push(format!(
+                        "{} receives {} of {}, which the position mark cannot price",
+                        delta.address, delta.amount, delta.coin_type
+                    )),
+                    None => {}
+                }
+            }
+        }
+        let position_loss = mark.value_a.saturating_sub(position_value_after);
+        let value_lost = paid_out + position_loss;
+        let max_value_lost = mark.value_a * self.config.max_value_loss_bps as u128 / 10000;
+        if value_lost > max_value_lost {
+            violations.push(format!(
+                "loses {} of coin A value ({} paid out, {} off the position), more than {} ({} bps of {})",
+                value_lost,
+                paid_out,
+                position_loss,
+                max_value_lost,
+                self.config.max_value_loss_bps,
+                mark.value_a
+            ));
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const Q64: u128 = 1 << 64;
+
+    fn coin(name: &str) -> TypeTag {
+        TypeTag::from_str(&format!("0x5::{}::{}", name.to_lowercase(), name)).unwrap()
+    }
+
+    #[test]
+    fn test_position_mark() {
+        // sqrt price 2 means 1 A is worth 4 B
+        let mark = PositionMark::new(coin("A"), coin("B"), 2 * Q64, 100, 400);
+        assert_eq!(mark.value_a, 200);
+        assert_eq!(mark.value_of(&coin("B"), 40), Some(10));
+        assert_eq!(mark.value_of(&coin("C"), 40), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let config = PreflightConfig {
+            max_gas_mist: 1000,
+            max_value_loss_bps: 100,
+        };
+        let policy = PreflightPolicy::new(&config);
+        let sender = Address::from_str("0x1").unwrap();
+        let other = Address::from_str("0x2").unwrap();
+        let mark = PositionMark::new(coin("A"), coin("B"), Q64, 10000, 10000);
+        let sui = TypeTag::from_str("0x2::sui::SUI").unwrap();
+
+        let delta = |address, coin_type: &TypeTag, amount| BalanceDelta {
+            address,
+            coin_type: coin_type.clone(),
+            amount,
+        };
+
+        let ok = [delta(sender, &sui, -800), delta(other, &coin("A"), 150)];
+        assert!(policy.check(sender, sender, &mark, mark.value_a, 800, &ok).is_empty());
+
+        // over the gas limit, sender pays more than gas, loses coin A, and 250 B leaves
+        let bad = [
+            delta(sender, &sui, -1500),
+            delta(sender, &coin("A"), -1),
+            delta(other, &coin("B"), 250),
+        ];
+        assert_eq!(policy.check(sender, sender, &mark, mark.value_a, 1200, &bad).len(), 4);
+
+        // sponsored, the sender pays nothing and the sponsor only gas
+        let sponsor = Address::from_str("0x3").unwrap();
+        let sponsored = [delta(sponsor, &sui, -800), delta(sender, &sui, -1)];
+        assert_eq!(
+            policy.check(sender, sponsor, &mark, mark.value_a, 800, &sponsored).len(),
+            1
+        );
+
+        // a coin the mark cannot price may only go to the sender
+        let reward = [delta(other, &coin("C"), 1), delta(sender, &coin("D"), 1)];
+        assert_eq!(policy.check(sender, sender, &mark, mark.value_a, 800, &reward).len(), 1);
+
+        // the 150 paid out and 40 off the position fit in the 200 allowed, 60 more do not
+        assert!(policy.check(sender, sender, &mark, mark.value_a - 40, 800, &ok).is_empty());
+        assert_eq!(policy.check(sender, sender, &mark, mark.value_a - 100, 800, &ok).len(), 1);
+    }
+
+    #[test]
+    fn test_position_mark_value_of_amounts() {
+        let mark = PositionMark::new(coin("A"), coin("B"), 2 * Q64, 100, 400);
+        assert_eq!(mark.value_of_amounts(100, 400), mark.value_a);
+        assert_eq!(mark.value_of_amounts(0, 40), 10);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::config::{AbortRule, RetryConfig};
+use crate::transactions_builder::helper::SuiUtilsError;
+use std::time::Duration;
+use sui_sdk_types::{ExecutionError, ExecutionStatus};
+
+/// Fragments of submit errors meaning an input object was consumed or locked by
+/// another transaction, so a rebuild against the latest versions goes through.
+const VERSION_CONFLICT_MARKERS: [&str; 5] = [
+    "ObjectVersionUnavailableForConsumption",
+    "not available for consumption",
+    "ObjectLockConflict",
+    "already locked",
+    "congest",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Shared object version conflict, congestion or an unreachable fullnode.
+    Contention,
+    /// A slippage check aborted, the quote the transaction was built from is stale.
+    StaleQuote,
+    /// Fails the same way on every retry, e.g. a cooldown abort or a preflight rejection.
+    Deterministic,
+    /// Anything else, retried with backoff like contention.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Refetch, rebuild and submit again after waiting.
+    Retry(Duration),
+    GiveUp,
+}
+
+/// Decides whether a failed attempt is worth rebuilding and submitting again.
+pub struct RetryPolicy<'a> {
+    config: &'a RetryConfig,
+}
+
+impl<'a> RetryPolicy<'a> {
+    pub fn new(config: &'a RetryConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn classify(&self, err: &SuiUtilsError) -> FailureKind {
+        match err {
+            SuiUtilsError::TransactionExecutionError {
+                abort: Some(abort), ..
+            } => {
+                let matches = |rules: &[AbortRule]| {
+                    rules
+                        .iter()
+                        .any(|rule| rule.matches(&abort.module, abort.code))
+                };
+                if matches(&self.config.fatal_aborts) {
+                    FailureKind::Deterministic
+                } else if matches(&self.config.requote_aborts) {
+                    FailureKind::StaleQuote
+                } else {
+                    FailureKind::Unknown
+                }
+            }
+            SuiUtilsError::TransactionExecutionError {
+                status:
+                    Some(ExecutionStatus::Failure {
+                        error: ExecutionError::ExecutionCancelledDueToSharedObjectCongestion { .. },
+                        ..
+                    }),
+                ..
+            } => FailureKind::Contention,
+            SuiUtilsError::TransactionExecutionError { message, .. }
+                if is_version_conflict(message) =>
+            {
+                FailureKind::Contention
+            }
+            SuiUtilsError::GraphQL(_) | SuiUtilsError::InvalidTransactionEffects => {
+                FailureKind::Contention
+            }
+            SuiUtilsError::TransactionSigningError(_) | SuiUtilsError::PreflightRejected(_) => {
+                FailureKind::Deterministic
+            }
+            _ => FailureKind::Unknown,
+        }
+    }
+
+    /// Decision after `attempt` (starting at 1) failed with `err`.
+    pub fn decide(&self, err: &SuiUtilsError, attempt: u32) -> RetryDecision {
+        if attempt >= self.config.max_attempts {
+            return RetryDecision::GiveUp;
+        }
+        match self.classify(err) {
+            FailureKind::Deterministic => RetryDecision::GiveUp,
+            FailureKind::StaleQuote => RetryDecision::Retry(Duration::ZERO),
+            FailureKind::Contention | FailureKind::Unknown => {
+                RetryDecision::Retry(self.backoff(attempt))
+            }
+        }
+    }
+
+    /// Exponential backoff after `attempt` failed, capped at `max_backoff_ms`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        let backoff_ms = self.config.base_backoff_ms.saturating_mul(factor);
+        Duration::from_millis(backoff_ms.min(self.config.max_backoff_ms))
+    }
+}
+
+fn is_version_conflict(message: &str) -> bool {
+    VERSION_CONFLICT_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transactions_builder::helper::MoveAbort;
+
+    fn aborted(module: &str, code: u64) -> SuiUtilsError {
+        SuiUtilsError::TransactionExecutionError {
+            message: format!("aborted in {}", module),
+            status: None,
+            abort: Some(MoveAbort {
+                package: "0x1".to_string(),
+                module: module.to_string(),
+                function: None,
+                code,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let config = RetryConfig::default();
+        let policy = RetryPolicy::new(&config);
+        assert_eq!(
+            policy.classify(&aborted("auto_rebalance", 7)),
+            FailureKind::Deterministic
+        );
+        assert_eq!(
+            policy.classify(&aborted("utils", 0)),
+            FailureKind::StaleQuote
+        );
+        assert_eq!(policy.classify(&aborted("pool", 3)), FailureKind::Unknown);
+        assert_eq!(
+            policy.classify(&SuiUtilsError::execution(
+                "Object 0x5 version 12 is not available for consumption"
+            )),
+            FailureKind::Contention
+        );
+        assert_eq!(
+            policy.classify(&SuiUtilsError::GraphQL("timeout".to_string())),
+            FailureKind::Contention
+        );
+    }
+
+    #[test]
+    fn test_decide() {
+        let config = RetryConfig {
+            max_attempts: 3,
+            ..RetryConfig::default()
+        };
+        let policy = RetryPolicy::new(&config);
+        assert_eq!(
+            policy.decide(&aborted("registry", 1), 1),
+            RetryDecision::GiveUp
+        );
+        assert_eq!(
+            policy.decide(&aborted("utils", 0), 1),
+            RetryDecision::Retry(Duration::ZERO)
+        );
+        assert_eq!(
+            policy.decide(&SuiUtilsError::GraphQL("timeout".to_string()), 2),
+            RetryDecision::Retry(Duration::from_millis(1000))
+        );
+        assert_eq!(
+            policy.decide(&SuiUtilsError::GraphQL("timeout".to_string()), 3),
+            RetryDecision::GiveUp
+        );
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = RetryConfig::default();
+        let policy = RetryPolicy::new(&config);
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(4), Duration::from_millis(4000));
+        assert_eq!(policy.backoff(30), Duration::from_millis(10000));
+    }
+}
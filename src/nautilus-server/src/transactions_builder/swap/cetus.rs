@@ -3,27 +3,21 @@ use std::ops::Add;
 use sui_graphql_client::query_types::schema::__fields::SystemParameters::stakeSubsidyStartEpoch;
 use sui_graphql_client::Client;
 use sui_transaction_builder::{Function, TransactionBuilder};
+use crate::config::AppConfig;
 use crate::transactions_builder::argument::{self, ArgCache};
-use crate::transactions_builder::constant::{
-    GLOBAL_CONFIG_ID,
-    CETUS_INTEGRATE_PACKAGE_ID,
-    REWARDERS_GLOBAL_VAULT_ID,
-    CETUS_PACKAGE_ID,
-    CETUS_PARTNER_ID,
-    CETUS_AGGREGATOR_V1_PACKAGE_ID,
-    CLOCK_OBJECT_ID,
-};
+use crate::transactions_builder::constant::CLOCK_OBJECT_ID;
 use sui_sdk_types::{Address, Argument, Identifier, TypeTag};
 
 pub struct CetusSwapAdapter<'a> {
     pub client: &'a Client,
     pub tx: &'a mut TransactionBuilder,
     pub arg_cache: &'a mut ArgCache,
+    pub config: &'a AppConfig,
 }
 
 impl<'a> CetusSwapAdapter<'a> {
-    pub fn new(client: &'a Client, tx: &'a mut TransactionBuilder, arg_cache: &'a mut ArgCache) -> Self {
-        Self { client, tx, arg_cache }
+    pub fn new(client: &'a Client, tx: &'a mut TransactionBuilder, arg_cache: &'a mut ArgCache, config: &'a AppConfig) -> Self {
+        Self { client, tx, arg_cache, config }
     }
 
     pub async fn swap_exact_in(
@@ -33,11 +27,11 @@ impl<'a> CetusSwapAdapter<'a> {
         to_type: TypeTag,
         direction: bool,
         coin_arg: Argument,
-    ) -> Argument {
-        let global_config_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(GLOBAL_CONFIG_ID).unwrap()).await.unwrap();
-        let pool_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(pool_id).unwrap()).await.unwrap();
-        let partner_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(CETUS_PARTNER_ID).unwrap()).await.unwrap();
-        let clock_arg = argument::shared_ref_cached(self.client, self.tx, self.arg_cache, Address::from_hex(CLOCK_OBJECT_ID).unwrap()).await.unwrap();
+    ) -> Result<Argument, anyhow::Error> {
+        let global_config_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(&self.config.cetus.global_config_id)?).await?;
+        let pool_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(pool_id)?).await?;
+        let partner_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(&self.config.cetus.partner_id)?).await?;
+        let clock_arg = argument::shared_ref_cached(self.client, self.tx, self.arg_cache, Address::from_hex(CLOCK_OBJECT_ID)?).await?;
         let func_name = if direction {
             "swap_a2b"
         } else {
@@ -48,11 +42,11 @@ impl<'a> CetusSwapAdapter<'a> {
         } else {
             vec![to_type, from_type]
         };
-        self.tx.move_call(
+        Ok(self.tx.move_call(
             Function::new(
-                Address::from_hex(CETUS_AGGREGATOR_V1_PACKAGE_ID).unwrap(),
-                Identifier::new("cetus").unwrap(),
-                Identifier::new(func_name).unwrap(),
+                Address::from_hex(&self.config.cetus.aggregator_v1_package_id)?,
+                Identifier::new("cetus")?,
+                Identifier::new(func_name)?,
                 type_args,
             ),
             vec![
@@ -62,6 +56,6 @@ impl<'a> CetusSwapAdapter<'a> {
                 coin_arg,
                 clock_arg,
             ],
-        )
+        ))
     }
 }
\ No newline at end of file
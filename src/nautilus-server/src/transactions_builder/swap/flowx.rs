@@ -0,0 +1,60 @@
+use sui_graphql_client::Client;
+use sui_transaction_builder::{Function, TransactionBuilder};
+use crate::config::AppConfig;
+use crate::transactions_builder::argument::{self, ArgCache};
+use crate::transactions_builder::constant::CLOCK_OBJECT_ID;
+use sui_sdk_types::{Address, Argument, Identifier, TypeTag};
+
+pub struct FlowXSwapAdapter<'a> {
+    pub client: &'a Client,
+    pub tx: &'a mut TransactionBuilder,
+    pub arg_cache: &'a mut ArgCache,
+    pub config: &'a AppConfig,
+}
+
+impl<'a> FlowXSwapAdapter<'a> {
+    pub fn new(client: &'a Client, tx: &'a mut TransactionBuilder, arg_cache: &'a mut ArgCache, config: &'a AppConfig) -> Self {
+        Self { client, tx, arg_cache, config }
+    }
+
+    // FlowX pools are looked up in the pool registry by coin types and fee rate,
+    // so the hop is identified by `fee_rate` instead of the pool id.
+    pub async fn swap_exact_in(
+        &mut self,
+        fee_rate: u64,
+        from_type: TypeTag,
+        to_type: TypeTag,
+        direction: bool,
+        coin_arg: Argument,
+    ) -> Result<Argument, anyhow::Error> {
+        let pool_registry_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(&self.config.flowx.pool_registry_id)?).await?;
+        let versioned_arg = argument::shared_mut_cached(self.client, self.tx, self.arg_cache, Address::from_hex(&self.config.flowx.versioned_id)?).await?;
+        let clock_arg = argument::shared_ref_cached(self.client, self.tx, self.arg_cache, Address::from_hex(CLOCK_OBJECT_ID)?).await?;
+        let fee_rate_arg = argument::pure(self.tx, fee_rate)?;
+        let func_name = if direction {
+            "swap_a2b"
+        } else {
+            "swap_b2a"
+        };
+        let type_args = if direction {
+            vec![from_type, to_type]
+        } else {
+            vec![to_type, from_type]
+        };
+        Ok(self.tx.move_call(
+            Function::new(
+                Address::from_hex(&self.config.flowx.aggregator_package_id)?,
+                Identifier::new("flowx_clmm")?,
+                Identifier::new(func_name)?,
+                type_args,
+            ),
+            vec![
+                pool_registry_arg,
+                fee_rate_arg,
+                coin_arg,
+                versioned_arg,
+                clock_arg,
+            ],
+        ))
+    }
+}
@@ -1 +1,94 @@
-pub mod cetus;
\ No newline at end of file
+use crate::aggregator;
+use crate::config::AppConfig;
+use crate::EnclaveError;
+use crate::transactions_builder::argument::{self, ArgCache};
+use crate::transactions_builder::{assert_coin_value, min_amount_with_slippage};
+use crate::transactions_builder::types::SwapByAmountInRequest;
+use std::str::FromStr;
+use sui_graphql_client::Client;
+use sui_sdk_types::{Argument, TypeTag};
+use sui_transaction_builder::TransactionBuilder;
+
+pub mod cetus;
+pub mod flowx;
+
+use cetus::CetusSwapAdapter;
+use flowx::FlowXSwapAdapter;
+
+/// Aggregator provider name of FlowX v3 hops, every other hop is routed through Cetus.
+const PROVIDER_FLOWX_V3: &str = "FLOWXV3";
+
+/// Swap `coin_input` along the aggregator routes (fetched when the request carries none)
+/// and return the merged output coin together with the quoted output amount.
+/// Every hop aborts the transaction if it yields less than its quote minus the slippage tolerance.
+/// Shared by every DEX builder, since the aggregator is independent of where the position lives.
+pub async fn swap_by_amount_in(
+    client: &Client,
+    tx: &mut TransactionBuilder,
+    arg_cache: &mut ArgCache,
+    config: &AppConfig,
+    swap_request: SwapByAmountInRequest,
+) -> Result<(Argument, u64), anyhow::Error> {
+    let cetus_aggregator = aggregator::cetus::CetusAggregator::new();
+    let route = match swap_request.route {
+        Some(route) => route,
+        None => cetus_aggregator
+            .swap_by_amount_in(aggregator::cetus::SwapByAmountInRequest {
+                from: swap_request.from.to_string(),
+                to: swap_request.to.to_string(),
+                amount_in: swap_request.amount_in,
+            })
+            .await?
+            .data
+            .ok_or_else(|| EnclaveError::AggregatorFailure(format!(
+                "No route from {} to {}",
+                swap_request.from, swap_request.to
+            )))?,
+    };
+    let coin_input = swap_request.coin_input;
+    let new_coin = argument::zero_coin(tx, swap_request.to)?;
+    for i in 0..route.routes.len() {
+        let swap_route = &route.routes[i];
+        let mut coin_in_this_route = if i == route.routes.len() - 1 {
+            coin_input
+        } else {
+            let swap_amount_in_arg = argument::pure(tx, swap_route.amount_in)?;
+            tx.split_coins(coin_input, vec![swap_amount_in_arg])
+        };
+        for j in 0..swap_route.path.len() {
+            let path = &swap_route.path[j];
+            let from_type = TypeTag::from_str(&path.from)?;
+            let to_type = TypeTag::from_str(&path.target)?;
+            let output_type = to_type.clone();
+            let min_amount_out = min_amount_with_slippage(path.amount_out, swap_request.slippage_tolerance);
+            let coin_output = match path.provider.as_str() {
+                PROVIDER_FLOWX_V3 => {
+                    FlowXSwapAdapter::new(client, tx, arg_cache, config)
+                        .swap_exact_in(
+                            path.fee_rate.parse::<u64>()?,
+                            from_type,
+                            to_type,
+                            path.direction,
+                            coin_in_this_route,
+                        )
+                        .await?
+                }
+                _ => {
+                    CetusSwapAdapter::new(client, tx, arg_cache, config)
+                        .swap_exact_in(
+                            path.id.clone(),
+                            from_type,
+                            to_type,
+                            path.direction,
+                            coin_in_this_route,
+                        )
+                        .await?
+                }
+            };
+            assert_coin_value(tx, config, output_type, coin_output, min_amount_out)?;
+            coin_in_this_route = coin_output;
+        }
+        tx.merge_coins(new_coin, vec![coin_in_this_route]);
+    }
+    Ok((new_coin, route.routes.iter().map(|route| route.amount_out).sum::<u64>()))
+}
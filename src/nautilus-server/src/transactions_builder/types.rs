@@ -0,0 +1,330 @@
+use crate::aggregator;
+use crate::math::swap_math::PoolTicks;
+use crate::parsers::PendingRewards;
+use sui_sdk_types::{Argument, TypeTag};
+
+#[derive(Clone)]
+pub struct ZapOutRequest {
+    pub position: Argument,
+    pub pool_arg: Argument,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub sqrt_price_x64: u128,
+    pub liquidity: u128,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub slippage_tolerance: u64,
+}
+
+impl ZapOutRequest {
+    pub fn new(
+        position: Argument,
+        pool_arg: Argument,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        sqrt_price_x64: u128,
+        liquidity: u128,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        slippage_tolerance: u64,
+    ) -> Self {
+        Self {
+            position,
+            pool_arg,
+            coin_a_type,
+            coin_b_type,
+            sqrt_price_x64,
+            liquidity,
+            tick_lower_index,
+            tick_upper_index,
+            slippage_tolerance,
+        }
+    }
+}
+
+pub struct ZapInRequest {
+    pub coin_a: Argument,
+    pub pool_arg: Argument,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub sqrt_price_x64: u128,
+    pub coin_a_amount: u64,
+    pub fee_rate: u64,
+    pub pool_liquidity: u128,
+    pub pool_ticks: PoolTicks,
+    pub slippage_tolerance: u64,
+}
+
+impl ZapInRequest {
+    pub fn new(
+        coin_a: Argument,
+        pool_arg: Argument,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        sqrt_price_x64: u128,
+        coin_a_amount: u64,
+        fee_rate: u64,
+        pool_liquidity: u128,
+        pool_ticks: PoolTicks,
+        slippage_tolerance: u64,
+    ) -> Self {
+        Self {
+            coin_a,
+            pool_arg,
+            coin_a_type,
+            coin_b_type,
+            tick_lower_index,
+            tick_upper_index,
+            sqrt_price_x64,
+            coin_a_amount,
+            fee_rate,
+            pool_liquidity,
+            pool_ticks,
+            slippage_tolerance,
+        }
+    }
+}
+
+pub struct AddLiquidityRequest {
+    pub position: Argument,
+    pub coin_a: Argument,
+    pub pool_arg: Argument,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub sqrt_price_x64: u128,
+    pub fee_rate: u64,
+    pub pool_liquidity: u128,
+    pub pool_ticks: PoolTicks,
+    pub coin_a_amount: u64,
+    pub slippage_tolerance: u64,
+}
+
+impl AddLiquidityRequest {
+    pub fn new(
+        position: Argument,
+        coin_a: Argument,
+        pool_arg: Argument,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        sqrt_price_x64: u128,
+        fee_rate: u64,
+        pool_liquidity: u128,
+        pool_ticks: PoolTicks,
+        coin_a_amount: u64,
+        slippage_tolerance: u64,
+    ) -> Self {
+        Self {
+            position,
+            coin_a,
+            pool_arg,
+            coin_a_type,
+            coin_b_type,
+            tick_lower_index,
+            tick_upper_index,
+            sqrt_price_x64,
+            fee_rate,
+            pool_liquidity,
+            pool_ticks,
+            coin_a_amount,
+            slippage_tolerance,
+        }
+    }
+
+    pub fn from_zap_in(position: Argument, request: &ZapInRequest) -> Self {
+        Self::new(
+            position,
+            request.coin_a,
+            request.pool_arg,
+            request.coin_a_type.clone(),
+            request.coin_b_type.clone(),
+            request.tick_lower_index,
+            request.tick_upper_index,
+            request.sqrt_price_x64,
+            request.fee_rate,
+            request.pool_liquidity,
+            request.pool_ticks.clone(),
+            request.coin_a_amount,
+            request.slippage_tolerance,
+        )
+    }
+}
+
+pub struct CompoundData {
+    pub position: Argument,
+    pub pool_arg: Argument,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub current_sqrt_price: u128,
+    pub fee_rate: u64,
+    pub pool_liquidity: u128,
+    pub pool_ticks: PoolTicks,
+    pub lp_slippage_tolerance_bps: u64,
+    pub coin_receipt: Argument,
+    pub rewarder_coin_types: Vec<TypeTag>,
+    pub pending_rewards: PendingRewards,
+}
+
+impl CompoundData {
+    pub fn new(
+        position: Argument,
+        pool_arg: Argument,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        current_sqrt_price: u128,
+        fee_rate: u64,
+        pool_liquidity: u128,
+        pool_ticks: PoolTicks,
+        lp_slippage_tolerance_bps: u64,
+        coin_receipt: Argument,
+        rewarder_coin_types: Vec<TypeTag>,
+        pending_rewards: PendingRewards,
+    ) -> Self {
+        Self {
+            position,
+            pool_arg,
+            coin_a_type,
+            coin_b_type,
+            tick_lower_index,
+            tick_upper_index,
+            current_sqrt_price,
+            fee_rate,
+            pool_liquidity,
+            pool_ticks,
+            lp_slippage_tolerance_bps,
+            coin_receipt,
+            rewarder_coin_types,
+            pending_rewards,
+        }
+    }
+}
+
+pub struct RebalanceData {
+    pub position: Argument,
+    pub pool_arg: Argument,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub current_tick_lower_index: i32,
+    pub current_tick_upper_index: i32,
+    pub current_sqrt_price: u128,
+    pub current_position_liquidity: u128,
+    pub new_tick_lower_index: i32,
+    pub new_tick_upper_index: i32,
+    pub fee_rate: u64,
+    pub pool_liquidity: u128,
+    pub pool_ticks: PoolTicks,
+    pub lp_slippage_tolerance_bps: u64,
+    pub coin_receipt: Argument,
+    pub rewarder_coin_types: Vec<TypeTag>,
+}
+
+impl RebalanceData {
+    pub fn new(
+        position: Argument,
+        pool_arg: Argument,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        current_tick_lower_index: i32,
+        current_tick_upper_index: i32,
+        current_sqrt_price: u128,
+        current_position_liquidity: u128,
+        new_tick_lower_index: i32,
+        new_tick_upper_index: i32,
+        fee_rate: u64,
+        pool_liquidity: u128,
+        pool_ticks: PoolTicks,
+        lp_slippage_tolerance_bps: u64,
+        coin_receipt: Argument,
+        rewarder_coin_types: Vec<TypeTag>,
+    ) -> Self {
+        Self {
+            position,
+            pool_arg,
+            coin_a_type,
+            coin_b_type,
+            current_tick_lower_index,
+            current_tick_upper_index,
+            current_sqrt_price,
+            current_position_liquidity,
+            new_tick_lower_index,
+            new_tick_upper_index,
+            fee_rate,
+            pool_liquidity,
+            pool_ticks,
+            lp_slippage_tolerance_bps,
+            coin_receipt,
+            rewarder_coin_types,
+        }
+    }
+}
+
+pub struct CollectFeesAndRewardsRequest {
+    pub position: Argument,
+    pub pool_arg: Argument,
+    pub coin_a_type: TypeTag,
+    pub coin_b_type: TypeTag,
+    pub rewarder_coin_types: Vec<TypeTag>,
+    pub is_collect_fees: bool,
+    pub is_collect_rewards: bool,
+}
+
+impl CollectFeesAndRewardsRequest {
+    pub fn new(
+        position: Argument,
+        pool_arg: Argument,
+        coin_a_type: TypeTag,
+        coin_b_type: TypeTag,
+        rewarder_coin_types: Vec<TypeTag>,
+        is_collect_fees: bool,
+        is_collect_rewards: bool,
+    ) -> Self {
+        Self {
+            position,
+            pool_arg,
+            coin_a_type,
+            coin_b_type,
+            rewarder_coin_types,
+            is_collect_fees,
+            is_collect_rewards,
+        }
+    }
+}
+
+pub struct CollectFeesAndRewardsResult {
+    pub coin_type: TypeTag,
+    pub coin: Argument,
+}
+
+pub struct SwapByAmountInRequest {
+    pub from: TypeTag,
+    pub to: TypeTag,
+    pub amount_in: u64,
+    pub coin_input: Argument,
+    pub route: Option<aggregator::cetus::RouteData>,
+    // applied to every hop of the route, in bps
+    pub slippage_tolerance: u64,
+}
+
+impl SwapByAmountInRequest {
+    pub fn new(from: TypeTag, to: TypeTag, amount_in: u64, coin_input: Argument, route: Option<aggregator::cetus::RouteData>, slippage_tolerance: u64) -> Self {
+        Self {
+            from,
+            to,
+            amount_in,
+            coin_input,
+            route,
+            slippage_tolerance,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Encoding of the attestation documents the NSM returns: a COSE_Sign1 structure
+//! whose CBOR payload is an `AttestationDoc`.
+
+use ciborium::Value;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// COSE algorithm identifier of ECDSA with SHA-384, the only one the NSM signs with.
+const COSE_ES384: i128 = -35;
+const COSE_SIGN1_TAG: u64 = 18;
+
+#[derive(Debug, Error)]
+#[error("Invalid COSE_Sign1 structure: {0}")]
+pub struct CoseError(pub String);
+
+pub type Result<T> = std::result::Result<T, CoseError>;
+
+/// Payload of an attestation document, see the Nitro Enclaves attestation spec.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationDoc {
+    pub module_id: String,
+    pub digest: String,
+    /// Milliseconds since the epoch at which the NSM produced the document.
+    pub timestamp: u64,
+    pub pcrs: BTreeMap<usize, ByteBuf>,
+    pub certificate: ByteBuf,
+    /// Root first, then intermediates down to the issuer of `certificate`.
+    pub cabundle: Vec<ByteBuf>,
+    pub public_key: Option<ByteBuf>,
+    pub user_data: Option<ByteBuf>,
+    pub nonce: Option<ByteBuf>,
+}
+
+/// The parts of a COSE_Sign1 structure the signature covers.
+pub struct CoseSign1 {
+    pub protected: Vec<u8>,
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl CoseSign1 {
+    /// Unsigned structure over `payload` with an ES384 protected header.
+    pub fn es384(payload: Vec<u8>) -> Result<Self> {
+        let header = Value::Map(vec![(
+            Value::Integer(1.into()),
+            Value::Integer((COSE_ES384 as i64).into()),
+        )]);
+        let mut protected = vec![];
+        ciborium::ser::into_writer(&header, &mut protected)
+            .map_err(|e| CoseError(e.to_string()))?;
+        Ok(Self {
+            protected,
+            payload,
+            signature: vec![],
+        })
+    }
+
+    /// Untagged CBOR array, as the NSM returns it.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let sign1 = Value::Array(vec![
+            Value::Bytes(self.protected.clone()),
+            Value::Map(vec![]),
+            Value::Bytes(self.payload.clone()),
+            Value::Bytes(self.signature.clone()),
+        ]);
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&sign1, &mut bytes).map_err(|e| CoseError(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Tagged or untagged structure, rejecting any algorithm but ES384.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let value: Value =
+            ciborium::de::from_reader(bytes).map_err(|e| CoseError(e.to_string()))?;
+        let value = match value {
+            Value::Tag(COSE_SIGN1_TAG, value) => *value,
+            value => value,
+        };
+        let Value::Array(items) = value else {
+            return Err(CoseError("not an array".to_string()));
+        };
+        let [Value::Bytes(protected), _unprotected, Value::Bytes(payload), Value::Bytes(signature)] =
+            <[Value; 4]>::try_from(items)
+                .map_err(|items| CoseError(format!("{} items", items.len())))?
+        else {
+            return Err(CoseError("unexpected item types".to_string()));
+        };
+
+        let header: Value = ciborium::de::from_reader(protected.as_slice())
+            .map_err(|e| CoseError(e.to_string()))?;
+        let algorithm = header
+            .as_map()
+            .and_then(|header| {
+                header
+                    .iter()
+                    .find(|(key, _)| key.as_integer() == Some(1.into()))
+            })
+            .and_then(|(_, algorithm)| algorithm.as_integer())
+            .map(i128::from);
+        if algorithm != Some(COSE_ES384) {
+            return Err(CoseError(format!("unsupported algorithm {:?}", algorithm)));
+        }
+        Ok(Self {
+            protected,
+            payload,
+            signature,
+        })
+    }
+
+    /// `Sig_structure` of RFC 8152 section 4.4, what the signature is computed over.
+    pub fn sig_structure(&self) -> Result<Vec<u8>> {
+        let structure = Value::Array(vec![
+            Value::Text("Signature1".to_string()),
+            Value::Bytes(self.protected.clone()),
+            Value::Bytes(vec![]),
+            Value::Bytes(self.payload.clone()),
+        ]);
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&structure, &mut bytes)
+            .map_err(|e| CoseError(e.to_string()))?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rejects_malformed_documents() {
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&Value::Array(vec![Value::Bytes(vec![])]), &mut bytes)
+            .unwrap();
+        assert!(CoseSign1::decode(&bytes).is_err());
+
+        // a well formed structure whose protected header names ES256 (-7)
+        let mut protected = vec![];
+        let header = Value::Map(vec![(Value::Integer(1.into()), Value::Integer((-7).into()))]);
+        ciborium::ser::into_writer(&header, &mut protected).unwrap();
+        let sign1 = Value::Tag(
+            COSE_SIGN1_TAG,
+            Box::new(Value::Array(vec![
+                Value::Bytes(protected),
+                Value::Map(vec![]),
+                Value::Bytes(vec![]),
+                Value::Bytes(vec![0; 96]),
+            ])),
+        );
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&sign1, &mut bytes).unwrap();
+        assert!(CoseSign1::decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut sign1 = CoseSign1::es384(b"payload".to_vec()).unwrap();
+        sign1.signature = vec![1; 96];
+        let decoded = CoseSign1::decode(&sign1.encode().unwrap()).unwrap();
+        assert_eq!(decoded.payload, b"payload".to_vec());
+        assert_eq!(decoded.signature, vec![1; 96]);
+        assert_eq!(decoded.sig_structure().unwrap(), sign1.sig_structure().unwrap());
+    }
+}
@@ -0,0 +1,131 @@
+//! Access to the Nitro Secure Module, or to a software stand-in outside an enclave.
+//!
+//! `NitroNsm` talks to `/dev/nsm` through the NSM driver. `MockNsm`, behind the `mock`
+//! feature, signs attestation documents with a certificate chain of its own and draws
+//! entropy from the OS RNG, so the attestation path can run on any Linux box.
+
+pub mod document;
+#[cfg(feature = "mock")]
+mod mock;
+
+#[cfg(feature = "mock")]
+pub use mock::MockNsm;
+use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
+use nsm_api::driver;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Number of PCRs the NSM reports.
+pub const PCR_COUNT: usize = 16;
+/// Length of a PCR, a SHA-384 digest.
+pub const PCR_LEN: usize = 48;
+
+#[derive(Debug, Error)]
+pub enum NsmError {
+    #[error("Failed to connect to NSM device")]
+    Unavailable,
+    #[error("Unexpected NSM response: {0}")]
+    UnexpectedResponse(String),
+    #[error("Mock NSM failure: {0}")]
+    Mock(String),
+}
+
+pub type Result<T> = std::result::Result<T, NsmError>;
+
+pub trait Nsm: Send + Sync {
+    /// Attestation document committing to the PCRs and to the given public key, user
+    /// data and nonce, serialized as COSE_Sign1.
+    fn attestation(
+        &self,
+        public_key: Option<Vec<u8>>,
+        user_data: Option<Vec<u8>>,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>>;
+
+    /// `len` bytes of entropy.
+    fn random(&self, len: usize) -> Result<Vec<u8>>;
+}
+
+/// Which NSM to use, `nitro` inside an enclave and `mock` everywhere else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NsmMode {
+    Nitro,
+    Mock,
+}
+
+impl FromStr for NsmMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nitro" => Ok(NsmMode::Nitro),
+            "mock" => Ok(NsmMode::Mock),
+            _ => Err(format!("Unknown NSM mode '{}'", s)),
+        }
+    }
+}
+
+/// The NSM device of the enclave, opened for the duration of each request.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NitroNsm;
+
+impl NitroNsm {
+    fn request(&self, request: NsmRequest) -> Result<NsmResponse> {
+        let fd = driver::nsm_init();
+        if fd < 0 {
+            return Err(NsmError::Unavailable);
+        }
+        let response = driver::nsm_process_request(fd, request);
+        driver::nsm_exit(fd);
+        Ok(response)
+    }
+}
+
+impl Nsm for NitroNsm {
+    fn attestation(
+        &self,
+        public_key: Option<Vec<u8>>,
+        user_data: Option<Vec<u8>>,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let request = NsmRequest::Attestation {
+            user_data: user_data.map(ByteBuf::from),
+            nonce: nonce.map(ByteBuf::from),
+            public_key: public_key.map(ByteBuf::from),
+        };
+        match self.request(request)? {
+            NsmResponse::Attestation { document } => Ok(document),
+            response => Err(NsmError::UnexpectedResponse(format!("{:?}", response))),
+        }
+    }
+
+    fn random(&self, len: usize) -> Result<Vec<u8>> {
+        let mut random = Vec::with_capacity(len);
+        while random.len() < len {
+            match self.request(NsmRequest::GetRandom)? {
+                NsmResponse::GetRandom { random: chunk } if !chunk.is_empty() => {
+                    random.extend_from_slice(&chunk)
+                }
+                response => return Err(NsmError::UnexpectedResponse(format!("{:?}", response))),
+            }
+        }
+        random.truncate(len);
+        Ok(random)
+    }
+}
+
+/// The NSM of `mode`, with `pcrs` reported by the mock.
+#[cfg_attr(not(feature = "mock"), allow(unused_variables))]
+pub fn from_mode(mode: NsmMode, pcrs: BTreeMap<usize, Vec<u8>>) -> Result<Box<dyn Nsm>> {
+    match mode {
+        NsmMode::Nitro => Ok(Box::new(NitroNsm)),
+        #[cfg(feature = "mock")]
+        NsmMode::Mock => Ok(Box::new(MockNsm::new(pcrs)?)),
+        #[cfg(not(feature = "mock"))]
+        NsmMode::Mock => Err(NsmError::Mock("built without the mock feature".to_string())),
+    }
+}
@@ -0,0 +1,193 @@
+//! Software NSM whose documents pass `verifier::verify` of `get_attestation_cli` against
+//! `MockNsm::root`, never against the AWS Nitro root.
+
+use crate::document::{AttestationDoc, CoseSign1};
+use crate::{Nsm, NsmError, Result, PCR_COUNT, PCR_LEN};
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{DerSignature, Signature, SigningKey};
+use p384::pkcs8::EncodePublicKey;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::der::Encode;
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::Validity;
+use x509_cert::Certificate;
+
+/// How long the mock certificates are valid for.
+const MOCK_VALIDITY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Software NSM with fixed PCRs, signing with a leaf certificate issued by a root
+/// generated on creation.
+pub struct MockNsm {
+    module_id: String,
+    pcrs: BTreeMap<usize, Vec<u8>>,
+    root: Certificate,
+    certificate: Certificate,
+    key: SigningKey,
+}
+
+impl MockNsm {
+    /// Mock reporting `pcrs`, PCRs left out are all zeros like those of a debug
+    /// enclave.
+    pub fn new(pcrs: BTreeMap<usize, Vec<u8>>) -> Result<Self> {
+        if let Some((index, pcr)) = pcrs
+            .iter()
+            .find(|(index, pcr)| **index >= PCR_COUNT || pcr.len() != PCR_LEN)
+        {
+            return Err(NsmError::Mock(format!(
+                "PCR{} must be one of the {} PCRs and {} bytes long, got {} bytes",
+                index,
+                PCR_COUNT,
+                PCR_LEN,
+                pcr.len()
+            )));
+        }
+        let pcrs = (0..PCR_COUNT)
+            .map(|index| {
+                let pcr = pcrs.get(&index).cloned().unwrap_or(vec![0; PCR_LEN]);
+                (index, pcr)
+            })
+            .collect();
+
+        let root_key = SigningKey::random(&mut OsRng);
+        let root_name = mock_name("mock-nitro-root")?;
+        let root = issue(Profile::Root, root_name.clone(), &root_key, &root_key)?;
+        let key = SigningKey::random(&mut OsRng);
+        let certificate = issue(
+            Profile::Leaf {
+                issuer: root_name,
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            mock_name("mock-nitro-enclave")?,
+            &key,
+            &root_key,
+        )?;
+        Ok(Self {
+            module_id: "i-mock-enc0000000000000000".to_string(),
+            pcrs,
+            root,
+            certificate,
+            key,
+        })
+    }
+
+    /// Root certificate the documents chain up to, what a verifier must trust in
+    /// place of the AWS Nitro root.
+    pub fn root(&self) -> &Certificate {
+        &self.root
+    }
+}
+
+impl Nsm for MockNsm {
+    fn attestation(
+        &self,
+        public_key: Option<Vec<u8>>,
+        user_data: Option<Vec<u8>>,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| NsmError::Mock(e.to_string()))?
+            .as_millis() as u64;
+        let doc = AttestationDoc {
+            module_id: self.module_id.clone(),
+            digest: "SHA384".to_string(),
+            timestamp,
+            pcrs: self
+                .pcrs
+                .iter()
+                .map(|(index, pcr)| (*index, ByteBuf::from(pcr.clone())))
+                .collect(),
+            certificate: ByteBuf::from(to_der(&self.certificate)?),
+            cabundle: vec![ByteBuf::from(to_der(&self.root)?)],
+            public_key: public_key.map(ByteBuf::from),
+            user_data: user_data.map(ByteBuf::from),
+            nonce: nonce.map(ByteBuf::from),
+        };
+        let mut payload = vec![];
+        ciborium::ser::into_writer(&doc, &mut payload)
+            .map_err(|e| NsmError::Mock(e.to_string()))?;
+
+        let mut sign1 = CoseSign1::es384(payload).map_err(|e| NsmError::Mock(e.to_string()))?;
+        let sig_structure = sign1
+            .sig_structure()
+            .map_err(|e| NsmError::Mock(e.to_string()))?;
+        let signature: Signature = self.key.sign(&sig_structure);
+        sign1.signature = signature.to_bytes().to_vec();
+        sign1.encode().map_err(|e| NsmError::Mock(e.to_string()))
+    }
+
+    fn random(&self, len: usize) -> Result<Vec<u8>> {
+        let mut random = vec![0; len];
+        OsRng
+            .try_fill_bytes(&mut random)
+            .map_err(|e| NsmError::Mock(e.to_string()))?;
+        Ok(random)
+    }
+}
+
+fn mock_name(common_name: &str) -> Result<Name> {
+    Name::from_str(&format!("CN={},O=Mock Nitro Enclaves", common_name))
+        .map_err(|e| NsmError::Mock(e.to_string()))
+}
+
+/// Certificate for `key` under `subject`, signed by `issuer_key`.
+fn issue(
+    profile: Profile,
+    subject: Name,
+    key: &SigningKey,
+    issuer_key: &SigningKey,
+) -> Result<Certificate> {
+    let mut serial = [0u8; 16];
+    OsRng.fill_bytes(&mut serial);
+    // a positive serial number, as RFC 5280 requires
+    serial[0] &= 0x7f;
+    let serial = SerialNumber::new(&serial).map_err(|e| NsmError::Mock(e.to_string()))?;
+    let validity = Validity::from_now(MOCK_VALIDITY).map_err(|e| NsmError::Mock(e.to_string()))?;
+    let spki = key
+        .verifying_key()
+        .to_public_key_der()
+        .map_err(|e| NsmError::Mock(e.to_string()))
+        .and_then(|der| {
+            SubjectPublicKeyInfoOwned::try_from(der.as_bytes())
+                .map_err(|e| NsmError::Mock(e.to_string()))
+        })?;
+    CertificateBuilder::new(profile, serial, validity, subject, spki, issuer_key)
+        .map_err(|e| NsmError::Mock(e.to_string()))?
+        .build::<DerSignature>()
+        .map_err(|e| NsmError::Mock(e.to_string()))
+}
+
+fn to_der(certificate: &Certificate) -> Result<Vec<u8>> {
+    certificate
+        .to_der()
+        .map_err(|e| NsmError::Mock(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mock_rejects_malformed_pcrs() {
+        assert!(MockNsm::new(BTreeMap::from([(0, vec![1; 32])])).is_err());
+        assert!(MockNsm::new(BTreeMap::from([(PCR_COUNT, vec![1; PCR_LEN])])).is_err());
+    }
+
+    #[test]
+    fn test_mock_random() {
+        let nsm = MockNsm::new(BTreeMap::new()).unwrap();
+        assert_eq!(nsm.random(0).unwrap().len(), 0);
+        let random = nsm.random(1000).unwrap();
+        assert_eq!(random.len(), 1000);
+        assert_ne!(random, nsm.random(1000).unwrap());
+    }
+}